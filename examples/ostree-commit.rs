@@ -4,7 +4,7 @@ extern crate log;
 extern crate serde_derive;
 extern crate serde_gvariant;
 
-use serde_gvariant::{errors, Variant};
+use serde_gvariant::{errors, VarDict};
 use std::{fs, io};
 
 /// An ostree commit object.
@@ -14,7 +14,7 @@ use std::{fs, io};
 /// https://github.com/ostreedev/ostree/blob/v2018.5/src/libostree/ostree-core.h#L137-L150
 #[derive(Debug, Deserialize, PartialEq, Serialize)]
 struct OstreeCommit {
-    metadata: Vec<(String, Variant)>,
+    metadata: VarDict,
     parent_checksum: Vec<u8>,
     related_objs: Vec<OstreeCommitRelated>,
     subject: String,
@@ -12,6 +12,7 @@ use std::{fs, io};
 /// Signature: "(uuua(ayay))"
 /// Original definition at:
 /// https://github.com/ostreedev/ostree/blob/v2018.5/src/libostree/ostree-core.h#L112-L126
+#[allow(dead_code)]
 #[derive(Debug, Deserialize)]
 struct OstreeDirmeta {
     uid: u32,                 // u - uid (big-endian)
@@ -21,6 +22,7 @@ struct OstreeDirmeta {
 }
 
 // An ostree xattr object, as embedded into dirmeta.
+#[allow(dead_code)]
 #[derive(Debug, Deserialize)]
 struct OstreeXattr {
     key: Vec<u8>,
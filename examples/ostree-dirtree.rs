@@ -12,6 +12,7 @@ use std::{fs, io};
 /// Signature: "(a(say)a(sayay))"
 /// Original definition at:
 /// https://github.com/ostreedev/ostree/blob/v2018.5/src/libostree/ostree-core.h#L128-L135
+#[allow(dead_code)]
 #[derive(Debug, Deserialize)]
 struct OstreeDirtree {
     files: Vec<OstreeDirtreeFile>,
@@ -19,6 +20,7 @@ struct OstreeDirtree {
 }
 
 // An ostree file object, as embedded into dirtree.
+#[allow(dead_code)]
 #[derive(Debug, Deserialize)]
 struct OstreeDirtreeFile {
     filename: String,
@@ -26,6 +28,7 @@ struct OstreeDirtreeFile {
 }
 
 // An ostree directory object, as embedded into dirtree.
+#[allow(dead_code)]
 #[derive(Debug, Deserialize)]
 struct OstreeDirtreeDir {
     dirname: String,
@@ -0,0 +1,247 @@
+//! Streaming access to large arrays.
+
+use crate::config::Config;
+use crate::de::{Deserializer, Input, IoReader, SeqDeAccess, SeqState};
+use crate::errors;
+use crate::framing;
+use crate::variant::ser::merge;
+use crate::variant_type::VariantType;
+use serde::de::SeqAccess;
+use std::fmt;
+use std::io::{self, Read, Seek, Write};
+use std::marker::PhantomData;
+
+/// Incremental writer of an array, one element at a time.
+///
+/// Elements are serialized and written as they are pushed: only their
+/// framing offsets are kept in memory, and written out by `finish`. The array
+/// starts at the position of the writer when created, which need not be
+/// seekable.
+#[derive(Debug)]
+pub struct ArrayWriter<W> {
+    writer: W,
+    options: Config,
+    elem_type: Option<VariantType>,
+    pos: u64,
+    len: u64,
+    ends: Vec<u64>,
+}
+
+impl<W> ArrayWriter<W>
+where
+    W: Write,
+{
+    /// Start an array on `writer`, using the default configuration.
+    pub fn new(writer: W) -> Self {
+        Self::with_config(writer, crate::config())
+    }
+
+    pub(crate) fn with_config(writer: W, options: Config) -> Self {
+        Self {
+            writer,
+            options,
+            elem_type: None,
+            pos: 0,
+            len: 0,
+            ends: vec![],
+        }
+    }
+
+    /// Serialize and append an element.
+    ///
+    /// All elements must be of the same type. If the configuration has a
+    /// signature, elements are serialized as its element type.
+    pub fn push<T>(&mut self, value: &T) -> errors::Result<()>
+    where
+        T: ?Sized + serde::Serialize,
+    {
+        let expected = match self.options.signature {
+            Some(VariantType::Vec(ref elem)) => Some((**elem).clone()),
+            _ => None,
+        };
+        let (mut buf, ty, _) = self
+            .options
+            .serialize_direct(value, expected)
+            .map_err(|e| e.index(self.len as usize))?;
+        let ty = match self.elem_type.take() {
+            None => ty,
+            // Same check as for values of dictionaries in `Variant` trees.
+            Some(prev) if merge(prev.clone(), ty.clone()) == merge(ty.clone(), prev.clone()) => {
+                merge(prev, ty)
+            }
+            Some(prev) => {
+                let err = errors::Error::mismatch(&prev, &ty);
+                self.elem_type = Some(prev);
+                return Err(err.index(self.len as usize));
+            }
+        };
+
+        // Elements start aligned, and fixed-size ones are padded to their size.
+        let padding = framing::align(self.pos as usize, ty.alignment()) as u64 - self.pos;
+        if let Some(size) = ty.fixed_size() {
+            buf.resize(size.max(buf.len()), 0x00);
+        }
+        self.writer.write_all(&vec![0x00; padding as usize])?;
+        self.writer.write_all(&buf)?;
+        self.pos += padding + buf.len() as u64;
+        self.len += 1;
+        if ty.fixed_size().is_none() {
+            self.ends.push(self.pos);
+        }
+        self.elem_type = Some(ty);
+        Ok(())
+    }
+
+    /// Return the number of elements written so far.
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    /// Return whether no element was written so far.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Return the type of the elements written so far, if any.
+    pub fn element_type(&self) -> Option<&VariantType> {
+        self.elem_type.as_ref()
+    }
+
+    /// Write the framing offsets, and return the underlying writer.
+    pub fn finish(mut self) -> errors::Result<W> {
+        let size = framing::offsets_width(self.pos, self.ends.len() as u64);
+        for end in &self.ends {
+            self.writer.write_all(&end.to_le_bytes()[..size])?;
+        }
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+}
+
+/// Lazy iterator over the elements of a serialized array.
+///
+/// Elements are located through the framing offsets, and deserialized one at
+/// a time: the array as a whole is never held in memory. Iteration stops after
+/// the first error.
+pub struct ArrayIter<'de, T> {
+    source: Source<'de>,
+    state: Option<SeqState>,
+    marker: PhantomData<fn() -> T>,
+}
+
+trait ReadSeek: Read + Seek {}
+
+impl<R: Read + Seek> ReadSeek for R {}
+
+enum Source<'de> {
+    Slice(Deserializer<io::Cursor<&'de [u8]>>),
+    Reader(Deserializer<IoReader<Box<dyn ReadSeek + 'de>>>),
+}
+
+impl<'de, T> ArrayIter<'de, T> {
+    /// Iterate over the array serialized in `bytes`, using the default configuration.
+    ///
+    /// Elements may borrow from `bytes`.
+    pub fn from_slice(bytes: &'de [u8]) -> errors::Result<Self>
+    where
+        T: serde::Deserialize<'de>,
+    {
+        Self::slice_with_config(bytes, crate::config())
+    }
+
+    /// Iterate over the array serialized from the current position of
+    /// `reader` to its end, using the default configuration.
+    pub fn from_reader<R>(reader: R) -> errors::Result<Self>
+    where
+        R: Read + Seek + 'de,
+        T: serde::de::DeserializeOwned,
+    {
+        Self::reader_with_config(reader, crate::config())
+    }
+
+    pub(crate) fn slice_with_config(bytes: &'de [u8], options: Config) -> errors::Result<Self> {
+        let mut top = Deserializer {
+            reader: io::Cursor::new(bytes),
+            options,
+            alignment: 1,
+        };
+        let state = start(&mut top)?;
+        Ok(Self {
+            source: Source::Slice(top),
+            state: Some(state),
+            marker: PhantomData,
+        })
+    }
+
+    pub(crate) fn reader_with_config<R>(reader: R, options: Config) -> errors::Result<Self>
+    where
+        R: Read + Seek + 'de,
+    {
+        let mut top = Deserializer {
+            reader: IoReader(Box::new(reader) as Box<dyn ReadSeek + 'de>),
+            options,
+            alignment: 1,
+        };
+        let state = start(&mut top)?;
+        Ok(Self {
+            source: Source::Reader(top),
+            state: Some(state),
+            marker: PhantomData,
+        })
+    }
+}
+
+/// Locate the array spanning the rest of the input.
+fn start<RS: Read + Seek>(top: &mut Deserializer<RS>) -> errors::Result<SeqState> {
+    let (start, end) = {
+        let cd = top.forward("array")?;
+        (cd.start, cd.end)
+    };
+    SeqState::new(top, start, end)
+}
+
+fn next_element<'de, RS, T>(
+    state: &mut SeqState,
+    top: &mut Deserializer<RS>,
+) -> errors::Result<Option<T>>
+where
+    RS: Input<'de>,
+    T: serde::Deserialize<'de>,
+{
+    let mut access = SeqDeAccess { state, top };
+    (&mut access).next_element()
+}
+
+impl<'de, T> Iterator for ArrayIter<'de, T>
+where
+    T: serde::Deserialize<'de>,
+{
+    type Item = errors::Result<T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let state = self.state.as_mut()?;
+        let res = match self.source {
+            Source::Slice(ref mut top) => next_element(state, top),
+            Source::Reader(ref mut top) => next_element(state, top),
+        };
+        match res {
+            Ok(Some(value)) => Some(Ok(value)),
+            Ok(None) => {
+                self.state = None;
+                None
+            }
+            Err(e) => {
+                self.state = None;
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+impl<T> fmt::Debug for ArrayIter<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ArrayIter")
+            .field("state", &self.state)
+            .finish_non_exhaustive()
+    }
+}
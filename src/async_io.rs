@@ -0,0 +1,56 @@
+//! Async I/O entry points, over `tokio` readers and writers.
+
+use crate::config::Config;
+use crate::errors;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+impl Config {
+    /// Deserializes an object from an async reader using this configuration.
+    ///
+    /// The whole input is buffered first, up to the configured maximum input
+    /// length.
+    pub async fn deserialize_async_read<R, T>(&self, reader: R) -> errors::Result<T>
+    where
+        R: AsyncRead + Unpin,
+        T: serde::de::DeserializeOwned,
+    {
+        let mut buf = vec![];
+        reader.take(self.read_limit()).read_to_end(&mut buf).await?;
+        self.check_input_len(&buf)?;
+        self.deserialize_slice(&buf)
+    }
+
+    /// Serializes a serializable object into an async writer using this configuration.
+    ///
+    /// The writer is flushed once the whole value has been written.
+    pub async fn serialize_async_write<W, T>(&self, writer: &mut W, value: &T) -> errors::Result<()>
+    where
+        W: AsyncWrite + Unpin,
+        T: ?Sized + serde::Serialize,
+    {
+        let buf = self.serialize(value)?;
+        writer.write_all(&buf).await?;
+        writer.flush().await?;
+        Ok(())
+    }
+}
+
+/// Deserializes an object from an async reader using the default configuration.
+///
+/// Input is buffered in memory first, see `Config::max_input_len`.
+pub async fn from_async_read<R, T>(reader: R) -> errors::Result<T>
+where
+    R: AsyncRead + Unpin,
+    T: serde::de::DeserializeOwned,
+{
+    crate::config().deserialize_async_read(reader).await
+}
+
+/// Serializes a serializable object into an async writer using the default configuration.
+pub async fn to_async_write<W, T>(writer: &mut W, value: &T) -> errors::Result<()>
+where
+    W: AsyncWrite + Unpin,
+    T: ?Sized + serde::Serialize,
+{
+    crate::config().serialize_async_write(writer, value).await
+}
@@ -1,22 +1,60 @@
-use crate::errors::{self, ResultExt};
+use crate::errors;
+use crate::io;
 use crate::variant;
-use serde;
-use std::io;
+use crate::variant::decode::Decoder;
+use crate::variant_type::VariantType;
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
+#[cfg(feature = "std")]
+use std::io::Read;
+#[cfg(all(feature = "std", unix))]
+use std::os::fd::OwnedFd;
+
+/// GVariant type of `char` values, which GVariant lacks.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CharType {
+    /// A `u`, holding the Unicode scalar value.
+    U32,
+    /// A `s`, holding a single character.
+    String,
+}
+
+/// GVariant type of `i8` values, which GVariant lacks.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum I8Type {
+    /// Widened to a `n`, and range-checked on the way back.
+    I16,
+    /// Rejected as unsupported.
+    Reject,
+}
 
 /// A configuration object whose settings will be used while
 /// serializing and deserializing.
 #[derive(Clone, Debug)]
 pub struct Config {
+    pub(crate) canonical: bool,
+    pub(crate) char_type: CharType,
+    pub(crate) i8_type: I8Type,
+    pub(crate) max_input_len: usize,
     pub(crate) max_string_len: usize,
     pub(crate) network_endian: bool,
+    pub(crate) signature: Option<VariantType>,
+    pub(crate) trailing_defaults: bool,
 }
 
 impl Config {
     /// Builds a new configuration object, with default settings.
     pub fn new() -> Config {
         Config {
+            canonical: false,
+            char_type: CharType::U32,
+            i8_type: I8Type::I16,
+            max_input_len: 128 * 1024 * 1024,
             max_string_len: 8192,
             network_endian: false,
+            signature: None,
+            trailing_defaults: false,
         }
     }
 
@@ -26,21 +64,188 @@ impl Config {
         cfg.network_endian = ne;
         cfg
     }
+
+    /// Sets whether to always serialize in normal form.
+    ///
+    /// Padding is always zeroed, framing offsets are always as narrow as
+    /// possible, and maps (e.g. `HashMap`) are always sorted by key. On top of
+    /// that, floats are then normalized (a single NaN, no negative zero), as
+    /// well as `Variant` values, so that equal values encode to the same
+    /// bytes. Layout is otherwise the same as without this setting.
+    pub fn canonical(self, enable: bool) -> Config {
+        let mut cfg = self;
+        cfg.canonical = enable;
+        cfg
+    }
+
+    /// Sets the GVariant type of `char` values.
+    pub fn char_type(self, ty: CharType) -> Config {
+        let mut cfg = self;
+        cfg.char_type = ty;
+        cfg
+    }
+
+    /// Sets the GVariant type of `i8` values.
+    pub fn i8_type(self, ty: I8Type) -> Config {
+        let mut cfg = self;
+        cfg.i8_type = ty;
+        cfg
+    }
+
+    /// Sets the maximum length (in bytes) of input buffered from non-seekable readers.
+    pub fn max_input_len(self, len: usize) -> Config {
+        let mut cfg = self;
+        cfg.max_input_len = len;
+        cfg
+    }
+
+    /// Sets whether structures may end before their trailing fields.
+    ///
+    /// When enabled, fields past the end of a shorter (older) structure are
+    /// missing, and take their `#[serde(default)]` value. This only works if
+    /// the older structure ends with a fixed-size field, otherwise framing
+    /// offsets do not line up: decode through the older signature with
+    /// `VariantRef::get` instead.
+    pub fn trailing_defaults(self, enable: bool) -> Config {
+        let mut cfg = self;
+        cfg.trailing_defaults = enable;
+        cfg
+    }
+
+    /// Sets the GVariant type of values, for self-describing decoding and exact encoding.
+    ///
+    /// When set, input is first decoded as a `Variant` of this type, so that
+    /// targets relying on `deserialize_any` (e.g. untagged enums or
+    /// `serde_json::Value`) work at any depth. Strings are then copied, not
+    /// borrowed. Otherwise, only `v` values describe their own type.
+    ///
+    /// Values are serialized as this type, which serde alone does not tell
+    /// for empty sequences and maps, nor for `None`. Values of another type
+    /// are rejected.
+    pub fn signature(self, ty: VariantType) -> Config {
+        let mut cfg = self;
+        cfg.signature = Some(ty);
+        cfg
+    }
 }
 
 impl Config {
     /// Serializes a serializable object into a `Vec` of bytes using this configuration
     pub fn serialize<T: ?Sized + serde::Serialize>(&self, t: &T) -> errors::Result<Vec<u8>> {
-        let mut buf = vec![];
-        {
-            let mut serializer = crate::ser::Serializer {
-                current_pos: 0,
-                writer: &mut buf,
-                options: self.clone(),
-            };
-            serde::Serialize::serialize(t, &mut serializer).chain_err(|| "failed to serialize")?;
+        self.serialize_direct(t, self.signature.clone())
+            .map(|(buf, _, _)| buf)
+    }
+
+    /// Serializes a serializable object, also returning the type signature of what was written.
+    ///
+    /// Serde does not tell the element types of empty sequences and maps, nor
+    /// the inner type of `None`: unless set through `signature`, these are
+    /// unknown and the value is rejected, as its signature would be wrong.
+    /// `Variant` trees do keep the types of their empty containers.
+    pub fn serialize_with_signature<T: ?Sized + serde::Serialize>(
+        &self,
+        t: &T,
+    ) -> errors::Result<(Vec<u8>, String)> {
+        let (buf, ty, guessed) = self.serialize_direct(t, self.signature.clone())?;
+        if guessed {
+            bail!(errors::Error::unsupported(alloc::format!(
+                "'{}', with unknown types of empty containers or None",
+                ty
+            )));
         }
-        Ok(buf)
+        Ok((buf, ty.to_string()))
+    }
+
+    /// Serializes `t` as the `expected` type, if known.
+    ///
+    /// Also returns the type of what was written, and whether parts of it
+    /// were guessed as `v` for lack of an expected type.
+    pub(crate) fn serialize_direct<T: ?Sized + serde::Serialize>(
+        &self,
+        t: &T,
+        expected: Option<VariantType>,
+    ) -> errors::Result<(Vec<u8>, VariantType, bool)> {
+        let mut buf = vec![];
+        let mut serializer = crate::ser::Serializer {
+            current_pos: 0,
+            writer: &mut buf,
+            options: self.clone(),
+            expected: None,
+            guessed: false,
+            alignment: 1,
+        };
+        let p = serializer.serialize_expected(t, expected)?;
+        let guessed = serializer.guessed;
+        Ok((buf, p.ty, guessed))
+    }
+
+    /// Serializes a serializable object, collecting the file descriptors of its `Fd` values.
+    ///
+    /// Descriptors are duplicated, and listed in the order of their handles.
+    #[cfg(all(feature = "std", unix))]
+    pub fn serialize_with_fds<T: ?Sized + serde::Serialize>(
+        &self,
+        t: &T,
+    ) -> errors::Result<(Vec<u8>, Vec<OwnedFd>)> {
+        crate::handle::collect_fds(|| self.serialize(t))
+    }
+
+    /// Deserializes a slice of bytes, whose `Fd` values are taken from `fds`.
+    ///
+    /// Descriptors which are not referred to are closed.
+    #[cfg(all(feature = "std", unix))]
+    pub fn deserialize_slice_with_fds<'a, T: serde::Deserialize<'a>>(
+        &self,
+        bytes: &'a [u8],
+        fds: Vec<OwnedFd>,
+    ) -> errors::Result<T> {
+        crate::handle::pass_fds(fds, || self.deserialize_slice(bytes))
+    }
+
+    /// Starts an array on `writer`, whose elements are serialized using this configuration.
+    #[cfg(feature = "std")]
+    pub fn array_writer<W: std::io::Write>(&self, writer: W) -> crate::ArrayWriter<W> {
+        crate::ArrayWriter::with_config(writer, self.clone())
+    }
+
+    /// Iterates lazily over the array serialized in `bytes`, using this configuration.
+    #[cfg(feature = "std")]
+    pub fn array_iter_slice<'a, T: serde::Deserialize<'a>>(
+        &self,
+        bytes: &'a [u8],
+    ) -> errors::Result<crate::ArrayIter<'a, T>> {
+        crate::ArrayIter::slice_with_config(bytes, self.clone())
+    }
+
+    /// Iterates lazily over the array serialized from a `Read`er, using this configuration.
+    #[cfg(feature = "std")]
+    pub fn array_iter_reader<'a, R, T>(&self, reader: R) -> errors::Result<crate::ArrayIter<'a, T>>
+    where
+        R: io::Read + io::Seek + 'a,
+        T: serde::de::DeserializeOwned,
+    {
+        crate::ArrayIter::reader_with_config(reader, self.clone())
+    }
+
+    /// Walks a serialized value of type `ty` using this configuration, calling back `visitor`.
+    pub fn walk<'a, V>(
+        &self,
+        bytes: &'a [u8],
+        ty: &VariantType,
+        visitor: &mut V,
+    ) -> errors::Result<()>
+    where
+        V: crate::GVariantVisitor<'a> + ?Sized,
+    {
+        let walker = crate::walk::Walker {
+            network_endian: self.network_endian,
+        };
+        walker.walk(bytes, ty, visitor)
+    }
+
+    /// Labels each byte of a value serialized as type `signature`, using this configuration.
+    pub fn explain<'a>(&self, bytes: &'a [u8], signature: &str) -> crate::Report<'a> {
+        crate::explain::explain(bytes, signature, self.network_endian)
     }
 
     /// Deserializes a slice of bytes into an instance of `T` using this configuration
@@ -48,47 +253,106 @@ impl Config {
         &self,
         bytes: &'a [u8],
     ) -> errors::Result<T> {
+        if let Some(ref ty) = self.signature {
+            return serde::Deserialize::deserialize(self.decode(bytes, ty)?);
+        }
         let reader = io::Cursor::new(bytes);
         let mut deserializer = crate::de::Deserializer {
             reader,
             options: self.clone(),
+            alignment: 1,
         };
         serde::Deserialize::deserialize(&mut deserializer)
-            .chain_err(|| "failed to deserialize slice")
+            .map_err(|e| e.at(deserializer.reader.position()))
     }
 
     /// Deserializes an object directly from a `Read`er using this configuration
+    #[cfg(feature = "std")]
     pub fn deserialize_reader<R: io::Read + io::Seek, T: serde::de::DeserializeOwned>(
         &self,
         reader: R,
     ) -> errors::Result<T> {
+        if let Some(ref ty) = self.signature {
+            let buf = self.read_limited(reader)?;
+            return serde::Deserialize::deserialize(self.decode(&buf, ty)?);
+        }
         //let mut deserializer = ::de::Deserializer::<R>::new(reader, &self);
         let mut deserializer = crate::de::Deserializer {
-            reader,
+            reader: crate::de::IoReader(reader),
             options: self.clone(),
+            alignment: 1,
         };
-        serde::Deserialize::deserialize(&mut deserializer)
-            .chain_err(|| "failed to deserialize reader")
+        match serde::Deserialize::deserialize(&mut deserializer) {
+            Ok(value) => Ok(value),
+            Err(e) => match io::Seek::stream_position(&mut deserializer.reader) {
+                Ok(pos) => Err(e.at(pos)),
+                Err(_) => Err(e),
+            },
+        }
+    }
+
+    /// Deserializes an object from a non-seekable `Read`er using this configuration.
+    ///
+    /// Framing offsets live at the end of containers, so the whole input is
+    /// buffered first, up to the configured maximum input length.
+    #[cfg(feature = "std")]
+    pub fn deserialize_read<R: io::Read, T: serde::de::DeserializeOwned>(
+        &self,
+        reader: R,
+    ) -> errors::Result<T> {
+        let buf = self.read_limited(reader)?;
+        self.deserialize_slice(&buf)
+    }
+
+    /// Read the whole input, up to the configured maximum input length.
+    #[cfg(feature = "std")]
+    fn read_limited<R: io::Read>(&self, reader: R) -> errors::Result<Vec<u8>> {
+        let mut buf = vec![];
+        reader.take(self.read_limit()).read_to_end(&mut buf)?;
+        self.check_input_len(&buf)?;
+        Ok(buf)
     }
 
-    pub fn deserialize_variant<T>(&self, value: &variant::Variant) -> errors::Result<T>
+    /// How many bytes to read from a reader, one more than the maximum input
+    /// length so that longer inputs can be told apart.
+    #[cfg(feature = "std")]
+    pub(crate) fn read_limit(&self) -> u64 {
+        (self.max_input_len as u64).saturating_add(1)
+    }
+
+    /// Check input read up to `read_limit` against the maximum input length.
+    #[cfg(feature = "std")]
+    pub(crate) fn check_input_len(&self, buf: &[u8]) -> errors::Result<()> {
+        if buf.len() > self.max_input_len {
+            bail!(errors::Error::limit(format!(
+                "input longer than {} bytes",
+                self.max_input_len
+            )));
+        }
+        Ok(())
+    }
+
+    /// Converts a serializable object into a `Variant` tree using this configuration.
+    pub fn serialize_variant<T>(&self, value: &T) -> errors::Result<variant::Variant>
     where
-        T: serde::de::DeserializeOwned,
+        T: ?Sized + serde::Serialize,
     {
-        let mut serializer = crate::ser::Serializer {
-            current_pos: 0,
-            writer: io::Cursor::new(vec![]),
-            options: self.clone(),
-        };
-        serde::Serialize::serialize(&value, &mut serializer)
-            .chain_err(|| "failed to serialize variant")?;
+        value.serialize(variant::ser::VariantSerializer::new(self))
+    }
 
-        let mut deserializer = crate::de::Deserializer {
-            reader: serializer.writer,
-            options: self.clone(),
+    /// Deserializes a `Variant` tree into an instance of `T`, without encoding it.
+    pub fn deserialize_variant<'a, T>(&self, value: &'a variant::Variant) -> errors::Result<T>
+    where
+        T: serde::Deserialize<'a>,
+    {
+        serde::Deserialize::deserialize(variant::de::VariantDeserializer::new(value, self))
+    }
+
+    fn decode(&self, bytes: &[u8], ty: &VariantType) -> errors::Result<variant::Variant> {
+        let decoder = Decoder {
+            network_endian: self.network_endian,
         };
-        serde::Deserialize::deserialize(&mut deserializer)
-            .chain_err(|| "failed to deserialize variant")
+        decoder.decode(bytes, ty)
     }
 }
 
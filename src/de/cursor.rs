@@ -1,11 +1,20 @@
-use crate::de::seq::SeqDeAccess;
+use crate::de::input::Input;
+use crate::de::seq::{MapDeAccess, SeqDeAccess, SeqState};
 use crate::de::some::SomeDeserializer;
 use crate::de::struc::StructDeAccess;
-use crate::de::variant::EnumDeAccess;
+use crate::de::util;
 use crate::errors;
-use byteorder::{BigEndian, LittleEndian, ReadBytesExt};
-use serde::de::{self, Error};
-use std::io;
+use crate::io;
+use crate::io::ReadBytesExt;
+use crate::variant::decode::Decoder;
+use crate::variant::ser::VARIANT_TOKEN;
+use crate::variant::Variant;
+use alloc::boxed::Box;
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec;
+use byteorder::{BigEndian, LittleEndian};
+use serde::de;
 
 #[derive(Debug)]
 pub(crate) struct CursorDeserializer<'a, RS: 'a> {
@@ -14,39 +23,48 @@ pub(crate) struct CursorDeserializer<'a, RS: 'a> {
     pub(crate) top: &'a mut crate::de::top::TopDeserializer<RS>,
 }
 
+impl<RS> CursorDeserializer<'_, RS> {
+    /// Report an invalid string, at its first invalid byte.
+    fn invalid_utf8(&self, err: core::str::Utf8Error) -> errors::Error {
+        errors::Error::from_kind(errors::ErrorKind::InvalidUtf8)
+            .at(self.start + err.valid_up_to() as u64)
+    }
+}
+
 impl<'de, 'a, RS> de::Deserializer<'de> for &'a mut CursorDeserializer<'a, RS>
 where
-    RS: io::Read + io::Seek,
+    RS: Input<'de>,
 {
     type Error = errors::Error;
 
-    fn deserialize_any<V>(self, _visitor: V) -> errors::Result<V::Value>
+    deserialize_mapped!(top.options);
+
+    // Without a known type, only a `v` describes itself.
+    fn deserialize_any<V>(self, visitor: V) -> errors::Result<V::Value>
     where
         V: de::Visitor<'de>,
     {
-        Err(Self::Error::custom("cursor: any not supported"))
+        self.boxed()?.deserialize_any(visitor)
     }
 
-    fn deserialize_bool<V>(self, visitor: V) -> errors::Result<V::Value>
+    // Skip the whole value, whatever its type.
+    fn deserialize_ignored_any<V>(self, visitor: V) -> errors::Result<V::Value>
     where
         V: de::Visitor<'de>,
     {
-        let byte = self.top.reader.read_u8()?;
-        self.start += 1;
-        let res = match byte {
-            0 => false,
-            _ => true,
-        };
-        visitor.visit_bool(res)
+        self.top.reader.seek(io::SeekFrom::Start(self.end))?;
+        self.start = self.end;
+        visitor.visit_unit()
     }
 
-    fn deserialize_i8<V>(self, visitor: V) -> errors::Result<V::Value>
+    fn deserialize_bool<V>(self, visitor: V) -> errors::Result<V::Value>
     where
         V: de::Visitor<'de>,
     {
-        let res = self.top.reader.read_i8()?;
+        let byte = self.top.reader.read_u8()?;
         self.start += 1;
-        visitor.visit_i8(res)
+        let res = byte != 0;
+        visitor.visit_bool(res)
     }
 
     fn deserialize_i16<V>(self, visitor: V) -> errors::Result<V::Value>
@@ -136,13 +154,6 @@ where
         visitor.visit_u64(res)
     }
 
-    fn deserialize_f32<V>(self, _visitor: V) -> errors::Result<V::Value>
-    where
-        V: de::Visitor<'de>,
-    {
-        Err(Self::Error::custom("cursor: unsupported f32"))
-    }
-
     fn deserialize_f64<V>(self, visitor: V) -> errors::Result<V::Value>
     where
         V: de::Visitor<'de>,
@@ -161,28 +172,40 @@ where
         V: de::Visitor<'de>,
     {
         let buflen = self.end.checked_sub(self.start).ok_or_else(|| {
-            Self::Error::custom(format!(
+            errors::Error::framing(format!(
                 "cursor: string length underflow - end={:#x}, start={:#x}",
                 self.end, self.start
             ))
         })? as usize;
         if buflen > self.top.options.max_string_len {
-            return Err(Self::Error::custom(format!(
-                "cursor: overlong string, length={}",
-                buflen
+            return Err(errors::Error::limit(format!(
+                "string longer than {} bytes",
+                self.top.options.max_string_len
             )));
         };
         if buflen == 0 {
             return visitor.visit_string("".to_string());
         };
 
+        // Memory-backed input lends its bytes, as long as they are valid UTF-8.
+        if let Some(buf) = self.top.reader.borrow_bytes(buflen)? {
+            let strlen = buf
+                .iter()
+                .position(|x| x == &b'\0')
+                .ok_or_else(|| errors::Error::framing("non-terminated string"))?;
+            let s = core::str::from_utf8(&buf[..strlen]).map_err(|e| self.invalid_utf8(e))?;
+            self.start += buflen as u64;
+            return visitor.visit_borrowed_str(s);
+        }
+
         let mut buf = vec![0; buflen];
         self.top.reader.read_exact(&mut buf)?;
         let strlen = buf
             .iter()
             .position(|x| x == &b'\0')
-            .ok_or_else(|| Self::Error::custom("cursor: non-terminated string"))?;
-        let s = String::from_utf8_lossy(&buf[..strlen]).into_owned();
+            .ok_or_else(|| errors::Error::framing("non-terminated string"))?;
+        buf.truncate(strlen);
+        let s = String::from_utf8(buf).map_err(|e| self.invalid_utf8(e.utf8_error()))?;
         trace!(
             "got string: buflen={:#x}, strlen={:#x}, string='{}'",
             buflen,
@@ -193,28 +216,45 @@ where
         visitor.visit_string(s)
     }
 
+    fn deserialize_str<V>(self, visitor: V) -> errors::Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        self.deserialize_string(visitor)
+    }
+
     fn deserialize_byte_buf<V>(self, visitor: V) -> errors::Result<V::Value>
     where
         V: de::Visitor<'de>,
     {
         let buflen = self.end.checked_sub(self.start).ok_or_else(|| {
-            Self::Error::custom(format!(
+            errors::Error::framing(format!(
                 "byte_buf length underflow - end={:#x}, start={:#x}",
                 self.end, self.start
             ))
         })? as usize;
+        self.start += buflen as u64;
+        if let Some(buf) = self.top.reader.borrow_bytes(buflen)? {
+            return visitor.visit_borrowed_bytes(buf);
+        }
         let mut buf = vec![0; buflen];
         self.top.reader.read_exact(&mut buf)?;
-        self.start += buflen as u64;
         visitor.visit_byte_buf(buf)
     }
 
+    fn deserialize_bytes<V>(self, visitor: V) -> errors::Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        self.deserialize_byte_buf(visitor)
+    }
+
     fn deserialize_option<V>(self, visitor: V) -> errors::Result<V::Value>
     where
         V: de::Visitor<'de>,
     {
         let buflen = self.end.checked_sub(self.start).ok_or_else(|| {
-            Self::Error::custom(format!(
+            errors::Error::framing(format!(
                 "cursor: option length underflow - end={:#x}, start={:#x}",
                 self.end, self.start
             ))
@@ -243,41 +283,30 @@ where
     where
         V: de::Visitor<'de>,
     {
-        let buflen = self
-            .end
-            .checked_sub(self.start)
-            .ok_or_else(|| Self::Error::custom("cursor: array length underflow"))?;
-
-        // If items are variable-sized, record where the last one ends.
-        // That is, where the framing offsets start.
-        let fstart = match buflen {
-            0 => 0u64,
-            _ => {
-                self.top.reader.seek(io::SeekFrom::End(-1))?;
-                let b = self.top.reader.read_u8()?;
-                self.top.reader.seek(io::SeekFrom::Start(self.start))?;
-                u64::from(b)
-            }
-        };
-
-        trace!(
-            "SeqDe: start={:#x}, end={:#x}, length={:#x}",
-            self.start,
-            self.end,
-            buflen,
-        );
+        let mut state = SeqState::new(self.top, self.start, self.end)?;
+        self.start = self.end;
         let mut sub = SeqDeAccess {
-            start: self.start,
-            end: self.end,
-            seq_framing_start: fstart,
-            seq_fixed_width: true,
-            seq_length: buflen,
+            state: &mut state,
             top: self.top,
         };
-        self.start += buflen as u64;
         visitor.visit_seq(&mut sub)
     }
 
+    // Dictionaries are arrays of entries.
+    fn deserialize_map<V>(self, visitor: V) -> errors::Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        let mut state = SeqState::new(self.top, self.start, self.end)?;
+        self.start = self.end;
+        let mut sub = MapDeAccess {
+            state: &mut state,
+            entry: None,
+            top: self.top,
+        };
+        visitor.visit_map(&mut sub)
+    }
+
     fn deserialize_tuple<V>(self, len: usize, visitor: V) -> errors::Result<V::Value>
     where
         V: de::Visitor<'de>,
@@ -297,7 +326,7 @@ where
     {
         trace!("tuple_struct -> seq");
         if len > 32 {
-            bail!("too many fields in tuple");
+            bail!(errors::Error::limit("tuple with more than 32 fields"));
         }
         let syn_fields = &[
             "0", "1", "2", "3", "4", "5", "6", "7", "8", "9", "10", "11", "12", "13", "14", "15",
@@ -319,7 +348,7 @@ where
         let buflen = self
             .end
             .checked_sub(self.start)
-            .ok_or_else(|| Self::Error::custom("option length underflow"))?;
+            .ok_or_else(|| errors::Error::framing("option length underflow"))?;
 
         trace!(
             "StructDe: name={}, num_fields={}, start={:#x}, end={:#x}, length={:#x}",
@@ -331,13 +360,17 @@ where
         );
         let mut sub = StructDeAccess {
             cur_field: 0,
+            base: self.start,
+            offset_size: util::compute_size(buflen),
             start: self.start,
-            end: buflen,
+            end: self.end,
+            exact_end: true,
+            variable: false,
             _name: name,
             fields,
             top: self.top,
         };
-        self.start += buflen as u64;
+        self.start += buflen;
         visitor.visit_seq(&mut sub)
     }
 
@@ -350,85 +383,83 @@ where
     where
         V: de::Visitor<'de>,
     {
-        let buflen = self
-            .end
-            .checked_sub(self.start)
-            .ok_or_else(|| Self::Error::custom("string length underflow"))?;
-        let mut payload_len = buflen;
-        let mut sig: Vec<u8> = Vec::new();
-        while payload_len > 0 {
-            payload_len -= 1;
-            let _cur = self
-                .top
-                .reader
-                .seek(io::SeekFrom::Start(self.start + payload_len))?;
-            let charsig = self.top.reader.read_u8()?;
-            if charsig == 0x00 {
-                break;
-            }
-            sig.insert(0, charsig);
+        if name != VARIANT_TOKEN {
+            return Err(errors::Error::unsupported("enum"));
         }
-        let _cur = self.top.reader.seek(io::SeekFrom::Start(self.start))?;
-
         trace!(
-            "EnumDe: name={}, sig={}, start={:#x}, end={:#x}, length={:#x}, payload_length={:#x}",
+            "EnumDe: name={}, start={:#x}, end={:#x}",
             name,
-            String::from_utf8_lossy(&sig),
             self.start,
             self.end,
-            buflen,
-            payload_len,
         );
-        let mut start = self.start;
-        let mut end = self.start + payload_len;
-        let mut fstart = 0u64;
-        let mut sub = EnumDeAccess {
-            cur_field: 0,
-            start: &mut start,
-            end: &mut end,
+        let content = self.boxed()?;
+        de::Deserializer::deserialize_enum(
+            Variant::Variant(Box::new(content)),
             name,
             variants,
-            top: self.top,
-            signature: sig,
-            seq_fixed_width: true,
-            seq_framing_start: &mut fstart,
-            seq_length: 0,
-            seq_start: self.start,
-        };
-        self.start += buflen as u64;
-        visitor.visit_enum(&mut sub)
+            visitor,
+        )
     }
 
     fn deserialize_unit_struct<V>(
         self,
         _name: &'static str,
-        _visitor: V,
+        visitor: V,
     ) -> Result<V::Value, Self::Error>
     where
         V: de::Visitor<'de>,
     {
-        Err(Self::Error::custom("cursor: unit_struct not supported"))
+        self.deserialize_unit(visitor)
     }
 
     fn deserialize_newtype_struct<V>(
         self,
         _name: &'static str,
-        _visitor: V,
+        visitor: V,
     ) -> Result<V::Value, Self::Error>
     where
         V: de::Visitor<'de>,
     {
-        Err(Self::Error::custom("cursor: newtype_struct not supported"))
+        visitor.visit_newtype_struct(self)
     }
 
-    fn deserialize_unit<V>(self, _visitor: V) -> Result<V::Value, Self::Error>
+    fn deserialize_unit<V>(self, visitor: V) -> Result<V::Value, Self::Error>
     where
         V: de::Visitor<'de>,
     {
-        Err(Self::Error::custom("cursor: unit not supported"))
+        // The unit structure is a single 0x00 byte.
+        self.top.reader.read_u8()?;
+        self.start += 1;
+        visitor.visit_unit()
     }
 
     forward_to_deserialize_any! {
-        identifier ignored_any map char bytes str
+        identifier
+    }
+}
+
+impl<'a, RS> CursorDeserializer<'a, RS>
+where
+    RS: io::Read + io::Seek,
+{
+    /// Decode the whole range as a `v`, guided by its own signature.
+    ///
+    /// Returns the boxed value.
+    fn boxed(&mut self) -> errors::Result<Variant> {
+        let buflen = self
+            .end
+            .checked_sub(self.start)
+            .ok_or_else(|| errors::Error::framing("cursor: variant length underflow"))?;
+        let mut buf = vec![0; buflen as usize];
+        self.top.reader.seek(io::SeekFrom::Start(self.start))?;
+        self.top.reader.read_exact(&mut buf)?;
+        let decoder = Decoder {
+            network_endian: self.top.options.network_endian,
+        };
+        let content = decoder
+            .decode_boxed(&buf)
+            .map_err(|e| e.shift(self.start))?;
+        self.start += buflen;
+        Ok(content)
     }
 }
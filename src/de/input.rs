@@ -0,0 +1,47 @@
+use crate::io;
+
+/// A seekable source of bytes for deserializers.
+///
+/// Memory-backed sources can lend out their bytes for the whole `'de`
+/// lifetime, so that strings and byte arrays are borrowed instead of copied.
+pub(crate) trait Input<'de>: io::Read + io::Seek {
+    /// Borrow the next `len` bytes and advance past them, if memory-backed.
+    fn borrow_bytes(&mut self, _len: usize) -> io::Result<Option<&'de [u8]>> {
+        Ok(None)
+    }
+}
+
+impl<'de> Input<'de> for io::Cursor<&'de [u8]> {
+    fn borrow_bytes(&mut self, len: usize) -> io::Result<Option<&'de [u8]>> {
+        let data: &'de [u8] = self.get_ref();
+        let start = self.position() as usize;
+        let end = start
+            .checked_add(len)
+            .filter(|end| *end <= data.len())
+            .ok_or_else(|| io::Error::from(io::ErrorKind::UnexpectedEof))?;
+        self.set_position(end as u64);
+        Ok(Some(&data[start..end]))
+    }
+}
+
+/// A generic reader, whose bytes are always copied out.
+#[cfg(feature = "std")]
+#[derive(Debug)]
+pub(crate) struct IoReader<R>(pub(crate) R);
+
+#[cfg(feature = "std")]
+impl<R: io::Read> io::Read for IoReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.0.read(buf)
+    }
+}
+
+#[cfg(feature = "std")]
+impl<R: io::Seek> io::Seek for IoReader<R> {
+    fn seek(&mut self, pos: io::SeekFrom) -> io::Result<u64> {
+        self.0.seek(pos)
+    }
+}
+
+#[cfg(feature = "std")]
+impl<'de, R: io::Read + io::Seek> Input<'de> for IoReader<R> {}
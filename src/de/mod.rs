@@ -1,9 +1,54 @@
+/// Implement `deserialize_i8`, `deserialize_f32` and `deserialize_char`,
+/// for Rust types without a GVariant counterpart, as mapped by the `Config`
+/// at the given field path.
+macro_rules! deserialize_mapped {
+    ($($options:ident).+) => {
+        fn deserialize_i8<V>(self, visitor: V) -> errors::Result<V::Value>
+        where
+            V: de::Visitor<'de>,
+        {
+            match self.$($options).+.i8_type {
+                crate::config::I8Type::I16 => {
+                    self.deserialize_i16(crate::de::util::I8Visitor(visitor))
+                }
+                crate::config::I8Type::Reject => Err(errors::Error::unsupported("i8")),
+            }
+        }
+
+        fn deserialize_f32<V>(self, visitor: V) -> errors::Result<V::Value>
+        where
+            V: de::Visitor<'de>,
+        {
+            self.deserialize_f64(crate::de::util::F32Visitor(visitor))
+        }
+
+        fn deserialize_char<V>(self, visitor: V) -> errors::Result<V::Value>
+        where
+            V: de::Visitor<'de>,
+        {
+            match self.$($options).+.char_type {
+                crate::config::CharType::U32 => {
+                    self.deserialize_u32(crate::de::util::CharVisitor(visitor))
+                }
+                crate::config::CharType::String => {
+                    self.deserialize_str(crate::de::util::CharVisitor(visitor))
+                }
+            }
+        }
+    };
+}
+
 mod cursor;
+mod input;
 mod seq;
 mod some;
 mod struc;
 mod top;
 mod util;
-mod variant;
 
+#[cfg(feature = "std")]
+pub(crate) use self::input::{Input, IoReader};
+#[cfg(feature = "std")]
+pub(crate) use self::seq::{SeqDeAccess, SeqState};
 pub(crate) use self::top::TopDeserializer as Deserializer;
+pub(crate) use self::util::{CharVisitor, F32Visitor, I8Visitor};
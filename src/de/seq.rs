@@ -1,23 +1,116 @@
 use crate::errors;
-use byteorder::ReadBytesExt;
-use serde::de::{self, Error};
-use std::io;
+use crate::io;
+use alloc::format;
+use serde::de;
 
 use crate::de::cursor::CursorDeserializer;
+use crate::de::input::Input;
+use crate::de::struc;
 use crate::de::util;
 
-pub(crate) struct SeqDeAccess<'a, RS: 'a> {
+/// Position of a walk through the elements of an array.
+#[derive(Debug)]
+pub(crate) struct SeqState {
     pub(crate) start: u64,
     pub(crate) end: u64,
+    pub(crate) base: u64,
+    pub(crate) offset_size: u64,
     pub(crate) seq_framing_start: u64,
     pub(crate) seq_fixed_width: bool,
-    pub(crate) seq_length: u64,
+    /// Alignment of the elements, learned from those decoded so far.
+    pub(crate) alignment: u64,
+    pub(crate) index: usize,
+}
+
+impl SeqState {
+    /// Start walking the array in `start..end`.
+    pub(crate) fn new<RS>(
+        top: &mut crate::de::top::TopDeserializer<RS>,
+        start: u64,
+        end: u64,
+    ) -> errors::Result<Self>
+    where
+        RS: io::Read + io::Seek,
+    {
+        let buflen = end
+            .checked_sub(start)
+            .ok_or_else(|| errors::Error::framing("cursor: array length underflow"))?;
+
+        // If items are variable-sized, record where the last one ends.
+        // That is, where the framing offsets start.
+        let offset_size = util::compute_size(buflen);
+        let fstart = match buflen {
+            0 => start,
+            _ => {
+                let off = util::read_len(top, start, end, offset_size)?;
+                start.saturating_add(off)
+            }
+        };
+
+        trace!(
+            "SeqDe: start={:#x}, end={:#x}, length={:#x}",
+            start,
+            end,
+            buflen,
+        );
+        Ok(SeqState {
+            start,
+            end,
+            base: start,
+            offset_size,
+            seq_framing_start: fstart,
+            seq_fixed_width: true,
+            alignment: 1,
+            index: 0,
+        })
+    }
+
+    /// Whether all elements were walked through.
+    fn is_done(&self) -> bool {
+        // Stop conditions:
+        //   1. fixed-width entries: EOF reached
+        if self.start == self.end {
+            trace!("got fixed width array: len={}", self.index);
+            return true;
+        }
+        //   2. variable-width entries: all framings processed
+        if !self.seq_fixed_width && self.seq_framing_start >= self.end {
+            trace!("got variable width array: len={}", self.index);
+            return true;
+        }
+        false
+    }
+
+    /// Move to the start of the next element, aligned like the first one.
+    fn align(&mut self) {
+        self.start += (self.alignment - (self.start % self.alignment)) % self.alignment;
+    }
+
+    /// Check that the element decoded at `start` is aligned as it turned out
+    /// to need, and learn its `alignment`.
+    ///
+    /// The padding before an element is told by the first one: a later one
+    /// that needs more was misplaced, and its fields misread.
+    fn learn_alignment(&mut self, start: u64, alignment: u64) -> errors::Result<()> {
+        if self.index > 0 && !start.is_multiple_of(alignment) {
+            return Err(errors::Error::unsupported(format!(
+                "array element aligned to {} after a first one aligned to {}, without a signature",
+                alignment, self.alignment
+            )));
+        }
+        self.alignment = self.alignment.max(alignment);
+        Ok(())
+    }
+}
+
+pub(crate) struct SeqDeAccess<'a, RS: 'a> {
+    pub(crate) state: &'a mut SeqState,
     pub(crate) top: &'a mut crate::de::top::TopDeserializer<RS>,
 }
 
 impl<'a, 'de, RS> de::SeqAccess<'de> for &'a mut SeqDeAccess<'a, RS>
 where
-    RS: io::Read + io::Seek,
+    RS: Input<'de>,
 {
     type Error = errors::Error;
 
@@ -25,113 +118,273 @@ where
     where
         T: de::DeserializeSeed<'de>,
     {
-        // Stop conditions:
-        //   1. fixed-width entries: EOF reached
-        if self.start == self.end {
-            trace!("got fixed width array: len={}", self.seq_length);
-            return Ok(None);
-        }
-        //   2. variable-width entries: all framings processed
-        if !self.seq_fixed_width && self.seq_framing_start == self.seq_length {
-            trace!("got variable width array: len={}", self.seq_length);
+        let state = &mut *self.state;
+        if state.is_done() {
             return Ok(None);
         }
+        state.align();
 
         // Deserialize next element
         trace!(
-            "accessing array element: cur_start={:#x}, end={:#x} seq_length={:#x}",
-            self.start,
-            self.end,
-            self.seq_length
+            "accessing array element: cur_start={:#x}, end={:#x} index={}",
+            state.start,
+            state.end,
+            state.index
         );
+        let (elem_start, index) = (state.start, state.index);
+        let outer = core::mem::replace(&mut self.top.alignment, 1);
         let mut seq_de = SeqDeserializer {
-            start: &mut self.start,
-            end: &mut self.end,
-            seq_framing_start: &mut self.seq_framing_start,
-            seq_length: &mut self.seq_length,
-            seq_fixed_width: &mut self.seq_fixed_width,
-            top: &mut self.top,
+            start: &mut state.start,
+            end: &mut state.end,
+            base: state.base,
+            offset_size: state.offset_size,
+            seq_framing_start: &mut state.seq_framing_start,
+            seq_fixed_width: &mut state.seq_fixed_width,
+            top: self.top,
         };
-        let v = de::DeserializeSeed::deserialize(seed, &mut seq_de)?;
+        let v = de::DeserializeSeed::deserialize(seed, &mut seq_de)
+            .map_err(|e| e.at(elem_start).index(index))?;
+        state
+            .learn_alignment(elem_start, self.top.alignment)
+            .map_err(|e| e.at(elem_start).index(index))?;
+        self.top.alignment = outer.max(state.alignment);
+        state.index += 1;
         Ok(Some(v))
     }
 }
 
+/// Walk through the entries of a dictionary, an array of `{kv}` structures.
+pub(crate) struct MapDeAccess<'a, RS: 'a> {
+    pub(crate) state: &'a mut SeqState,
+    /// Entry whose key was decoded, where it ends, and the outer alignment.
+    pub(crate) entry: Option<(struc::Entry, u64, u64)>,
+    pub(crate) top: &'a mut crate::de::top::TopDeserializer<RS>,
+}
+
+impl<'a, 'de, RS> de::MapAccess<'de> for &'a mut MapDeAccess<'a, RS>
+where
+    RS: Input<'de>,
+{
+    type Error = errors::Error;
+
+    fn next_key_seed<K>(&mut self, seed: K) -> errors::Result<Option<K::Value>>
+    where
+        K: de::DeserializeSeed<'de>,
+    {
+        let state = &mut *self.state;
+        if state.is_done() {
+            return Ok(None);
+        }
+        state.align();
+
+        // Read the end ahead, and only consume it for a framed entry.
+        let (start, index) = (state.start, state.index);
+        let framing_end = state.seq_framing_start.saturating_add(state.offset_size);
+        let end = util::read_len(self.top, start, framing_end, state.offset_size)
+            .ok()
+            .and_then(|val| state.base.checked_add(val))
+            .unwrap_or(state.end);
+        trace!(
+            "accessing dictionary entry: cur_start={:#x}, end={:#x} index={}",
+            start,
+            end,
+            index
+        );
+        let outer = core::mem::replace(&mut self.top.alignment, 1);
+        let mut entry = struc::Entry::new(start, end);
+        let key = entry.field(self.top, 0, seed).map_err(|e| e.index(index))?;
+        self.entry = Some((entry, end, outer));
+        Ok(Some(key))
+    }
+
+    fn next_value_seed<V>(&mut self, seed: V) -> errors::Result<V::Value>
+    where
+        V: de::DeserializeSeed<'de>,
+    {
+        let (mut entry, end, outer) = self
+            .entry
+            .take()
+            .ok_or_else(|| errors::Error::from("dictionary value without key"))?;
+        let state = &mut *self.state;
+        let index = state.index;
+        let value = entry.field(self.top, 1, seed).map_err(|e| e.index(index))?;
+        let alignment = self.top.alignment;
+        let start = state.start;
+        state
+            .learn_alignment(start, alignment)
+            .map_err(|e| e.at(start).index(index))?;
+        state.start = match entry.layout(alignment) {
+            struc::Layout::Fixed(end) => {
+                state.seq_fixed_width = true;
+                end
+            }
+            struc::Layout::Variable => {
+                state.seq_fixed_width = false;
+                state.seq_framing_start += state.offset_size;
+                if end < state.start {
+                    bail!(errors::Error::framing("array: entry length underflow"));
+                }
+                end
+            }
+        };
+        self.top.alignment = outer.max(state.alignment);
+        state.index += 1;
+        Ok(value)
+    }
+}
+
 // A Deserializer specialized on array, with custom logic
 // for non-fized-size ones.
 pub(crate) struct SeqDeserializer<'a, RS: 'a> {
     pub(crate) start: &'a mut u64,
     pub(crate) end: &'a mut u64,
+    pub(crate) base: u64,
+    pub(crate) offset_size: u64,
     pub(crate) seq_framing_start: &'a mut u64,
     pub(crate) seq_fixed_width: &'a mut bool,
-    pub(crate) seq_length: &'a mut u64,
     pub(crate) top: &'a mut crate::de::top::TopDeserializer<RS>,
 }
 
-impl<'de, 'a, RS> de::Deserializer<'de> for &'a mut SeqDeserializer<'a, RS>
+impl<'a, RS> SeqDeserializer<'a, RS>
 where
     RS: io::Read + io::Seek,
+{
+    /// Locate a basic fixed-size element, aligned to its size, and move past it.
+    ///
+    /// The first element also aligns the start of the array, which depends on
+    /// the type of its elements.
+    fn fixed_element(&mut self, size: u64) -> errors::Result<u64> {
+        *self.seq_fixed_width = true;
+        let start = *self.start + (size - (*self.start % size)) % size;
+        if start + size > *self.end {
+            bail!(errors::Error::framing("array: element past the end"));
+        }
+        if start != *self.start {
+            self.top.reader.seek(io::SeekFrom::Start(start))?;
+        }
+        self.top.alignment = self.top.alignment.max(size);
+        *self.start = start + size;
+        Ok(start)
+    }
+
+    /// Locate a variable-sized element, and move past it.
+    ///
+    /// Element ends are framed in order, relative to the start of the array.
+    /// Returns the element start and end positions.
+    fn variable_element(&mut self, kind: &str) -> errors::Result<(u64, u64)> {
+        *self.seq_fixed_width = false;
+
+        let start = *self.start;
+        let framing_end = self.seq_framing_start.saturating_add(self.offset_size);
+        let val = util::read_len(self.top, start, framing_end, self.offset_size)?;
+        *self.seq_framing_start = framing_end;
+        let end = self
+            .base
+            .checked_add(val)
+            .ok_or_else(|| errors::Error::framing("array: framing offset overflow"))?;
+        let buflen = end
+            .checked_sub(start)
+            .ok_or_else(|| errors::Error::framing(format!("array: {} length underflow", kind)))?;
+        trace!("{}: start={}, end={}, buflen={}", kind, start, end, buflen);
+
+        // Update position to prepare for next element
+        *self.start = end;
+        Ok((start, end))
+    }
+}
+
+impl<'de, 'a, RS> de::Deserializer<'de> for &'a mut SeqDeserializer<'a, RS>
+where
+    RS: Input<'de>,
 {
     type Error = errors::Error;
 
-    // Unsupported
-    fn deserialize_any<V>(self, _visitor: V) -> errors::Result<V::Value>
+    deserialize_mapped!(top.options);
+
+    // Without a known type, only a `v` describes itself.
+    fn deserialize_any<V>(self, visitor: V) -> errors::Result<V::Value>
     where
         V: de::Visitor<'de>,
     {
-        Err(Self::Error::custom("unsupported"))
+        self.top.alignment = self.top.alignment.max(8);
+        let (start, end) = self.variable_element("variant")?;
+        let mut top = CursorDeserializer {
+            start,
+            end,
+            top: &mut *self.top,
+        };
+        top.deserialize_any(visitor)
     }
+
+    // Without a known type, the size of the element is unknown.
+    fn deserialize_ignored_any<V>(self, _visitor: V) -> errors::Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        Err(errors::Error::unsupported(
+            "ignored element without a signature",
+        ))
+    }
+
     forward_to_deserialize_any! {
-        f32 identifier ignored_any
+        identifier
     }
 
     forward_to_deserialize_any! {
-            char str bytes byte_buf
-            unit unit_struct map
-            option newtype_struct
+            bytes byte_buf
+            option
     }
 
-    // Fixed size
-    fn deserialize_bool<V>(self, visitor: V) -> errors::Result<V::Value>
+    fn deserialize_newtype_struct<V>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> errors::Result<V::Value>
     where
         V: de::Visitor<'de>,
     {
-        *self.seq_fixed_width = true;
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_unit_struct<V>(self, _name: &'static str, visitor: V) -> errors::Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        self.deserialize_unit(visitor)
+    }
 
-        let cur = *self.start;
-        *self.start += 1;
+    // Fixed size
+    fn deserialize_unit<V>(self, visitor: V) -> errors::Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        let cur = self.fixed_element(1)?;
         let mut top = CursorDeserializer {
             start: cur,
             end: *self.end,
             top: &mut *self.top,
         };
-        top.deserialize_bool(visitor)
+        top.deserialize_unit(visitor)
     }
 
-    fn deserialize_i8<V>(self, visitor: V) -> errors::Result<V::Value>
+    fn deserialize_bool<V>(self, visitor: V) -> errors::Result<V::Value>
     where
         V: de::Visitor<'de>,
     {
-        *self.seq_fixed_width = true;
-
-        let cur = *self.start;
-        *self.start += 1;
+        let cur = self.fixed_element(1)?;
         let mut top = CursorDeserializer {
             start: cur,
             end: *self.end,
             top: &mut *self.top,
         };
-        top.deserialize_i8(visitor)
+        top.deserialize_bool(visitor)
     }
 
     fn deserialize_u8<V>(self, visitor: V) -> errors::Result<V::Value>
     where
         V: de::Visitor<'de>,
     {
-        *self.seq_fixed_width = true;
-
-        let cur = *self.start;
-        *self.start += 1;
+        let cur = self.fixed_element(1)?;
         let mut top = CursorDeserializer {
             start: cur,
             end: *self.end,
@@ -144,10 +397,7 @@ where
     where
         V: de::Visitor<'de>,
     {
-        *self.seq_fixed_width = true;
-
-        let cur = *self.start;
-        *self.start += 2;
+        let cur = self.fixed_element(2)?;
         let mut top = CursorDeserializer {
             start: cur,
             end: *self.end,
@@ -160,10 +410,7 @@ where
     where
         V: de::Visitor<'de>,
     {
-        *self.seq_fixed_width = true;
-
-        let cur = *self.start;
-        *self.start += 2;
+        let cur = self.fixed_element(2)?;
         let mut top = CursorDeserializer {
             start: cur,
             end: *self.end,
@@ -176,10 +423,7 @@ where
     where
         V: de::Visitor<'de>,
     {
-        *self.seq_fixed_width = true;
-
-        let cur = *self.start;
-        *self.start += 4;
+        let cur = self.fixed_element(4)?;
         let mut top = CursorDeserializer {
             start: cur,
             end: *self.end,
@@ -192,10 +436,7 @@ where
     where
         V: de::Visitor<'de>,
     {
-        *self.seq_fixed_width = true;
-
-        let cur = *self.start;
-        *self.start += 4;
+        let cur = self.fixed_element(4)?;
         let mut top = CursorDeserializer {
             start: cur,
             end: *self.end,
@@ -208,10 +449,7 @@ where
     where
         V: de::Visitor<'de>,
     {
-        *self.seq_fixed_width = true;
-
-        let cur = *self.start;
-        *self.start += 8;
+        let cur = self.fixed_element(8)?;
         let mut top = CursorDeserializer {
             start: cur,
             end: *self.end,
@@ -224,10 +462,7 @@ where
     where
         V: de::Visitor<'de>,
     {
-        *self.seq_fixed_width = true;
-
-        let cur = *self.start;
-        *self.start += 8;
+        let cur = self.fixed_element(8)?;
         let mut top = CursorDeserializer {
             start: cur,
             end: *self.end,
@@ -240,10 +475,7 @@ where
     where
         V: de::Visitor<'de>,
     {
-        *self.seq_fixed_width = true;
-
-        let cur = *self.start;
-        *self.start += 8;
+        let cur = self.fixed_element(8)?;
         let mut top = CursorDeserializer {
             start: cur,
             end: *self.end,
@@ -257,53 +489,46 @@ where
     where
         V: de::Visitor<'de>,
     {
-        *self.seq_fixed_width = false;
-
-        let start = *self.start;
-        self.top
-            .reader
-            .seek(io::SeekFrom::Start(*self.seq_framing_start))?;
-        let end = u64::from(self.top.reader.read_u8()?);
-        *self.seq_framing_start = self.seq_framing_start.saturating_add(1);
-        let buflen = end
-            .checked_sub(start)
-            .ok_or_else(|| Self::Error::custom("array: string length underflow"))?;
-        trace!("string: start={}, end={}, buflen={}", start, end, buflen);
-
-        *self.start += buflen;
-        self.top.reader.seek(io::SeekFrom::Start(start))?;
+        let (start, end) = self.variable_element("string")?;
         let mut top = CursorDeserializer {
             start,
             end,
             top: &mut *self.top,
         };
-        let value = top.deserialize_string(visitor)?;
-        Ok(value)
+        top.deserialize_string(visitor)
+    }
+
+    fn deserialize_str<V>(self, visitor: V) -> errors::Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        self.deserialize_string(visitor)
     }
 
     fn deserialize_seq<V>(self, visitor: V) -> errors::Result<V::Value>
     where
         V: de::Visitor<'de>,
     {
-        *self.seq_fixed_width = false;
+        let (start, end) = self.variable_element("array")?;
+        let mut top = CursorDeserializer {
+            start,
+            end,
+            top: &mut *self.top,
+        };
+        top.deserialize_seq(visitor)
+    }
 
-        let start = *self.start;
-        self.top.reader.seek(io::SeekFrom::End(-1))?;
-        let end = u64::from(self.top.reader.read_u8()?);
-        *self.seq_length = self.seq_length.saturating_sub(1);
-        self.top.reader.seek(io::SeekFrom::Start(start))?;
-        let buflen = end
-            .checked_sub(start)
-            .ok_or_else(|| Self::Error::custom("array: array length underflow"))?;
-        trace!("seq: len={}", buflen);
-        *self.start += buflen;
+    fn deserialize_map<V>(self, visitor: V) -> errors::Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        let (start, end) = self.variable_element("dictionary")?;
         let mut top = CursorDeserializer {
             start,
             end,
             top: &mut *self.top,
         };
-        let v = top.deserialize_seq(visitor)?;
-        Ok(v)
+        top.deserialize_map(visitor)
     }
 
     fn deserialize_struct<V>(
@@ -315,35 +540,29 @@ where
     where
         V: de::Visitor<'de>,
     {
-        *self.seq_fixed_width = false;
-
-        let seq_start = *self.start;
-        let seq_end = *self.end;
-        let seq_len = seq_end
-            .checked_sub(seq_start)
-            .ok_or_else(|| Self::Error::custom("seq: length underflow"))?;
-        let (struct_end, size) = util::read_len(self.top, seq_start, seq_end, seq_len)?;
-        let buflen = struct_end
-            .checked_sub(seq_start)
-            .ok_or_else(|| Self::Error::custom("array: struct length underflow"))?;
-
-        // Update cursor for next element.
-        *self.end -= size;
-        *self.start += buflen;
-
-        // Deserialize struct.
-        trace!(
-            "struct: start={:#x}, end={:#x}, len={:#x}",
-            seq_start,
-            struct_end,
-            buflen
-        );
-        let mut top = CursorDeserializer {
-            start: seq_start,
-            end: struct_end,
-            top: &mut *self.top,
+        // Read the end ahead, and only consume it for a framed structure.
+        let start = *self.start;
+        let framing_end = self.seq_framing_start.saturating_add(self.offset_size);
+        let end = util::read_len(self.top, start, framing_end, self.offset_size)
+            .ok()
+            .and_then(|val| self.base.checked_add(val))
+            .unwrap_or(*self.end);
+        let (value, layout) = struc::nested(self.top, start, end, name, fields, visitor)?;
+        *self.start = match layout {
+            struc::Layout::Fixed(end) => {
+                *self.seq_fixed_width = true;
+                end
+            }
+            struc::Layout::Variable => {
+                *self.seq_fixed_width = false;
+                *self.seq_framing_start = framing_end;
+                if end < start {
+                    bail!(errors::Error::framing("array: struct length underflow"));
+                }
+                end
+            }
         };
-        top.deserialize_struct(name, fields, visitor)
+        Ok(value)
     }
 
     fn deserialize_tuple<V>(self, len: usize, visitor: V) -> errors::Result<V::Value>
@@ -351,7 +570,6 @@ where
         V: de::Visitor<'de>,
     {
         trace!("tuple -> tuple_struct");
-        *self.seq_fixed_width = false;
         self.deserialize_tuple_struct("tuple", len, visitor)
     }
 
@@ -365,9 +583,8 @@ where
         V: de::Visitor<'de>,
     {
         trace!("tuple_struct -> struct");
-        *self.seq_fixed_width = false;
         if len > 32 {
-            bail!("too many fields in tuple");
+            bail!(errors::Error::limit("tuple with more than 32 fields"));
         }
         let syn_fields = &[
             "0", "1", "2", "3", "4", "5", "6", "7", "8", "9", "10", "11", "12", "13", "14", "15",
@@ -386,17 +603,10 @@ where
     where
         V: de::Visitor<'de>,
     {
-        *self.seq_fixed_width = false;
-
-        let cur = self.top.reader.seek(io::SeekFrom::Current(0))?;
-        self.top.reader.seek(io::SeekFrom::End(-1))?;
-        let end = u64::from(self.top.reader.read_u8()?);
-        *self.seq_length = self.seq_length.saturating_sub(1);
-        self.top.reader.seek(io::SeekFrom::Start(cur))?;
-        let buflen = (end - cur) as usize;
-        trace!("enum: len={}", buflen);
+        self.top.alignment = self.top.alignment.max(8);
+        let (start, end) = self.variable_element("enum")?;
         let mut top = CursorDeserializer {
-            start: cur,
+            start,
             end,
             top: &mut *self.top,
         };
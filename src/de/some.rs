@@ -1,9 +1,10 @@
-use crate::errors::{self, ResultExt};
-use byteorder::ReadBytesExt;
-use serde::de::{self, Error};
-use std::io;
+use crate::errors;
+use crate::io;
+use crate::io::ReadBytesExt;
+use serde::de;
 
 use crate::de::cursor::CursorDeserializer;
+use crate::de::input::Input;
 
 pub(crate) struct SomeDeserializer<'a, RS: 'a> {
     pub(crate) _len: usize,
@@ -13,53 +14,83 @@ pub(crate) struct SomeDeserializer<'a, RS: 'a> {
 
 impl<'de, 'a, RS> de::Deserializer<'de> for &'a mut SomeDeserializer<'a, RS>
 where
-    RS: io::Read + io::Seek,
+    RS: Input<'de>,
 {
     type Error = errors::Error;
 
-    // Unsupported
-    fn deserialize_any<V>(self, _visitor: V) -> errors::Result<V::Value>
+    deserialize_mapped!(top.options);
+
+    // Without a known type, the inner value is taken as a `v`.
+    fn deserialize_any<V>(self, visitor: V) -> errors::Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        let start = self.top.reader.stream_position()?;
+        self.top.reader.seek(io::SeekFrom::Start(*self.end - 1))?;
+        let term = self.top.reader.read_u8()?;
+        self.top.reader.seek(io::SeekFrom::Start(start))?;
+        *self.end -= 1;
+        if term != 0x00 {
+            return Err(errors::Error::framing("maybe: non-zero variant terminator"));
+        }
+
+        let mut top = CursorDeserializer {
+            start,
+            end: *self.end,
+            top: &mut *self.top,
+        };
+        top.deserialize_any(visitor)
+    }
+
+    fn deserialize_ignored_any<V>(self, visitor: V) -> errors::Result<V::Value>
     where
         V: de::Visitor<'de>,
     {
-        Err(Self::Error::custom("unsupported"))
+        let start = self.top.reader.stream_position()?;
+        let mut top = CursorDeserializer {
+            start,
+            end: *self.end,
+            top: &mut *self.top,
+        };
+        top.deserialize_ignored_any(visitor)
     }
+
     forward_to_deserialize_any! {
-        f32 identifier ignored_any
+        identifier
     }
 
     // Fixed size
-    fn deserialize_bool<V>(self, visitor: V) -> errors::Result<V::Value>
+    fn deserialize_unit<V>(self, visitor: V) -> errors::Result<V::Value>
     where
         V: de::Visitor<'de>,
     {
-        let start = self.top.reader.seek(io::SeekFrom::Current(0))?;
+        let start = self.top.reader.stream_position()?;
         let mut top = CursorDeserializer {
             start,
             end: *self.end,
             top: &mut *self.top,
         };
-        top.deserialize_bool(visitor)
+        top.deserialize_unit(visitor)
     }
 
-    fn deserialize_i8<V>(self, visitor: V) -> errors::Result<V::Value>
+    fn deserialize_bool<V>(self, visitor: V) -> errors::Result<V::Value>
     where
         V: de::Visitor<'de>,
     {
-        let start = self.top.reader.seek(io::SeekFrom::Current(0))?;
+        let start = self.top.reader.stream_position()?;
         let mut top = CursorDeserializer {
             start,
             end: *self.end,
             top: &mut *self.top,
         };
-        top.deserialize_i8(visitor)
+        top.deserialize_bool(visitor)
     }
 
     fn deserialize_u8<V>(self, visitor: V) -> errors::Result<V::Value>
     where
         V: de::Visitor<'de>,
     {
-        let start = self.top.reader.seek(io::SeekFrom::Current(0))?;
+        let start = self.top.reader.stream_position()?;
         let mut top = CursorDeserializer {
             start,
             end: *self.end,
@@ -73,7 +104,7 @@ where
         V: de::Visitor<'de>,
     {
         const ALIGNMENT: u64 = 2;
-        let cur = self.top.reader.seek(io::SeekFrom::Current(0))?;
+        let cur = self.top.reader.stream_position()?;
         let padding = (ALIGNMENT - (cur % ALIGNMENT)) % ALIGNMENT;
         trace!("i16: skipping {} padding bytes", padding);
         let start = self
@@ -94,7 +125,7 @@ where
         V: de::Visitor<'de>,
     {
         const ALIGNMENT: u64 = 2;
-        let cur = self.top.reader.seek(io::SeekFrom::Current(0))?;
+        let cur = self.top.reader.stream_position()?;
         let padding = (ALIGNMENT - (cur % ALIGNMENT)) % ALIGNMENT;
         trace!("u16: skipping {} padding bytes", padding);
         let start = self
@@ -115,7 +146,7 @@ where
         V: de::Visitor<'de>,
     {
         const ALIGNMENT: u64 = 4;
-        let cur = self.top.reader.seek(io::SeekFrom::Current(0))?;
+        let cur = self.top.reader.stream_position()?;
         let padding = (ALIGNMENT - (cur % ALIGNMENT)) % ALIGNMENT;
         trace!("struct: skipping {} padding bytes", padding);
         let start = self
@@ -136,7 +167,7 @@ where
         V: de::Visitor<'de>,
     {
         const ALIGNMENT: u64 = 4;
-        let cur = self.top.reader.seek(io::SeekFrom::Current(0))?;
+        let cur = self.top.reader.stream_position()?;
         let padding = (ALIGNMENT - (cur % ALIGNMENT)) % ALIGNMENT;
         trace!("struct: skipping {} padding bytes", padding);
         let start = self
@@ -157,7 +188,7 @@ where
         V: de::Visitor<'de>,
     {
         const ALIGNMENT: u64 = 8;
-        let cur = self.top.reader.seek(io::SeekFrom::Current(0))?;
+        let cur = self.top.reader.stream_position()?;
         let padding = (ALIGNMENT - (cur % ALIGNMENT)) % ALIGNMENT;
         trace!("struct: skipping {} padding bytes", padding);
         let start = self
@@ -178,7 +209,7 @@ where
         V: de::Visitor<'de>,
     {
         const ALIGNMENT: u64 = 8;
-        let cur = self.top.reader.seek(io::SeekFrom::Current(0))?;
+        let cur = self.top.reader.stream_position()?;
         let padding = (ALIGNMENT - (cur % ALIGNMENT)) % ALIGNMENT;
         trace!("struct: skipping {} padding bytes", padding);
         let start = self
@@ -199,7 +230,7 @@ where
         V: de::Visitor<'de>,
     {
         const ALIGNMENT: u64 = 8;
-        let cur = self.top.reader.seek(io::SeekFrom::Current(0))?;
+        let cur = self.top.reader.stream_position()?;
         let padding = (ALIGNMENT - (cur % ALIGNMENT)) % ALIGNMENT;
         trace!("struct: skipping {} padding bytes", padding);
         let start = self
@@ -219,17 +250,13 @@ where
     where
         V: de::Visitor<'de>,
     {
-        let start = self.top.reader.seek(io::SeekFrom::Current(0))?;
+        let start = self.top.reader.stream_position()?;
         self.top.reader.seek(io::SeekFrom::Start(*self.end - 1))?;
-        let term = self
-            .top
-            .reader
-            .read_u8()
-            .chain_err(|| "struct: reading string length")?;
+        let term = self.top.reader.read_u8()?;
         self.top.reader.seek(io::SeekFrom::Start(start))?;
         *self.end -= 1;
         if term != 0x00 {
-            return Err(Self::Error::custom("some: string non-zero terminator"));
+            return Err(errors::Error::framing("maybe: non-zero string terminator"));
         }
 
         let mut top = CursorDeserializer {
@@ -244,17 +271,13 @@ where
     where
         V: de::Visitor<'de>,
     {
-        let start = self.top.reader.seek(io::SeekFrom::Current(0))?;
+        let start = self.top.reader.stream_position()?;
         self.top.reader.seek(io::SeekFrom::Start(*self.end - 1))?;
-        let term = self
-            .top
-            .reader
-            .read_u8()
-            .chain_err(|| "struct: reading string length")?;
+        let term = self.top.reader.read_u8()?;
         self.top.reader.seek(io::SeekFrom::Start(start))?;
         *self.end -= 1;
         if term != 0x00 {
-            return Err(Self::Error::custom("some: string non-zero terminator"));
+            return Err(errors::Error::framing("maybe: non-zero string terminator"));
         }
 
         let mut top = CursorDeserializer {
@@ -265,10 +288,42 @@ where
         top.deserialize_byte_buf(visitor)
     }
 
+    fn deserialize_str<V>(self, visitor: V) -> errors::Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        self.deserialize_string(visitor)
+    }
+
+    fn deserialize_bytes<V>(self, visitor: V) -> errors::Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        self.deserialize_byte_buf(visitor)
+    }
+
+    fn deserialize_newtype_struct<V>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> errors::Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_unit_struct<V>(self, _name: &'static str, visitor: V) -> errors::Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        self.deserialize_unit(visitor)
+    }
+
     // Pending implementation
     forward_to_deserialize_any! {
-        char str enum bytes
-        unit unit_struct seq tuple tuple_struct map
-        option newtype_struct struct
+        enum
+        seq tuple tuple_struct map
+        option struct
     }
 }
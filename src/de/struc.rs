@@ -1,23 +1,30 @@
-use byteorder::ReadBytesExt;
-use serde::de::{self, Error};
-use std::io;
+use crate::io;
+use alloc::format;
+use serde::de;
 
 use crate::de::cursor::CursorDeserializer;
+use crate::de::input::Input;
 use crate::de::util;
 use crate::errors;
 
 pub(crate) struct StructDeAccess<'a, RS: 'a> {
     pub(crate) cur_field: usize,
+    pub(crate) base: u64,
+    pub(crate) offset_size: u64,
     pub(crate) start: u64,
     pub(crate) end: u64,
+    /// Whether `end` is known, rather than read ahead for a nested structure.
+    pub(crate) exact_end: bool,
+    /// Whether a variable-sized field was decoded, framing the structure.
+    pub(crate) variable: bool,
     pub(crate) _name: &'static str,
     pub(crate) fields: &'static [&'static str],
     pub(crate) top: &'a mut crate::de::top::TopDeserializer<RS>,
 }
 
-impl<'a, 'de, RS> de::SeqAccess<'de> for &'a mut StructDeAccess<'a, RS>
+impl<'a, 'b, 'de, RS> de::SeqAccess<'de> for &'b mut StructDeAccess<'a, RS>
 where
-    RS: io::Read + io::Seek,
+    RS: Input<'de>,
 {
     type Error = errors::Error;
 
@@ -35,14 +42,32 @@ where
             return Ok(None);
         }
 
+        // Older, shorter structures end before their trailing fields.
+        if self.top.options.trailing_defaults
+            && (self.exact_end || self.variable)
+            && self.start >= self.end
+        {
+            trace!(
+                "struct exhausted: name={}, field={}",
+                self._name,
+                self.fields[self.cur_field]
+            );
+            return Ok(None);
+        }
+
         // Deserialize next element
+        let field_start = self.start;
+        let field_name = self.fields[self.cur_field];
         let v = {
             let mut seq_de = StructDeserializer {
                 cur_field: &self.cur_field,
+                base: self.base,
+                offset_size: self.offset_size,
                 start: &mut self.start,
                 end: &mut self.end,
+                variable: &mut self.variable,
                 fields: self.fields,
-                top: &mut self.top,
+                top: self.top,
             };
             trace!(
                 "next field: field_name={}, field_start={:#x} - struct_name={}, struct_end={:#x}",
@@ -51,42 +76,250 @@ where
                 self._name,
                 seq_de.end
             );
-            de::DeserializeSeed::deserialize(seed, &mut seq_de)?
+            de::DeserializeSeed::deserialize(seed, &mut seq_de)
+                .map_err(|e| e.at(field_start).field(field_name))?
         };
         self.cur_field += 1;
         Ok(Some(v))
     }
 }
 
+/// Layout of a nested structure, only known once decoded.
+pub(crate) enum Layout {
+    /// Unframed, and ending at this position.
+    Fixed(u64),
+    /// Framed by an offset of the enclosing container.
+    Variable,
+}
+
+/// Decode a structure nested at `start`, ending at `end` if variable-sized.
+///
+/// Whether a structure is framed depends on the types of its fields: callers
+/// read its end ahead, and consume the framing offset for `Layout::Variable`
+/// only. Fixed-size structures are padded to their alignment.
+pub(crate) fn nested<'de, RS, V>(
+    top: &mut crate::de::top::TopDeserializer<RS>,
+    start: u64,
+    end: u64,
+    name: &'static str,
+    fields: &'static [&'static str],
+    visitor: V,
+) -> errors::Result<(V::Value, Layout)>
+where
+    RS: Input<'de>,
+    V: de::Visitor<'de>,
+{
+    let outer = core::mem::replace(&mut top.alignment, 1);
+    let mut sub = StructDeAccess {
+        cur_field: 0,
+        base: start,
+        offset_size: util::compute_size(end.saturating_sub(start)),
+        start,
+        end,
+        exact_end: false,
+        variable: false,
+        _name: name,
+        fields,
+        top: &mut *top,
+    };
+    let value = visitor.visit_seq(&mut sub)?;
+    let (variable, pos) = (sub.variable, sub.start);
+    let alignment = top.alignment;
+    top.alignment = outer.max(alignment);
+    if variable {
+        return Ok((value, Layout::Variable));
+    }
+    let padding = (alignment - (pos % alignment)) % alignment;
+    Ok((value, Layout::Fixed((pos + padding).max(start + 1))))
+}
+
+/// A dictionary entry, decoded as a `(key, value)` structure across the
+/// separate calls for its key and its value.
+#[derive(Debug)]
+pub(crate) struct Entry {
+    base: u64,
+    offset_size: u64,
+    start: u64,
+    end: u64,
+    variable: bool,
+}
+
+impl Entry {
+    const FIELDS: &'static [&'static str] = &["key", "value"];
+
+    /// Start an entry at `start`, ending at `end` if variable-sized.
+    pub(crate) fn new(start: u64, end: u64) -> Self {
+        Entry {
+            base: start,
+            offset_size: util::compute_size(end.saturating_sub(start)),
+            start,
+            end,
+            variable: false,
+        }
+    }
+
+    /// Decode the key (field 0) or the value (field 1) of the entry.
+    pub(crate) fn field<'de, RS, T>(
+        &mut self,
+        top: &mut crate::de::top::TopDeserializer<RS>,
+        field: usize,
+        seed: T,
+    ) -> errors::Result<T::Value>
+    where
+        RS: Input<'de>,
+        T: de::DeserializeSeed<'de>,
+    {
+        let field_start = self.start;
+        let mut entry_de = StructDeserializer {
+            cur_field: &field,
+            base: self.base,
+            offset_size: self.offset_size,
+            start: &mut self.start,
+            end: &mut self.end,
+            variable: &mut self.variable,
+            fields: Self::FIELDS,
+            top,
+        };
+        de::DeserializeSeed::deserialize(seed, &mut entry_de)
+            .map_err(|e| e.at(field_start).field(Self::FIELDS[field]))
+    }
+
+    /// Layout of the whole entry, once decoded with the given alignment.
+    pub(crate) fn layout(&self, alignment: u64) -> Layout {
+        if self.variable {
+            return Layout::Variable;
+        }
+        let padding = (alignment - (self.start % alignment)) % alignment;
+        Layout::Fixed((self.start + padding).max(self.base + 1))
+    }
+}
+
 // A Deserializer specialized on structures, with custom logic
 // for non-fized-size ones.
 pub(crate) struct StructDeserializer<'a, RS: 'a> {
     pub(crate) cur_field: &'a usize,
+    pub(crate) base: u64,
+    pub(crate) offset_size: u64,
     pub(crate) start: &'a mut u64,
     pub(crate) end: &'a mut u64,
+    pub(crate) variable: &'a mut bool,
     pub(crate) fields: &'static [&'static str],
     pub(crate) top: &'a mut crate::de::top::TopDeserializer<RS>,
 }
 
-impl<'de, 'a, RS> de::Deserializer<'de> for &'a mut StructDeserializer<'a, RS>
+impl<'a, RS> StructDeserializer<'a, RS>
 where
     RS: io::Read + io::Seek,
+{
+    /// Whether the current field is the last one, whose end is not framed.
+    fn is_last(&self) -> bool {
+        self.cur_field.saturating_add(1) >= self.fields.len()
+    }
+
+    /// Start of the structure, which framing offsets are relative to.
+    ///
+    /// A structure nested after less aligned values starts after padding,
+    /// as told by the alignment of the fields decoded so far.
+    fn framing_base(&self) -> u64 {
+        let alignment = self.top.alignment;
+        self.base + (alignment - (self.base % alignment)) % alignment
+    }
+
+    /// Read the end of the current field from the framing offsets,
+    /// which are relative to the start of the structure.
+    fn framed_end(&mut self) -> errors::Result<u64> {
+        let val = util::read_len(self.top, *self.start, *self.end, self.offset_size)?;
+        *self.end -= self.offset_size;
+        self.framing_base()
+            .checked_add(val)
+            .ok_or_else(|| errors::Error::framing("struct: framing offset overflow"))
+    }
+
+    /// Locate a variable-sized field, and move past it.
+    ///
+    /// Returns the field start and end positions.
+    fn variable_field(&mut self, kind: &str) -> errors::Result<(u64, u64)> {
+        *self.variable = true;
+        let struct_start = *self.start;
+        let struct_end = *self.end;
+        let struct_len = self
+            .end
+            .checked_sub(struct_start)
+            .ok_or_else(|| errors::Error::framing("struct: length underflow"))?;
+
+        // Empty field.
+        if struct_len == 0 {
+            trace!("empty {}", kind);
+            return Ok((0, 0));
+        };
+
+        // Non-empty field, its end is framed unless it is the last one.
+        let end = if self.is_last() {
+            struct_end
+        } else {
+            self.framed_end()?
+        };
+        let buflen = end
+            .checked_sub(struct_start)
+            .ok_or_else(|| errors::Error::framing(format!("struct: {} length underflow", kind)))?;
+
+        // Update position to prepare for next element
+        *self.start += buflen;
+
+        trace!(
+            "{}: cur={:#x}, end={:#x}, length={:#x}",
+            kind,
+            struct_start,
+            end,
+            buflen
+        );
+        self.top.reader.seek(io::SeekFrom::Start(struct_start))?;
+        Ok((struct_start, end))
+    }
+
+    /// Locate a `v` field, aligned to 8 bytes, and move past it.
+    fn variant_field(&mut self) -> errors::Result<(u64, u64)> {
+        const ALIGNMENT: u64 = 8;
+        self.top.alignment = self.top.alignment.max(ALIGNMENT);
+        let padding = (ALIGNMENT - (*self.start % ALIGNMENT)) % ALIGNMENT;
+        if padding != 0 {
+            trace!("struct: skipping {} padding bytes", padding);
+            *self.start += padding;
+        }
+        self.variable_field("variant")
+    }
+}
+
+impl<'de, 'a, RS> de::Deserializer<'de> for &'a mut StructDeserializer<'a, RS>
+where
+    RS: Input<'de>,
 {
     type Error = errors::Error;
 
-    // Unsupported
-    fn deserialize_any<V>(self, _visitor: V) -> errors::Result<V::Value>
+    deserialize_mapped!(top.options);
+
+    // Without a known type, only a `v` describes itself.
+    fn deserialize_any<V>(self, visitor: V) -> errors::Result<V::Value>
     where
         V: de::Visitor<'de>,
     {
-        Err(Self::Error::custom("struct: any not supported"))
+        let (start, end) = self.variant_field()?;
+        let mut top = CursorDeserializer {
+            start,
+            end,
+            top: &mut *self.top,
+        };
+        top.deserialize_any(visitor)
     }
 
+    // Without a known type, the size of the field is unknown.
     fn deserialize_ignored_any<V>(self, _visitor: V) -> errors::Result<V::Value>
     where
         V: de::Visitor<'de>,
     {
-        Err(Self::Error::custom("struct: ignored_any not supported"))
+        Err(errors::Error::unsupported(
+            "ignored field without a signature",
+        ))
     }
 
     // Fixed size
@@ -95,6 +328,7 @@ where
         V: de::Visitor<'de>,
     {
         let cur = *self.start;
+        self.top.reader.seek(io::SeekFrom::Start(cur))?;
         *self.start += 1;
         let mut top = CursorDeserializer {
             start: cur,
@@ -104,25 +338,12 @@ where
         top.deserialize_bool(visitor)
     }
 
-    fn deserialize_i8<V>(self, visitor: V) -> errors::Result<V::Value>
-    where
-        V: de::Visitor<'de>,
-    {
-        let cur = *self.start;
-        *self.start += 1;
-        let mut top = CursorDeserializer {
-            start: cur,
-            end: *self.end,
-            top: &mut *self.top,
-        };
-        top.deserialize_i8(visitor)
-    }
-
     fn deserialize_u8<V>(self, visitor: V) -> errors::Result<V::Value>
     where
         V: de::Visitor<'de>,
     {
         let cur = *self.start;
+        self.top.reader.seek(io::SeekFrom::Start(cur))?;
         *self.start += 1;
         let mut top = CursorDeserializer {
             start: cur,
@@ -138,11 +359,12 @@ where
     {
         const ALIGNMENT: u64 = 2;
         let padding = (ALIGNMENT - (*self.start % ALIGNMENT)) % ALIGNMENT;
+        self.top.alignment = self.top.alignment.max(ALIGNMENT);
         trace!("i16: skipping {} padding bytes", padding);
         let start = self
             .top
             .reader
-            .seek(io::SeekFrom::Current(padding as i64))?;
+            .seek(io::SeekFrom::Start(*self.start + padding))?;
         *self.start += padding + ALIGNMENT;
 
         let mut top = CursorDeserializer {
@@ -159,11 +381,12 @@ where
     {
         const ALIGNMENT: u64 = 2;
         let padding = (ALIGNMENT - (*self.start % ALIGNMENT)) % ALIGNMENT;
+        self.top.alignment = self.top.alignment.max(ALIGNMENT);
         trace!("u16: skipping {} padding bytes", padding);
         let start = self
             .top
             .reader
-            .seek(io::SeekFrom::Current(padding as i64))?;
+            .seek(io::SeekFrom::Start(*self.start + padding))?;
         *self.start += padding + ALIGNMENT;
 
         let mut top = CursorDeserializer {
@@ -181,11 +404,12 @@ where
     {
         const ALIGNMENT: u64 = 4;
         let padding = (ALIGNMENT - (*self.start % ALIGNMENT)) % ALIGNMENT;
+        self.top.alignment = self.top.alignment.max(ALIGNMENT);
         trace!("i32: skipping {} padding bytes", padding);
         let start = self
             .top
             .reader
-            .seek(io::SeekFrom::Current(padding as i64))?;
+            .seek(io::SeekFrom::Start(*self.start + padding))?;
         *self.start += padding + ALIGNMENT;
 
         let mut top = CursorDeserializer {
@@ -202,11 +426,12 @@ where
     {
         const ALIGNMENT: u64 = 4;
         let padding = (ALIGNMENT - (*self.start % ALIGNMENT)) % ALIGNMENT;
+        self.top.alignment = self.top.alignment.max(ALIGNMENT);
         trace!("u32: skipping {} padding bytes", padding);
         let start = self
             .top
             .reader
-            .seek(io::SeekFrom::Current(padding as i64))?;
+            .seek(io::SeekFrom::Start(*self.start + padding))?;
         *self.start += padding + ALIGNMENT;
 
         let mut top = CursorDeserializer {
@@ -223,11 +448,12 @@ where
     {
         const ALIGNMENT: u64 = 8;
         let padding = (ALIGNMENT - (*self.start % ALIGNMENT)) % ALIGNMENT;
+        self.top.alignment = self.top.alignment.max(ALIGNMENT);
         trace!("struct: skipping {} padding bytes", padding);
         let start = self
             .top
             .reader
-            .seek(io::SeekFrom::Current(padding as i64))?;
+            .seek(io::SeekFrom::Start(*self.start + padding))?;
         *self.start += padding + ALIGNMENT;
 
         let mut top = CursorDeserializer {
@@ -244,11 +470,12 @@ where
     {
         const ALIGNMENT: u64 = 8;
         let padding = (ALIGNMENT - (*self.start % ALIGNMENT)) % ALIGNMENT;
+        self.top.alignment = self.top.alignment.max(ALIGNMENT);
         trace!("struct: skipping {} padding bytes", padding);
         let start = self
             .top
             .reader
-            .seek(io::SeekFrom::Current(padding as i64))?;
+            .seek(io::SeekFrom::Start(*self.start + padding))?;
         *self.start += padding + ALIGNMENT;
 
         let mut top = CursorDeserializer {
@@ -265,11 +492,12 @@ where
     {
         const ALIGNMENT: u64 = 8;
         let padding = (ALIGNMENT - (*self.start % ALIGNMENT)) % ALIGNMENT;
+        self.top.alignment = self.top.alignment.max(ALIGNMENT);
         trace!("struct: skipping {} padding bytes", padding);
         let start = self
             .top
             .reader
-            .seek(io::SeekFrom::Current(padding as i64))?;
+            .seek(io::SeekFrom::Start(*self.start + padding))?;
         *self.start += padding + ALIGNMENT;
 
         let mut top = CursorDeserializer {
@@ -284,47 +512,9 @@ where
     where
         V: de::Visitor<'de>,
     {
-        let struct_start = *self.start;
-        let struct_end = *self.end;
-        let struct_len = self
-            .end
-            .checked_sub(struct_start)
-            .ok_or_else(|| Self::Error::custom("struct: length underflow"))?;
-
-        // Empty string.
-        if struct_len == 0 {
-            trace!("empty string");
-            let mut top = CursorDeserializer {
-                start: 0,
-                end: 0,
-                top: &mut *self.top,
-            };
-            return top.deserialize_string(visitor);
-        };
-
-        // Non-empty string.
-        let end = if self.cur_field.saturating_add(1) >= self.fields.len() {
-            struct_end as u64
-        } else {
-            let (val, size) = util::read_len(self.top, struct_start, struct_end, struct_len)?;
-            *self.end -= size;
-            val
-        };
-        let buflen = end
-            .checked_sub(struct_start)
-            .ok_or_else(|| Self::Error::custom("struct: string length underflow"))?;
-
-        // Update position to prepare for next element
-        *self.start += buflen;
-
-        trace!(
-            "string: cur={:#x}, end={:#x}, length={:#x}",
-            struct_start,
-            end,
-            buflen
-        );
+        let (start, end) = self.variable_field("string")?;
         let mut top = CursorDeserializer {
-            start: struct_start,
+            start,
             end,
             top: &mut *self.top,
         };
@@ -335,12 +525,13 @@ where
     where
         V: de::Visitor<'de>,
     {
+        *self.variable = true;
         let struct_start = *self.start;
         let struct_end = *self.end;
         let struct_len = self
             .end
             .checked_sub(struct_start)
-            .ok_or_else(|| Self::Error::custom("struct: length underflow"))?;
+            .ok_or_else(|| errors::Error::framing("struct: length underflow"))?;
 
         // Empty array.
         if struct_len == 0 {
@@ -355,18 +546,14 @@ where
 
         // Non-empty array.
         let cur = *self.start;
-        let end = if self.cur_field.saturating_add(1) >= self.fields.len() {
-            let size = util::compute_size(struct_len);
-            *self.end -= size;
+        let end = if self.is_last() {
             struct_end
         } else {
-            let (val, size) = util::read_len(self.top, struct_start, struct_end, struct_len)?;
-            *self.end -= size;
-            val
+            self.framed_end()?
         };
         let buflen = end
             .checked_sub(cur)
-            .ok_or_else(|| Self::Error::custom("struct: array length underflow"))?;
+            .ok_or_else(|| errors::Error::framing("struct: array length underflow"))?;
 
         // Update position to prepare for next element
         *self.start = end;
@@ -404,7 +591,7 @@ where
     {
         trace!("tuple_struct -> struct");
         if len > 32 {
-            bail!("too many fields in tuple");
+            bail!(errors::Error::limit("tuple with more than 32 fields"));
         }
         let syn_fields = &[
             "0", "1", "2", "3", "4", "5", "6", "7", "8", "9", "10", "11", "12", "13", "14", "15",
@@ -423,104 +610,129 @@ where
     where
         V: de::Visitor<'de>,
     {
-        let cur = self.top.reader.seek(io::SeekFrom::Current(0))?;
-        self.top.reader.seek(io::SeekFrom::End(-1))?;
-        let end = u64::from(self.top.reader.read_u8()?);
-        *self.end = self.end.saturating_sub(1);
-        self.top.reader.seek(io::SeekFrom::Start(cur))?;
-        let buflen = (end - cur) as usize;
-        trace!("struct: len={}", buflen);
-        let mut top = CursorDeserializer {
-            start: cur,
-            end,
-            top: &mut *self.top,
+        // Read the end ahead, and only consume it for a framed structure.
+        let start = *self.start;
+        let end = match self.is_last() {
+            true => *self.end,
+            false => util::read_len(self.top, start, *self.end, self.offset_size)
+                .ok()
+                .and_then(|val| self.framing_base().checked_add(val))
+                .unwrap_or(*self.end),
+        };
+        let (value, layout) = nested(self.top, start, end, name, fields, visitor)?;
+        *self.start = match layout {
+            Layout::Fixed(end) => end,
+            Layout::Variable => {
+                *self.variable = true;
+                if !self.is_last() {
+                    *self.end -= self.offset_size;
+                }
+                if end < start {
+                    bail!(errors::Error::framing("struct: struct length underflow"));
+                }
+                end
+            }
         };
-        top.deserialize_struct(name, fields, visitor)
+        trace!("struct: len={}", *self.start - start);
+        Ok(value)
     }
 
     fn deserialize_unit_struct<V>(
         self,
         _name: &'static str,
-        _visitor: V,
+        visitor: V,
     ) -> Result<V::Value, Self::Error>
     where
         V: de::Visitor<'de>,
     {
-        Err(Self::Error::custom("struct: unit_struct not supported"))
+        self.deserialize_unit(visitor)
     }
 
     fn deserialize_newtype_struct<V>(
         self,
         _name: &'static str,
-        _visitor: V,
+        visitor: V,
     ) -> Result<V::Value, Self::Error>
     where
         V: de::Visitor<'de>,
     {
-        Err(Self::Error::custom("struct: newtype_struct not supported"))
-    }
-
-    fn deserialize_unit<V>(self, _visitor: V) -> Result<V::Value, Self::Error>
-    where
-        V: de::Visitor<'de>,
-    {
-        Err(Self::Error::custom("struct: unit not supported"))
+        visitor.visit_newtype_struct(self)
     }
 
-    fn deserialize_map<V>(self, _visitor: V) -> Result<V::Value, Self::Error>
+    fn deserialize_unit<V>(self, visitor: V) -> Result<V::Value, Self::Error>
     where
         V: de::Visitor<'de>,
     {
-        Err(Self::Error::custom("struct: map not supported"))
-    }
-
-    fn deserialize_byte_buf<V>(self, _visitor: V) -> errors::Result<V::Value>
-    where
-        V: de::Visitor<'de>,
-    {
-        Err(Self::Error::custom("struct: byte_buf not supported"))
+        let cur = *self.start;
+        self.top.reader.seek(io::SeekFrom::Start(cur))?;
+        *self.start += 1;
+        let mut top = CursorDeserializer {
+            start: cur,
+            end: *self.end,
+            top: &mut *self.top,
+        };
+        top.deserialize_unit(visitor)
     }
 
-    fn deserialize_bytes<V>(self, _visitor: V) -> Result<V::Value, Self::Error>
+    fn deserialize_map<V>(self, visitor: V) -> Result<V::Value, Self::Error>
     where
         V: de::Visitor<'de>,
     {
-        Err(Self::Error::custom("struct: bytes not supported"))
+        let (start, end) = self.variable_field("dictionary")?;
+        let mut top = CursorDeserializer {
+            start,
+            end,
+            top: &mut *self.top,
+        };
+        top.deserialize_map(visitor)
     }
 
-    fn deserialize_str<V>(self, _visitor: V) -> Result<V::Value, Self::Error>
+    fn deserialize_byte_buf<V>(self, visitor: V) -> errors::Result<V::Value>
     where
         V: de::Visitor<'de>,
     {
-        Err(Self::Error::custom("struct: str not supported"))
+        let (start, end) = self.variable_field("bytes")?;
+        let mut top = CursorDeserializer {
+            start,
+            end,
+            top: &mut *self.top,
+        };
+        top.deserialize_byte_buf(visitor)
     }
 
-    fn deserialize_char<V>(self, _visitor: V) -> Result<V::Value, Self::Error>
+    fn deserialize_bytes<V>(self, visitor: V) -> Result<V::Value, Self::Error>
     where
         V: de::Visitor<'de>,
     {
-        Err(Self::Error::custom("struct: char not supported"))
+        self.deserialize_byte_buf(visitor)
     }
 
-    fn deserialize_f32<V>(self, _visitor: V) -> errors::Result<V::Value>
+    fn deserialize_str<V>(self, visitor: V) -> Result<V::Value, Self::Error>
     where
         V: de::Visitor<'de>,
     {
-        Err(Self::Error::custom("struct: f32 not supported"))
+        self.deserialize_string(visitor)
     }
 
     fn deserialize_identifier<V>(self, _visitor: V) -> Result<V::Value, Self::Error>
     where
         V: de::Visitor<'de>,
     {
-        Err(Self::Error::custom("struct: identifier not supported"))
+        Err(errors::Error::unsupported("identifier"))
     }
 
-    fn deserialize_option<V>(self, _visitor: V) -> errors::Result<V::Value>
+    // Maybe types are never fixed-size, whatever their inner type.
+    fn deserialize_option<V>(self, visitor: V) -> errors::Result<V::Value>
     where
         V: de::Visitor<'de>,
     {
-        Err(Self::Error::custom("struct: option not supported"))
+        let (start, end) = self.variable_field("maybe")?;
+        let mut top = CursorDeserializer {
+            start,
+            end,
+            top: &mut *self.top,
+        };
+        top.deserialize_option(visitor)
     }
 
     fn deserialize_enum<V>(
@@ -532,60 +744,10 @@ where
     where
         V: de::Visitor<'de>,
     {
-        // Align start
-        const ALIGNMENT: u64 = 8;
-        let padding = (ALIGNMENT - (*self.start % ALIGNMENT)) % ALIGNMENT;
-        if padding != 0 {
-            trace!("struct: skipping {} padding bytes", padding);
-            *self.start = self
-                .top
-                .reader
-                .seek(io::SeekFrom::Current(padding as i64))?;
-        }
-
-        // Compute variant limits
-        let struct_start = *self.start;
-        let struct_end = *self.end;
-        let struct_len = struct_end
-            .checked_sub(struct_start)
-            .ok_or_else(|| Self::Error::custom("struct: length underflow"))?;
-
-        // Empty variant.
-        if struct_len == 0 {
-            trace!("empty enum");
-            let mut top = CursorDeserializer {
-                start: 0,
-                end: 0,
-                top: &mut *self.top,
-            };
-            return top.deserialize_enum(enumer, variants, visitor);
-        };
-
-        // Non-empty variant.
-        let end = if self.cur_field.saturating_add(1) >= self.fields.len() {
-            struct_end as u64
-        } else {
-            let (val, size) = util::read_len(self.top, struct_start, struct_end, struct_len)?;
-            *self.end -= size;
-            val
-        };
-        let buflen = end
-            .checked_sub(struct_start)
-            .ok_or_else(|| Self::Error::custom("struct: enum length underflow"))?;
-
-        // Update position to prepare for next element
-        *self.start += buflen;
-
-        // Deserialize
-        trace!(
-            "enum: start={:#x}, end={:#x}, length={:#x}",
-            struct_start,
-            end,
-            buflen
-        );
+        let (start, end) = self.variant_field()?;
         let mut top = CursorDeserializer {
-            start: struct_start,
-            end: struct_end,
+            start,
+            end,
             top: &mut *self.top,
         };
         top.deserialize_enum(enumer, variants, visitor)
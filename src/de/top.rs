@@ -1,13 +1,17 @@
 use crate::config;
 use crate::de::cursor::CursorDeserializer;
+use crate::de::input::Input;
 use crate::errors;
-use serde::de::{self, Error};
-use std::io;
+use crate::io;
+use alloc::format;
+use serde::de;
 
 #[derive(Debug)]
 pub(crate) struct TopDeserializer<RS> {
     pub(crate) reader: RS,
     pub(crate) options: config::Config,
+    /// Largest alignment of the values read in the current structure.
+    pub(crate) alignment: u64,
 }
 
 impl<'a, RS> TopDeserializer<RS>
@@ -18,12 +22,12 @@ where
         &'a mut self,
         kind: &'static str,
     ) -> errors::Result<CursorDeserializer<'a, RS>> {
-        let start = self.reader.seek(io::SeekFrom::Current(0))?;
+        let start = self.reader.stream_position()?;
         let end = self.reader.seek(io::SeekFrom::End(0))?;
         let _cur = self.reader.seek(io::SeekFrom::Start(start))?;
         let _buflen = end
             .checked_sub(start)
-            .ok_or_else(|| errors::Error::custom(format!("top: {} length underflow", kind)))?;
+            .ok_or_else(|| errors::Error::framing(format!("top: {} length underflow", kind)))?;
 
         let cd = CursorDeserializer {
             start,
@@ -34,33 +38,36 @@ where
     }
 }
 
-impl<'de, 'a, RS> de::Deserializer<'de> for &'a mut TopDeserializer<RS>
+impl<'de, RS> de::Deserializer<'de> for &mut TopDeserializer<RS>
 where
-    RS: io::Read + io::Seek,
+    RS: Input<'de>,
 {
     type Error = errors::Error;
 
-    fn deserialize_any<V>(self, _visitor: V) -> errors::Result<V::Value>
+    deserialize_mapped!(options);
+
+    fn deserialize_any<V>(self, visitor: V) -> errors::Result<V::Value>
     where
         V: de::Visitor<'de>,
     {
-        Err(Self::Error::custom("top: any not supported"))
+        let mut cd = self.forward("any")?;
+        cd.deserialize_any(visitor)
     }
 
-    fn deserialize_bool<V>(self, visitor: V) -> errors::Result<V::Value>
+    fn deserialize_ignored_any<V>(self, visitor: V) -> errors::Result<V::Value>
     where
         V: de::Visitor<'de>,
     {
-        let mut cd = self.forward("bool")?;
-        cd.deserialize_bool(visitor)
+        let mut cd = self.forward("ignored_any")?;
+        cd.deserialize_ignored_any(visitor)
     }
 
-    fn deserialize_i8<V>(self, visitor: V) -> errors::Result<V::Value>
+    fn deserialize_bool<V>(self, visitor: V) -> errors::Result<V::Value>
     where
         V: de::Visitor<'de>,
     {
-        let mut cd = self.forward("i8")?;
-        cd.deserialize_i8(visitor)
+        let mut cd = self.forward("bool")?;
+        cd.deserialize_bool(visitor)
     }
 
     fn deserialize_i16<V>(self, visitor: V) -> errors::Result<V::Value>
@@ -119,13 +126,6 @@ where
         cd.deserialize_u64(visitor)
     }
 
-    fn deserialize_f32<V>(self, _visitor: V) -> errors::Result<V::Value>
-    where
-        V: de::Visitor<'de>,
-    {
-        Err(Self::Error::custom("top: unsupported f32"))
-    }
-
     fn deserialize_f64<V>(self, visitor: V) -> errors::Result<V::Value>
     where
         V: de::Visitor<'de>,
@@ -166,6 +166,14 @@ where
         cd.deserialize_seq(visitor)
     }
 
+    fn deserialize_map<V>(self, visitor: V) -> errors::Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        let mut cd = self.forward("map")?;
+        cd.deserialize_map(visitor)
+    }
+
     fn deserialize_tuple<V>(self, len: usize, visitor: V) -> errors::Result<V::Value>
     where
         V: de::Visitor<'de>,
@@ -185,7 +193,7 @@ where
     {
         trace!("tuple_struct -> seq");
         if len > 32 {
-            bail!("top: too many fields in tuple");
+            bail!(errors::Error::limit("tuple with more than 32 fields"));
         }
         let syn_fields = &[
             "0", "1", "2", "3", "4", "5", "6", "7", "8", "9", "10", "11", "12", "13", "14", "15",
@@ -224,33 +232,48 @@ where
     fn deserialize_unit_struct<V>(
         self,
         _name: &'static str,
-        _visitor: V,
+        visitor: V,
     ) -> Result<V::Value, Self::Error>
     where
         V: de::Visitor<'de>,
     {
-        Err(Self::Error::custom("top: unit_struct not supported"))
+        self.deserialize_unit(visitor)
     }
 
     fn deserialize_newtype_struct<V>(
         self,
         _name: &'static str,
-        _visitor: V,
+        visitor: V,
     ) -> Result<V::Value, Self::Error>
     where
         V: de::Visitor<'de>,
     {
-        Err(Self::Error::custom("top: newtype_struct not supported"))
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_unit<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: de::Visitor<'de>,
+    {
+        let mut cd = self.forward("unit")?;
+        cd.deserialize_unit(visitor)
+    }
+
+    fn deserialize_str<V>(self, visitor: V) -> errors::Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        self.deserialize_string(visitor)
     }
 
-    fn deserialize_unit<V>(self, _visitor: V) -> Result<V::Value, Self::Error>
+    fn deserialize_bytes<V>(self, visitor: V) -> errors::Result<V::Value>
     where
         V: de::Visitor<'de>,
     {
-        Err(Self::Error::custom("top: unit not supported"))
+        self.deserialize_byte_buf(visitor)
     }
 
     forward_to_deserialize_any! {
-            identifier ignored_any map char bytes str
+            identifier
     }
 }
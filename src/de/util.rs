@@ -1,63 +1,151 @@
 use crate::de::top::TopDeserializer;
-use crate::errors::{self, ResultExt};
-use byteorder::{LittleEndian, ReadBytesExt};
-use serde::de::Error;
-use std::io;
+use crate::errors;
+use crate::io;
+use crate::io::ReadBytesExt;
+use byteorder::LittleEndian;
+use core::convert::TryFrom;
+use core::fmt;
+use serde::de;
 
+/// Read the framing offset which ends at `end`, then seek back to `start`.
+///
+/// Offsets are `size` bytes wide, depending on the length of their container.
 pub(crate) fn read_len<RS: io::Read + io::Seek>(
     top: &mut TopDeserializer<RS>,
     start: u64,
     end: u64,
-    len: u64,
-) -> errors::Result<(u64, u64)> {
-    let size = compute_size(len);
+    size: u64,
+) -> errors::Result<u64> {
     let len_pos = end
         .checked_sub(size)
-        .ok_or_else(|| errors::Error::custom("struct: length position underflow"))?;
+        .ok_or_else(|| errors::Error::framing("struct: length position underflow"))?;
     // Seek to end, in order to read length.
     top.reader.seek(io::SeekFrom::Start(len_pos))?;
     // Read length, which is variable in size.
     let val = match size {
         1 => {
-            let len8 = top
-                .reader
-                .read_u8()
-                .chain_err(|| "struct: reading array length (u8)")?;
+            let len8 = top.reader.read_u8()?;
             u64::from(len8)
         }
         2 => {
-            let len16 = top
-                .reader
-                .read_u16::<LittleEndian>()
-                .chain_err(|| "struct: reading array length (u16)")?;
+            let len16 = top.reader.read_u16::<LittleEndian>()?;
             u64::from(len16)
         }
         4 => {
-            let len32 = top
-                .reader
-                .read_u32::<LittleEndian>()
-                .chain_err(|| "struct: reading array length (u32)")?;
+            let len32 = top.reader.read_u32::<LittleEndian>()?;
             u64::from(len32)
         }
-        8 => top
-            .reader
-            .read_u64::<LittleEndian>()
-            .chain_err(|| "struct: reading array length (u64)")?,
-        _ => return Err(errors::Error::custom("struct: unsupported array size")),
+        8 => top.reader.read_u64::<LittleEndian>()?,
+        _ => {
+            return Err(errors::Error::framing(
+                "struct: unsupported framing offset size",
+            ))
+        }
     };
     // Reposition to the beginning.
     top.reader.seek(io::SeekFrom::Start(start))?;
-    Ok((val, size))
+    Ok(val)
 }
 
 pub(crate) fn compute_size(len: u64) -> u64 {
-    if len <= u64::from(::std::u8::MAX) {
+    if len <= u64::from(u8::MAX) {
         1
-    } else if len <= u64::from(::std::u16::MAX) {
+    } else if len <= u64::from(u16::MAX) {
         2
-    } else if len <= u64::from(::std::u32::MAX) {
+    } else if len <= u64::from(u32::MAX) {
         4
     } else {
         8
     }
 }
+
+/// Visitor adaptor for `i8`, decoded from a wider integer.
+pub(crate) struct I8Visitor<V>(pub(crate) V);
+
+impl<'de, V> de::Visitor<'de> for I8Visitor<V>
+where
+    V: de::Visitor<'de>,
+{
+    type Value = V::Value;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.0.expecting(f)
+    }
+
+    fn visit_i8<E: de::Error>(self, v: i8) -> Result<V::Value, E> {
+        self.0.visit_i8(v)
+    }
+
+    fn visit_i64<E: de::Error>(self, v: i64) -> Result<V::Value, E> {
+        match i8::try_from(v) {
+            Ok(v) => self.0.visit_i8(v),
+            Err(_) => Err(E::invalid_value(de::Unexpected::Signed(v), &"an i8")),
+        }
+    }
+
+    fn visit_u64<E: de::Error>(self, v: u64) -> Result<V::Value, E> {
+        match i8::try_from(v) {
+            Ok(v) => self.0.visit_i8(v),
+            Err(_) => Err(E::invalid_value(de::Unexpected::Unsigned(v), &"an i8")),
+        }
+    }
+}
+
+/// Visitor adaptor for `f32`, decoded from a `d`.
+pub(crate) struct F32Visitor<V>(pub(crate) V);
+
+impl<'de, V> de::Visitor<'de> for F32Visitor<V>
+where
+    V: de::Visitor<'de>,
+{
+    type Value = V::Value;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.0.expecting(f)
+    }
+
+    fn visit_f32<E: de::Error>(self, v: f32) -> Result<V::Value, E> {
+        self.0.visit_f32(v)
+    }
+
+    fn visit_f64<E: de::Error>(self, v: f64) -> Result<V::Value, E> {
+        // Infinities and NaN carry over, finite values must fit.
+        if v.is_finite() && v.abs() > f64::from(f32::MAX) {
+            return Err(E::invalid_value(de::Unexpected::Float(v), &"an f32"));
+        }
+        self.0.visit_f32(v as f32)
+    }
+}
+
+/// Visitor adaptor for `char`, decoded from a `u` or a `s`.
+pub(crate) struct CharVisitor<V>(pub(crate) V);
+
+impl<'de, V> de::Visitor<'de> for CharVisitor<V>
+where
+    V: de::Visitor<'de>,
+{
+    type Value = V::Value;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.0.expecting(f)
+    }
+
+    fn visit_char<E: de::Error>(self, v: char) -> Result<V::Value, E> {
+        self.0.visit_char(v)
+    }
+
+    fn visit_u64<E: de::Error>(self, v: u64) -> Result<V::Value, E> {
+        match u32::try_from(v).ok().and_then(char::from_u32) {
+            Some(c) => self.0.visit_char(c),
+            None => Err(E::invalid_value(de::Unexpected::Unsigned(v), &"a char")),
+        }
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<V::Value, E> {
+        let mut chars = v.chars();
+        match (chars.next(), chars.next()) {
+            (Some(c), None) => self.0.visit_char(c),
+            _ => Err(E::invalid_value(de::Unexpected::Str(v), &"a char")),
+        }
+    }
+}
@@ -1,12 +1,229 @@
 //! Error handling.
 
+use alloc::string::{String, ToString};
+use core::fmt;
 use serde::{de, ser};
-use std::{fmt, io, num};
 
-error_chain! {
-    foreign_links {
-        Io(io::Error);
-        ParseInt(num::ParseIntError);
+/// Result type of this crate.
+pub type Result<T> = core::result::Result<T, Error>;
+
+/// Kinds of errors.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum ErrorKind {
+    /// Free-form error message, e.g. from a `Serialize` or `Deserialize` impl.
+    Message(String),
+    /// Input ended before the value was complete.
+    UnexpectedEof,
+    /// Framing offsets or lengths are inconsistent with the data.
+    InvalidFraming(String),
+    /// A string is not valid UTF-8.
+    InvalidUtf8,
+    /// A type signature is malformed.
+    InvalidSignature(String),
+    /// A size or nesting limit was exceeded.
+    LimitExceeded(String),
+    /// A Rust or GVariant type which cannot be (de)serialized here.
+    UnsupportedType(String),
+    /// Value type does not match the expected signature.
+    SignatureMismatch(String, String),
+    /// I/O error.
+    #[cfg(feature = "std")]
+    Io(std::io::Error),
+}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ErrorKind::Message(ref msg) => f.write_str(msg),
+            ErrorKind::UnexpectedEof => f.write_str("unexpected end of input"),
+            ErrorKind::InvalidFraming(ref msg) => write!(f, "invalid framing: {}", msg),
+            ErrorKind::InvalidUtf8 => f.write_str("invalid UTF-8 string"),
+            ErrorKind::InvalidSignature(ref msg) => write!(f, "invalid signature: {}", msg),
+            ErrorKind::LimitExceeded(ref msg) => write!(f, "limit exceeded: {}", msg),
+            ErrorKind::UnsupportedType(ref ty) => write!(f, "unsupported type: {}", ty),
+            ErrorKind::SignatureMismatch(ref expected, ref actual) => write!(
+                f,
+                "signature mismatch: expected '{}', got '{}'",
+                expected, actual
+            ),
+            #[cfg(feature = "std")]
+            ErrorKind::Io(ref err) => fmt::Display::fmt(err, f),
+        }
+    }
+}
+
+impl From<String> for ErrorKind {
+    fn from(msg: String) -> Self {
+        ErrorKind::Message(msg)
+    }
+}
+
+impl From<&str> for ErrorKind {
+    fn from(msg: &str) -> Self {
+        ErrorKind::Message(msg.to_string())
+    }
+}
+
+/// Error type of this crate.
+///
+/// Besides its kind, an error records where it happened: the byte offset in
+/// the input and the serde path of the value (e.g. `related_objs[3].value`),
+/// when known.
+#[derive(Debug)]
+pub struct Error {
+    kind: ErrorKind,
+    offset: Option<u64>,
+    path: String,
+}
+
+impl Error {
+    /// Build an error of the given kind.
+    pub fn from_kind(kind: ErrorKind) -> Self {
+        Self {
+            kind,
+            offset: None,
+            path: String::new(),
+        }
+    }
+
+    /// Return the kind of this error.
+    pub fn kind(&self) -> &ErrorKind {
+        &self.kind
+    }
+
+    /// Consume this error, returning its kind.
+    pub fn into_kind(self) -> ErrorKind {
+        self.kind
+    }
+
+    /// Return the byte offset in the input where this error happened, if known.
+    pub fn offset(&self) -> Option<u64> {
+        self.offset
+    }
+
+    /// Return the path of the value where this error happened, if known.
+    pub fn path(&self) -> Option<&str> {
+        if self.path.is_empty() {
+            None
+        } else {
+            Some(&self.path)
+        }
+    }
+
+    pub(crate) fn framing<S: Into<String>>(msg: S) -> Self {
+        Self::from_kind(ErrorKind::InvalidFraming(msg.into()))
+    }
+
+    pub(crate) fn signature<S: Into<String>>(msg: S) -> Self {
+        Self::from_kind(ErrorKind::InvalidSignature(msg.into()))
+    }
+
+    pub(crate) fn limit<S: Into<String>>(msg: S) -> Self {
+        Self::from_kind(ErrorKind::LimitExceeded(msg.into()))
+    }
+
+    pub(crate) fn unsupported<S: Into<String>>(ty: S) -> Self {
+        Self::from_kind(ErrorKind::UnsupportedType(ty.into()))
+    }
+
+    pub(crate) fn mismatch<E: ToString, A: ToString>(expected: E, actual: A) -> Self {
+        Self::from_kind(ErrorKind::SignatureMismatch(
+            expected.to_string(),
+            actual.to_string(),
+        ))
+    }
+
+    /// Record the offset of this error, unless a more precise one is known.
+    pub(crate) fn at(mut self, offset: u64) -> Self {
+        self.offset.get_or_insert(offset);
+        self
+    }
+
+    /// Rebase the offset of this error, from a nested slice to its parent.
+    pub(crate) fn shift(mut self, base: u64) -> Self {
+        self.offset = Some(self.offset.unwrap_or(0).saturating_add(base));
+        self
+    }
+
+    /// Prepend a struct field (or dictionary key) to the path of this error.
+    pub(crate) fn field(mut self, name: &str) -> Self {
+        if !self.path.is_empty() && !self.path.starts_with('[') {
+            self.path.insert(0, '.');
+        }
+        self.path.insert_str(0, name);
+        self
+    }
+
+    /// Prepend a sequence index to the path of this error.
+    pub(crate) fn index(mut self, index: usize) -> Self {
+        let segment = alloc::format!("[{}]", index);
+        if !self.path.is_empty() && !self.path.starts_with('[') {
+            self.path.insert(0, '.');
+        }
+        self.path.insert_str(0, &segment);
+        self
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(&self.kind, f)?;
+        if let Some(path) = self.path() {
+            write!(f, " at '{}'", path)?;
+        }
+        if let Some(offset) = self.offset {
+            write!(f, " (byte offset {:#x})", offset)?;
+        }
+        Ok(())
+    }
+}
+
+impl core::error::Error for Error {
+    fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
+        match self.kind {
+            #[cfg(feature = "std")]
+            ErrorKind::Io(ref err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<ErrorKind> for Error {
+    fn from(kind: ErrorKind) -> Self {
+        Self::from_kind(kind)
+    }
+}
+
+impl From<String> for Error {
+    fn from(msg: String) -> Self {
+        Self::from_kind(msg.into())
+    }
+}
+
+impl From<&str> for Error {
+    fn from(msg: &str) -> Self {
+        Self::from_kind(msg.into())
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<std::io::Error> for Error {
+    fn from(err: std::io::Error) -> Self {
+        match err.kind() {
+            std::io::ErrorKind::UnexpectedEof => Self::from_kind(ErrorKind::UnexpectedEof),
+            _ => Self::from_kind(ErrorKind::Io(err)),
+        }
+    }
+}
+
+#[cfg(not(feature = "std"))]
+impl From<crate::io::Error> for Error {
+    fn from(err: crate::io::Error) -> Self {
+        match err.kind() {
+            crate::io::ErrorKind::UnexpectedEof => Self::from_kind(ErrorKind::UnexpectedEof),
+            _ => Self::framing(err.to_string()),
+        }
     }
 }
 
@@ -15,7 +232,7 @@ impl de::Error for Error {
     where
         T: fmt::Display,
     {
-        Self::from(format!("{}", msg))
+        Self::from(msg.to_string())
     }
 }
 
@@ -24,6 +241,16 @@ impl ser::Error for Error {
     where
         T: fmt::Display,
     {
-        Self::from(format!("{}", msg))
+        Self::from(msg.to_string())
     }
 }
+
+/// Return early with an error, built from a message or a format string.
+macro_rules! bail {
+    ($e:expr) => {
+        return Err($e.into())
+    };
+    ($fmt:expr, $($arg:tt)+) => {
+        return Err(alloc::format!($fmt, $($arg)+).into())
+    };
+}
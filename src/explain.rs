@@ -0,0 +1,450 @@
+//! Annotated dumps of serialized values, for debugging encodings.
+
+use crate::errors;
+use crate::framing;
+use crate::variant_type::VariantType;
+use crate::walk::{BasicValue, GVariantVisitor, Walker};
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::fmt;
+use core::ops::Range;
+
+/// Role of a range of bytes in a serialized value.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Role {
+    /// Content of a basic value.
+    Value,
+    /// Alignment padding, or trailing padding of a fixed-size structure.
+    Padding,
+    /// Nul terminator of a string, or marker of a non-empty maybe.
+    Nul,
+    /// Framing offset of a container, `width` bytes wide.
+    FramingOffset {
+        /// Size of the offset, in bytes.
+        width: usize,
+    },
+    /// Separator and type signature at the end of a `v`.
+    SignatureTrailer,
+    /// Bytes which could not be accounted for.
+    Unknown,
+}
+
+impl fmt::Display for Role {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Role::Value => f.pad("value"),
+            Role::Padding => f.pad("padding"),
+            Role::Nul => f.pad("nul"),
+            Role::FramingOffset { width } => f.pad(&format!("offset/{}", width)),
+            Role::SignatureTrailer => f.pad("signature"),
+            Role::Unknown => f.pad("unknown"),
+        }
+    }
+}
+
+/// A labelled range of bytes.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Region {
+    /// Byte range in the explained data.
+    pub range: Range<usize>,
+    /// Role of these bytes.
+    pub role: Role,
+    /// Path of the value these bytes belong to, as in error paths.
+    pub path: String,
+    /// Type of the value these bytes belong to, if known.
+    pub ty: Option<VariantType>,
+    /// Decoded content, for values and framing offsets.
+    pub content: Option<String>,
+    /// Why these bytes are not in normal form, or inconsistent.
+    pub issue: Option<String>,
+}
+
+/// Annotated dump of a serialized value, as returned by `explain`.
+///
+/// Regions cover the whole data, in order. Its `Display` implementation
+/// renders them as an annotated hexdump.
+#[derive(Debug)]
+pub struct Report<'a> {
+    data: &'a [u8],
+    regions: Vec<Region>,
+    error: Option<errors::Error>,
+}
+
+impl<'a> Report<'a> {
+    /// Return the labelled ranges, in order.
+    pub fn regions(&self) -> &[Region] {
+        &self.regions
+    }
+
+    /// Return the first error met while decoding, if any.
+    ///
+    /// The rest of the container it happened in is labelled as unknown, and
+    /// decoding goes on after it.
+    pub fn error(&self) -> Option<&errors::Error> {
+        self.error.as_ref()
+    }
+
+    /// Return whether the data is valid and in normal form.
+    pub fn is_normal(&self) -> bool {
+        self.error.is_none() && self.regions.iter().all(|r| r.issue.is_none())
+    }
+}
+
+impl fmt::Display for Report<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        const WIDTH: usize = 8;
+        for region in &self.regions {
+            let bytes = &self.data[region.range.clone()];
+            let mut chunks = bytes.chunks(WIDTH);
+            let hex = |chunk: Option<&[u8]>| {
+                let hex: Vec<_> = chunk
+                    .unwrap_or_default()
+                    .iter()
+                    .map(|b| format!("{:02x}", b))
+                    .collect();
+                hex.join(" ")
+            };
+            let ty = region.ty.as_ref().map(ToString::to_string);
+            let path = match region.path.as_str() {
+                "" => "/",
+                path => path,
+            };
+            write!(
+                f,
+                "{:08x}  {:<23}  {:<9} {:<6} {}",
+                region.range.start,
+                hex(chunks.next()),
+                region.role,
+                ty.unwrap_or_default(),
+                path
+            )?;
+            if let Some(ref content) = region.content {
+                write!(f, " = {}", content)?;
+            }
+            if let Some(ref issue) = region.issue {
+                write!(f, "  !! {}", issue)?;
+            }
+            writeln!(f)?;
+            for (i, chunk) in chunks.enumerate() {
+                let pos = region.range.start + (i + 1) * WIDTH;
+                writeln!(f, "{:08x}  {}", pos, hex(Some(chunk)))?;
+            }
+        }
+        if let Some(ref err) = self.error {
+            writeln!(f, "error: {}", err)?;
+        }
+        Ok(())
+    }
+}
+
+/// A container being walked.
+struct Frame {
+    ty: VariantType,
+    path: String,
+    /// End of the last part of the container accounted for.
+    pos: usize,
+    /// Index of the next child.
+    index: usize,
+}
+
+/// Visitor labelling what it walks through.
+struct Explainer<'a> {
+    data: &'a [u8],
+    stack: Vec<Frame>,
+    regions: Vec<Region>,
+    /// First error, from a container skipped over.
+    error: Option<errors::Error>,
+}
+
+impl Explainer<'_> {
+    fn push(&mut self, range: Range<usize>, role: Role, path: &str, ty: Option<&VariantType>) {
+        if range.is_empty() {
+            return;
+        }
+        self.regions.push(Region {
+            range,
+            role,
+            path: path.to_string(),
+            ty: ty.cloned(),
+            content: None,
+            issue: None,
+        });
+    }
+
+    /// Label the bytes of the current container up to `pos` as padding.
+    fn pad_to(&mut self, pos: usize) {
+        let (range, path, ty) = match self.stack.last() {
+            Some(frame) if frame.pos < pos => {
+                (frame.pos..pos, frame.path.clone(), frame.ty.clone())
+            }
+            _ => return,
+        };
+        self.pad(range, &path, &ty);
+    }
+
+    fn pad(&mut self, range: Range<usize>, path: &str, ty: &VariantType) {
+        if range.is_empty() {
+            return;
+        }
+        let nonzero = self.data[range.clone()].iter().any(|b| *b != 0x00);
+        self.push(range, Role::Padding, path, Some(ty));
+        if nonzero {
+            self.flag("non-zero padding");
+        }
+    }
+
+    /// Account for the next child of the current container, and return its path.
+    fn child(&mut self, range: &Range<usize>) -> String {
+        self.pad_to(range.start);
+        match self.stack.last_mut() {
+            Some(frame) => {
+                frame.pos = range.end;
+                frame.index += 1;
+                format!("{}[{}]", frame.path, frame.index - 1)
+            }
+            None => String::new(),
+        }
+    }
+
+    /// Record an issue on the last region.
+    fn flag(&mut self, issue: &str) {
+        if let Some(region) = self.regions.last_mut() {
+            region.issue = Some(issue.to_string());
+        }
+    }
+}
+
+impl<'a> GVariantVisitor<'a> for Explainer<'a> {
+    fn visit_basic(&mut self, range: Range<usize>, value: BasicValue<'a>) -> errors::Result<()> {
+        let path = self.child(&range);
+        let ty = basic_type(&value);
+        let content = match value {
+            BasicValue::String(s) | BasicValue::ObjectPath(s) | BasicValue::Signature(s) => {
+                format!("{:?}", s)
+            }
+            BasicValue::Bool(v) => v.to_string(),
+            BasicValue::U8(v) => v.to_string(),
+            BasicValue::U16(v) => v.to_string(),
+            BasicValue::U32(v) => v.to_string(),
+            BasicValue::U64(v) => v.to_string(),
+            BasicValue::I16(v) => v.to_string(),
+            BasicValue::I32(v) => v.to_string(),
+            BasicValue::I64(v) => v.to_string(),
+            BasicValue::F64(v) => v.to_string(),
+            BasicValue::Handle(v) => v.0.to_string(),
+        };
+        let nul = matches!(
+            value,
+            BasicValue::String(_) | BasicValue::ObjectPath(_) | BasicValue::Signature(_)
+        );
+        let end = if nul { range.end - 1 } else { range.end };
+        self.regions.push(Region {
+            range: range.start..end,
+            role: Role::Value,
+            path: path.clone(),
+            ty: Some(ty.clone()),
+            content: Some(content),
+            issue: None,
+        });
+        if let BasicValue::Bool(_) = value {
+            if self.data[range.start] > 1 {
+                self.flag("boolean neither 0 nor 1");
+            }
+        }
+        if nul {
+            self.push(end..range.end, Role::Nul, &path, Some(&ty));
+        }
+        Ok(())
+    }
+
+    fn begin_container(
+        &mut self,
+        range: Range<usize>,
+        ty: &VariantType,
+        _len: usize,
+    ) -> errors::Result<bool> {
+        let path = self.child(&range);
+        self.stack.push(Frame {
+            pos: range.start,
+            ty: ty.clone(),
+            path,
+            index: 0,
+        });
+        Ok(true)
+    }
+
+    fn visit_offsets(&mut self, range: Range<usize>, width: usize) -> errors::Result<()> {
+        self.pad_to(range.start);
+        let frame = match self.stack.last_mut() {
+            Some(frame) => frame,
+            None => return Ok(()),
+        };
+        frame.pos = range.end;
+        let (path, ty) = (frame.path.clone(), frame.ty.clone());
+        for start in range.step_by(width) {
+            let offset = framing::read_offset(self.data, start, width)?;
+            self.regions.push(Region {
+                range: start..start + width,
+                role: Role::FramingOffset { width },
+                path: path.clone(),
+                ty: Some(ty.clone()),
+                content: Some(format!("{:#x}", offset)),
+                issue: None,
+            });
+        }
+        Ok(())
+    }
+
+    fn end_container(&mut self, range: Range<usize>, ty: &VariantType) -> errors::Result<()> {
+        let frame = match self.stack.pop() {
+            Some(frame) => frame,
+            None => return Ok(()),
+        };
+        let rest = frame.pos..range.end;
+        let path = frame.path;
+        match *ty {
+            VariantType::Variant => {
+                let signature = self.data.get(rest.start + 1..rest.end).unwrap_or_default();
+                let signature = String::from_utf8_lossy(signature);
+                self.regions.push(Region {
+                    range: rest,
+                    role: Role::SignatureTrailer,
+                    path,
+                    ty: Some(ty.clone()),
+                    content: Some(format!("{:?}", signature)),
+                    issue: None,
+                });
+            }
+            VariantType::Option(ref inner) if inner.fixed_size().is_none() => {
+                self.push(rest, Role::Nul, &path, Some(ty));
+            }
+            _ if ty.fixed_size().is_some() => self.pad(rest, &path, ty),
+            _ if !rest.is_empty() => {
+                self.push(rest, Role::Unknown, &path, Some(ty));
+                self.flag("unexpected bytes");
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    // Label what is left of the container, and go on with the next value.
+    fn invalid_container(
+        &mut self,
+        range: Range<usize>,
+        ty: &VariantType,
+        offsets: Option<(Range<usize>, usize)>,
+        error: errors::Error,
+    ) -> errors::Result<()> {
+        let frame = match self.stack.pop() {
+            Some(frame) => frame,
+            None => return Err(error),
+        };
+        let table = offsets
+            .as_ref()
+            .map_or(range.end, |(r, _)| r.start.max(frame.pos));
+        if frame.pos < table {
+            self.push(frame.pos..table, Role::Unknown, &frame.path, Some(ty));
+            self.flag("decoding stopped here");
+        }
+        if let Some((table, width)) = offsets {
+            let framing = matches!(error.kind(), errors::ErrorKind::InvalidFraming(_));
+            for start in table.step_by(width).filter(|s| *s >= frame.pos) {
+                let offset = framing::read_offset(self.data, start, width)?;
+                self.regions.push(Region {
+                    range: start..start + width,
+                    role: Role::FramingOffset { width },
+                    path: frame.path.clone(),
+                    ty: Some(ty.clone()),
+                    content: Some(format!("{:#x}", offset)),
+                    issue: None,
+                });
+                if framing {
+                    self.flag("inconsistent framing offsets");
+                }
+            }
+        }
+        if self.error.is_none() {
+            self.error = Some(error.field(&frame.path));
+        }
+        Ok(())
+    }
+}
+
+/// Return the type of a basic value.
+fn basic_type(value: &BasicValue) -> VariantType {
+    match *value {
+        BasicValue::Bool(_) => VariantType::Bool,
+        BasicValue::U8(_) => VariantType::U8,
+        BasicValue::U16(_) => VariantType::U16,
+        BasicValue::U32(_) => VariantType::U32,
+        BasicValue::U64(_) => VariantType::U64,
+        BasicValue::I16(_) => VariantType::I16,
+        BasicValue::I32(_) => VariantType::I32,
+        BasicValue::I64(_) => VariantType::I64,
+        BasicValue::F64(_) => VariantType::F64,
+        BasicValue::String(_) => VariantType::String,
+        BasicValue::ObjectPath(_) => VariantType::ObjectPath,
+        BasicValue::Signature(_) => VariantType::Signature,
+        BasicValue::Handle(_) => VariantType::Handle,
+    }
+}
+
+/// Label the bytes of `data`, serialized as a value of type `signature`.
+pub(crate) fn explain<'a>(data: &'a [u8], signature: &str, network_endian: bool) -> Report<'a> {
+    let mut explainer = Explainer {
+        data,
+        stack: Vec::new(),
+        regions: Vec::new(),
+        error: None,
+    };
+    let error = match VariantType::parse(signature) {
+        Ok(ty) => {
+            let walker = Walker { network_endian };
+            walker.walk(data, &ty, &mut explainer).err()
+        }
+        Err(e) => Some(e),
+    };
+    let error = error.or(explainer.error);
+
+    // Whatever was not walked through is unknown.
+    let mut regions = Vec::with_capacity(explainer.regions.len());
+    let mut pos = 0;
+    for region in explainer.regions.into_iter().chain(Some(Region {
+        range: data.len()..data.len(),
+        role: Role::Unknown,
+        path: String::new(),
+        ty: None,
+        content: None,
+        issue: None,
+    })) {
+        if pos < region.range.start {
+            let range = pos..region.range.start;
+            let at = error.as_ref().and_then(|e| e.offset()).map(|o| o as usize);
+            regions.push(Region {
+                issue: Some(match at {
+                    Some(at) if range.contains(&at) || at == range.end => {
+                        "decoding stopped here".to_string()
+                    }
+                    _ => "unexplained bytes".to_string(),
+                }),
+                range,
+                role: Role::Unknown,
+                path: String::new(),
+                ty: None,
+                content: None,
+            });
+        }
+        pos = pos.max(region.range.end);
+        if !region.range.is_empty() {
+            regions.push(region);
+        }
+    }
+
+    Report {
+        data,
+        regions,
+        error,
+    }
+}
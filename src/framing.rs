@@ -0,0 +1,212 @@
+//! Framing offsets and alignment helpers, shared by slice-based codecs.
+
+use crate::errors;
+use alloc::format;
+use alloc::vec::Vec;
+
+/// Round `pos` up to the next multiple of `alignment`.
+pub(crate) fn align(pos: usize, alignment: usize) -> usize {
+    if alignment <= 1 {
+        return pos;
+    }
+    pos.div_ceil(alignment) * alignment
+}
+
+/// Size of framing offsets in a container of `len` bytes.
+pub(crate) fn offset_size(len: usize) -> usize {
+    if len as u64 > u64::from(u32::MAX) {
+        8
+    } else if len > usize::from(u16::MAX) {
+        4
+    } else if len > usize::from(u8::MAX) {
+        2
+    } else if len > 0 {
+        1
+    } else {
+        0
+    }
+}
+
+/// Read a (little-endian) framing offset of `size` bytes at `pos`.
+pub(crate) fn read_offset(data: &[u8], pos: usize, size: usize) -> errors::Result<usize> {
+    let end = pos
+        .checked_add(size)
+        .filter(|end| size > 0 && *end <= data.len())
+        .ok_or_else(|| {
+            errors::Error::framing(format!("offset out of bounds - size={}", size)).at(pos as u64)
+        })?;
+    let mut val = 0u64;
+    for (i, b) in data[pos..end].iter().enumerate() {
+        val |= u64::from(*b) << (8 * i);
+    }
+    Ok(val as usize)
+}
+
+/// Append the framing offsets `ends` to a container starting at `start`.
+///
+/// Offsets are as narrow as the final container length allows.
+pub(crate) fn write_offsets(buf: &mut Vec<u8>, start: usize, ends: &[usize]) {
+    if ends.is_empty() {
+        return;
+    }
+    let size = offsets_width((buf.len() - start) as u64, ends.len() as u64);
+    for end in ends {
+        buf.extend_from_slice(&(*end as u64).to_le_bytes()[..size]);
+    }
+}
+
+/// Size of framing offsets, for `count` of them after `body` bytes.
+pub(crate) fn offsets_width(body: u64, count: u64) -> usize {
+    [1, 2, 4, 8]
+        .iter()
+        .copied()
+        .find(|size| offset_size((body + count * *size as u64) as usize) <= *size)
+        .unwrap_or(8)
+}
+
+/// Return the number of elements in an array, and where its offsets start.
+///
+/// Elements are optionally `fixed`-sized; for variable-sized ones, the
+/// offsets table starts right after the last element.
+pub(crate) fn array_len(data: &[u8], fixed: Option<usize>) -> errors::Result<(usize, usize)> {
+    if let Some(size) = fixed {
+        if !data.len().is_multiple_of(size) {
+            return Err(errors::Error::framing(format!(
+                "array length {:#x} not a multiple of element size {}",
+                data.len(),
+                size
+            )));
+        }
+        return Ok((data.len() / size, data.len()));
+    }
+
+    if data.is_empty() {
+        return Ok((0, 0));
+    }
+    let osize = offset_size(data.len());
+    let last_end = read_offset(data, data.len() - osize, osize)?;
+    let table_len = data
+        .len()
+        .checked_sub(last_end)
+        .ok_or_else(|| errors::Error::framing("array offsets table out of bounds"))?;
+    if !table_len.is_multiple_of(osize) {
+        return Err(errors::Error::framing("array offsets table misaligned"));
+    }
+    Ok((table_len / osize, last_end))
+}
+
+/// Return the byte range of element `index` in an array.
+///
+/// `table` is the start of the offsets table, as returned by `array_len`.
+pub(crate) fn array_element(
+    data: &[u8],
+    elem_align: usize,
+    fixed: Option<usize>,
+    table: usize,
+    index: usize,
+) -> errors::Result<(usize, usize)> {
+    if let Some(size) = fixed {
+        return Ok((index * size, (index + 1) * size));
+    }
+
+    let osize = offset_size(data.len());
+    let end = read_offset(data, table + index * osize, osize)?;
+    let prev_end = match index {
+        0 => 0,
+        _ => read_offset(data, table + (index - 1) * osize, osize)?,
+    };
+    let start = align(prev_end, elem_align);
+    if start > end || end > table {
+        return Err(errors::Error::framing(format!(
+            "array element {} out of bounds - end={:#x}",
+            index, end
+        ))
+        .at(start as u64));
+    }
+    Ok((start, end))
+}
+
+/// Split an array of `elem_align`-aligned, optionally `fixed`-sized elements.
+///
+/// Returns the byte range of each element.
+pub(crate) fn split_array(
+    data: &[u8],
+    elem_align: usize,
+    fixed: Option<usize>,
+) -> errors::Result<Vec<(usize, usize)>> {
+    let (count, table) = array_len(data, fixed)?;
+    (0..count)
+        .map(|i| array_element(data, elem_align, fixed, table, i))
+        .collect()
+}
+
+/// Return the content of a maybe value, if any.
+pub(crate) fn maybe_content(data: &[u8], inner_fixed: bool) -> errors::Result<Option<&[u8]>> {
+    if data.is_empty() {
+        return Ok(None);
+    }
+    if inner_fixed {
+        return Ok(Some(data));
+    }
+    match data.split_last() {
+        Some((0x00, content)) => Ok(Some(content)),
+        _ => Err(errors::Error::framing(
+            "maybe value without trailing zero byte",
+        )),
+    }
+}
+
+/// Split a structure (or dictionary entry) into its fields.
+///
+/// Each field is given as `(alignment, fixed_size)`; returns the byte range
+/// of each field.
+pub(crate) fn split_fields(
+    data: &[u8],
+    fields: &[(usize, Option<usize>)],
+) -> errors::Result<Vec<(usize, usize)>> {
+    let osize = offset_size(data.len());
+    let mut ranges = Vec::with_capacity(fields.len());
+    let mut pos = 0;
+    let mut offsets_read = 0;
+    for (i, &(alignment, fixed)) in fields.iter().enumerate() {
+        let start = align(pos, alignment);
+        let end = match fixed {
+            Some(size) => start + size,
+            None if i + 1 == fields.len() => data
+                .len()
+                .checked_sub(offsets_read * osize)
+                .ok_or_else(|| errors::Error::framing("structure too short"))?,
+            None => {
+                offsets_read += 1;
+                let pos = data
+                    .len()
+                    .checked_sub(offsets_read * osize)
+                    .ok_or_else(|| errors::Error::framing("structure too short"))?;
+                read_offset(data, pos, osize)?
+            }
+        };
+        let limit = data.len().saturating_sub(offsets_read * osize);
+        if start > end || end > limit {
+            return Err(errors::Error::framing(format!(
+                "structure field {} out of bounds - end={:#x}",
+                i, end
+            ))
+            .at(start as u64));
+        }
+        ranges.push((start, end));
+        pos = end;
+    }
+    Ok(ranges)
+}
+
+/// Split a serialized `v` into its value and type signature.
+pub(crate) fn split_variant(data: &[u8]) -> errors::Result<(&[u8], &str)> {
+    let sep = data
+        .iter()
+        .rposition(|b| *b == 0x00)
+        .ok_or_else(|| errors::Error::framing("variant without signature separator"))?;
+    let signature = core::str::from_utf8(&data[sep + 1..]).map_err(|_| {
+        errors::Error::signature("variant signature is not valid UTF-8").at(sep as u64 + 1)
+    })?;
+    Ok((&data[..sep], signature))
+}
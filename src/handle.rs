@@ -0,0 +1,202 @@
+//! File descriptor handles (`h`).
+//!
+//! A handle is the index of a file descriptor in a list passed alongside the
+//! serialized data, e.g. as ancillary data over a Unix socket. With `std` on
+//! Unix, `Fd` values are collected into such a list by
+//! `Config::serialize_with_fds`, and taken back out of it by
+//! `Config::deserialize_slice_with_fds`.
+
+use crate::variant::ser::HANDLE_TOKEN;
+use core::fmt;
+use serde::{de, ser};
+
+#[cfg(all(feature = "std", unix))]
+pub use self::fds::Fd;
+#[cfg(all(feature = "std", unix))]
+pub(crate) use self::fds::{collect_fds, pass_fds};
+
+/// Index of a file descriptor, in the list passed alongside (signature: `h`).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Handle(pub i32);
+
+impl ser::Serialize for Handle {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: ser::Serializer,
+    {
+        serializer.serialize_newtype_struct(HANDLE_TOKEN, &self.0)
+    }
+}
+
+impl<'de> de::Deserialize<'de> for Handle {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        deserializer.deserialize_newtype_struct(HANDLE_TOKEN, HandleVisitor)
+    }
+}
+
+struct HandleVisitor;
+
+impl<'de> de::Visitor<'de> for HandleVisitor {
+    type Value = Handle;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a file descriptor handle")
+    }
+
+    fn visit_i32<E>(self, v: i32) -> Result<Handle, E>
+    where
+        E: de::Error,
+    {
+        Ok(Handle(v))
+    }
+
+    fn visit_newtype_struct<D>(self, deserializer: D) -> Result<Handle, D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        de::Deserialize::deserialize(deserializer).map(Handle)
+    }
+}
+
+#[cfg(all(feature = "std", unix))]
+mod fds {
+    use super::Handle;
+    use crate::errors;
+    use serde::{de, ser};
+    use std::cell::RefCell;
+    use std::convert::TryFrom;
+    use std::os::fd::{AsFd, AsRawFd, BorrowedFd, OwnedFd, RawFd};
+    use std::thread::LocalKey;
+
+    std::thread_local! {
+        /// Descriptors collected by the serialization in progress, if any.
+        static COLLECTED: RefCell<Option<Vec<OwnedFd>>> = const { RefCell::new(None) };
+        /// Descriptors passed to the deserialization in progress, if any.
+        static PASSED: RefCell<Option<Vec<Option<OwnedFd>>>> = const { RefCell::new(None) };
+    }
+
+    /// An owned file descriptor, (de)serialized as a handle (`h`).
+    ///
+    /// On serialization, the descriptor is duplicated into the list returned
+    /// by `Config::serialize_with_fds`. On deserialization, it is taken from
+    /// the list given to `Config::deserialize_slice_with_fds`. Anywhere else,
+    /// (de)serializing an `Fd` fails.
+    #[derive(Debug)]
+    pub struct Fd(OwnedFd);
+
+    impl From<OwnedFd> for Fd {
+        fn from(fd: OwnedFd) -> Self {
+            Fd(fd)
+        }
+    }
+
+    impl From<Fd> for OwnedFd {
+        fn from(fd: Fd) -> Self {
+            fd.0
+        }
+    }
+
+    impl AsFd for Fd {
+        fn as_fd(&self) -> BorrowedFd<'_> {
+            self.0.as_fd()
+        }
+    }
+
+    impl AsRawFd for Fd {
+        fn as_raw_fd(&self) -> RawFd {
+            self.0.as_raw_fd()
+        }
+    }
+
+    impl ser::Serialize for Fd {
+        fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+        where
+            S: ser::Serializer,
+        {
+            use serde::ser::Error;
+            let fd = self.0.try_clone().map_err(S::Error::custom)?;
+            let index = COLLECTED.with(|cell| match *cell.borrow_mut() {
+                Some(ref mut fds) => {
+                    fds.push(fd);
+                    i32::try_from(fds.len() - 1).map_err(|_| "too many file descriptors")
+                }
+                None => Err("file descriptors need `serialize_with_fds`"),
+            });
+            Handle(index.map_err(S::Error::custom)?).serialize(serializer)
+        }
+    }
+
+    impl<'de> de::Deserialize<'de> for Fd {
+        fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+        where
+            D: de::Deserializer<'de>,
+        {
+            use serde::de::Error;
+            let Handle(index) = Handle::deserialize(deserializer)?;
+            let fd = PASSED.with(|cell| match *cell.borrow_mut() {
+                Some(ref mut fds) => usize::try_from(index)
+                    .ok()
+                    .and_then(|i| fds.get_mut(i))
+                    .ok_or_else(|| format!("handle {} out of range", index))?
+                    .take()
+                    .ok_or_else(|| format!("handle {} already taken", index)),
+                None => Err("file descriptors need `deserialize_slice_with_fds`".to_string()),
+            });
+            fd.map(Fd).map_err(D::Error::custom)
+        }
+    }
+
+    /// Install `fds` in `slot` for the duration of `f`, then take them back.
+    ///
+    /// The previous content is restored afterwards, even on panic.
+    fn scoped<T, R, F>(slot: &'static LocalKey<RefCell<Option<T>>>, fds: T, f: F) -> (R, T)
+    where
+        F: FnOnce() -> R,
+    {
+        struct Restore<T: 'static> {
+            slot: &'static LocalKey<RefCell<Option<T>>>,
+            prev: Option<Option<T>>,
+        }
+
+        impl<T> Drop for Restore<T> {
+            fn drop(&mut self) {
+                if let Some(prev) = self.prev.take() {
+                    self.slot.with(|cell| cell.replace(prev));
+                }
+            }
+        }
+
+        let prev = slot.with(|cell| cell.replace(Some(fds)));
+        let mut guard = Restore {
+            slot,
+            prev: Some(prev),
+        };
+        let res = f();
+        let prev = guard.prev.take().unwrap_or_default();
+        let fds = slot.with(|cell| cell.replace(prev));
+        (res, fds.expect("file descriptor list vanished"))
+    }
+
+    /// Run a serialization, collecting the file descriptors of `Fd` values.
+    pub(crate) fn collect_fds<T, F>(f: F) -> errors::Result<(T, Vec<OwnedFd>)>
+    where
+        F: FnOnce() -> errors::Result<T>,
+    {
+        let (res, fds) = scoped(&COLLECTED, vec![], f);
+        res.map(|value| (value, fds))
+    }
+
+    /// Run a deserialization, passing `fds` to `Fd` values.
+    ///
+    /// Descriptors left untaken are closed.
+    pub(crate) fn pass_fds<T, F>(fds: Vec<OwnedFd>, f: F) -> errors::Result<T>
+    where
+        F: FnOnce() -> errors::Result<T>,
+    {
+        let fds = fds.into_iter().map(Some).collect();
+        scoped(&PASSED, fds, f).0
+    }
+}
@@ -0,0 +1,211 @@
+//! Minimal I/O layer, backed by `std::io` when available.
+//!
+//! Without `std`, this provides just enough of the `std::io` and `byteorder`
+//! traits for encoding into vectors and decoding from slices.
+
+#[cfg(feature = "std")]
+pub(crate) use byteorder::{ReadBytesExt, WriteBytesExt};
+#[cfg(feature = "std")]
+pub(crate) use std::io::{Cursor, Error, ErrorKind, Read, Result, Seek, SeekFrom, Write};
+
+#[cfg(not(feature = "std"))]
+pub(crate) use self::core_io::*;
+
+#[cfg(not(feature = "std"))]
+mod core_io {
+    use alloc::vec::Vec;
+    use byteorder::ByteOrder;
+    use core::fmt;
+
+    pub(crate) type Result<T> = core::result::Result<T, Error>;
+
+    /// Kinds of I/O errors.
+    #[derive(Clone, Copy, Debug, Eq, PartialEq)]
+    pub(crate) enum ErrorKind {
+        InvalidInput,
+        UnexpectedEof,
+    }
+
+    /// I/O error.
+    #[derive(Debug)]
+    pub(crate) struct Error {
+        kind: ErrorKind,
+    }
+
+    impl Error {
+        pub(crate) fn kind(&self) -> ErrorKind {
+            self.kind
+        }
+    }
+
+    impl From<ErrorKind> for Error {
+        fn from(kind: ErrorKind) -> Self {
+            Self { kind }
+        }
+    }
+
+    impl fmt::Display for Error {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            match self.kind {
+                ErrorKind::InvalidInput => f.write_str("invalid input"),
+                ErrorKind::UnexpectedEof => f.write_str("unexpected end of input"),
+            }
+        }
+    }
+
+    /// Position to seek to.
+    #[derive(Clone, Copy, Debug)]
+    pub(crate) enum SeekFrom {
+        Start(u64),
+        End(i64),
+        Current(i64),
+    }
+
+    pub(crate) trait Read {
+        fn read(&mut self, buf: &mut [u8]) -> Result<usize>;
+
+        fn read_exact(&mut self, buf: &mut [u8]) -> Result<()> {
+            let mut pos = 0;
+            while pos < buf.len() {
+                match self.read(&mut buf[pos..])? {
+                    0 => return Err(ErrorKind::UnexpectedEof.into()),
+                    n => pos += n,
+                }
+            }
+            Ok(())
+        }
+    }
+
+    pub(crate) trait Seek {
+        fn seek(&mut self, pos: SeekFrom) -> Result<u64>;
+
+        fn stream_position(&mut self) -> Result<u64> {
+            self.seek(SeekFrom::Current(0))
+        }
+    }
+
+    pub(crate) trait Write {
+        fn write_all(&mut self, buf: &[u8]) -> Result<()>;
+    }
+
+    impl Write for Vec<u8> {
+        fn write_all(&mut self, buf: &[u8]) -> Result<()> {
+            self.extend_from_slice(buf);
+            Ok(())
+        }
+    }
+
+    impl<W: Write + ?Sized> Write for &mut W {
+        fn write_all(&mut self, buf: &[u8]) -> Result<()> {
+            (**self).write_all(buf)
+        }
+    }
+
+    /// In-memory reader over a byte slice.
+    #[derive(Debug)]
+    pub(crate) struct Cursor<T> {
+        inner: T,
+        pos: u64,
+    }
+
+    impl<T> Cursor<T> {
+        pub(crate) fn new(inner: T) -> Self {
+            Self { inner, pos: 0 }
+        }
+
+        pub(crate) fn get_ref(&self) -> &T {
+            &self.inner
+        }
+
+        pub(crate) fn position(&self) -> u64 {
+            self.pos
+        }
+
+        pub(crate) fn set_position(&mut self, pos: u64) {
+            self.pos = pos;
+        }
+    }
+
+    impl Read for Cursor<&[u8]> {
+        fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+            let start = (self.pos as usize).min(self.inner.len());
+            let len = buf.len().min(self.inner.len() - start);
+            buf[..len].copy_from_slice(&self.inner[start..start + len]);
+            self.pos += len as u64;
+            Ok(len)
+        }
+    }
+
+    impl Seek for Cursor<&[u8]> {
+        fn seek(&mut self, pos: SeekFrom) -> Result<u64> {
+            let (base, offset) = match pos {
+                SeekFrom::Start(n) => {
+                    self.pos = n;
+                    return Ok(n);
+                }
+                SeekFrom::End(n) => (self.inner.len() as u64, n),
+                SeekFrom::Current(n) => (self.pos, n),
+            };
+            self.pos = base
+                .checked_add_signed(offset)
+                .ok_or(ErrorKind::InvalidInput)?;
+            Ok(self.pos)
+        }
+    }
+
+    macro_rules! read_number {
+        ($name:ident, $ty:ty, $size:expr, $method:ident) => {
+            fn $name<B: ByteOrder>(&mut self) -> Result<$ty> {
+                let mut buf = [0; $size];
+                self.read_exact(&mut buf)?;
+                Ok(B::$method(&buf))
+            }
+        };
+    }
+
+    macro_rules! write_number {
+        ($name:ident, $ty:ty, $size:expr, $method:ident) => {
+            fn $name<B: ByteOrder>(&mut self, n: $ty) -> Result<()> {
+                let mut buf = [0; $size];
+                B::$method(&mut buf, n);
+                self.write_all(&buf)
+            }
+        };
+    }
+
+    /// Subset of `byteorder::ReadBytesExt`.
+    pub(crate) trait ReadBytesExt: Read {
+        fn read_u8(&mut self) -> Result<u8> {
+            let mut buf = [0; 1];
+            self.read_exact(&mut buf)?;
+            Ok(buf[0])
+        }
+
+        read_number!(read_u16, u16, 2, read_u16);
+        read_number!(read_i16, i16, 2, read_i16);
+        read_number!(read_u32, u32, 4, read_u32);
+        read_number!(read_i32, i32, 4, read_i32);
+        read_number!(read_u64, u64, 8, read_u64);
+        read_number!(read_i64, i64, 8, read_i64);
+        read_number!(read_f64, f64, 8, read_f64);
+    }
+
+    impl<R: Read + ?Sized> ReadBytesExt for R {}
+
+    /// Subset of `byteorder::WriteBytesExt`.
+    pub(crate) trait WriteBytesExt: Write {
+        fn write_u8(&mut self, n: u8) -> Result<()> {
+            self.write_all(&[n])
+        }
+
+        write_number!(write_u16, u16, 2, write_u16);
+        write_number!(write_i16, i16, 2, write_i16);
+        write_number!(write_u32, u32, 4, write_u32);
+        write_number!(write_i32, i32, 4, write_i32);
+        write_number!(write_u64, u64, 8, write_u64);
+        write_number!(write_i64, i64, 8, write_i64);
+        write_number!(write_f64, f64, 8, write_f64);
+    }
+
+    impl<W: Write + ?Sized> WriteBytesExt for W {}
+}
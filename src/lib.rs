@@ -21,46 +21,181 @@
 //!     assert_eq!(target, decoded);
 //! }
 //! ```
+//!
+//! ### Serde attributes
+//!
+//!  * `#[serde(skip)]` fields are neither encoded nor decoded.
+//!  * Trailing fields left out by `#[serde(skip_serializing_if)]` are
+//!    decoded back from their `#[serde(default)]`, with
+//!    `Config::trailing_defaults`. Leaving out any other field is rejected
+//!    as `UnsupportedType`.
+//!  * `#[serde(flatten)]` turns a struct into a map, which `to_variant` and
+//!    `from_variant` convert to and from an `a{sv}` dictionary. Binary
+//!    encoding does not box values: the fields of such a struct must all
+//!    have the same type, or it is rejected as `UnsupportedType`. It is
+//!    decoded back through `Config::signature`.
+//!
+//! ### Type mapping
+//!
+//! GVariant has no `f32`, `char` or `i8`. An `f32` is encoded as a `d`, and
+//! range-checked when decoded. A `char` and an `i8` are mapped as set by
+//! `Config::char_type` and `Config::i8_type`.
+//!
+//! ### Empty containers
+//!
+//! Serde does not tell the element types of empty sequences and maps, nor the
+//! inner type of `None`, while their alignment depends on it. They are laid
+//! out exactly as the type set by `Config::signature`, or as told by a later
+//! element of the same sequence or map, and are otherwise not aligned at all.
+//!
+//! ### File descriptors
+//!
+//! A `Handle` (`h`) is the index of a file descriptor in a list passed
+//! alongside the data. On Unix, `Fd` values are collected into such a list by
+//! `Config::serialize_with_fds`, and taken from it by
+//! `Config::deserialize_slice_with_fds`.
+//!
+//! ### Self-describing decoding
+//!
+//! Targets such as `#[serde(untagged)]` enums, `serde_json::Value` or
+//! `IgnoredAny` need the type of the data. A `v` describes itself, other
+//! inputs can be described upfront with `Config::signature`.
+//!
+//! Without a signature, the padding before a nested container is told by its
+//! first value. Containers whose first value is less aligned than a later one,
+//! or holding variable-sized elements, need a signature when not aligned, and
+//! are otherwise rejected.
+//!
+//! ### Event-based traversal
+//!
+//! `walk` calls back a `GVariantVisitor` on the containers and basic values of
+//! serialized data of a known type, without decoding the rest of it.
+//! `explain` builds on it to label each byte of a value as an annotated
+//! hexdump, flagging non-normal or invalid regions.
+//!
+//! ### Features
+//!
+//!  * `std` (default): reader-based entry points. Without it, the crate is
+//!    `no_std` (with `alloc`), and works on slices only.
+//!  * `mmap`: memory-mapped entry points, over `memmap2`.
+//!  * `tokio`: async entry points over `tokio` I/O.
 
-#[macro_use]
-extern crate error_chain;
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
 #[macro_use]
 extern crate log;
 #[macro_use]
 extern crate serde;
+
 #[macro_use]
-extern crate serde_derive;
+pub mod errors;
 
+#[cfg(feature = "std")]
+mod array;
+#[cfg(feature = "tokio")]
+mod async_io;
 mod config;
 mod de;
-pub mod errors;
+mod explain;
+mod framing;
+mod handle;
+mod io;
+#[cfg(feature = "mmap")]
+mod mmap;
 mod ser;
 mod variant;
+mod variant_ref;
+mod variant_type;
+mod walk;
+
+use alloc::string::String;
+use alloc::vec::Vec;
 
-pub use crate::config::Config;
+#[cfg(feature = "std")]
+pub use crate::array::{ArrayIter, ArrayWriter};
+#[cfg(feature = "tokio")]
+pub use crate::async_io::{from_async_read, to_async_write};
+pub use crate::config::{CharType, Config, I8Type};
+pub use crate::explain::{Region, Report, Role};
+#[cfg(all(feature = "std", unix))]
+pub use crate::handle::Fd;
+pub use crate::handle::Handle;
+#[cfg(feature = "mmap")]
+pub use crate::mmap::MappedFile;
 pub use crate::variant::Array;
+pub use crate::variant::Change;
 pub use crate::variant::Dictionary;
+pub use crate::variant::Maybe;
 pub use crate::variant::Structure;
+pub use crate::variant::VarDict;
 pub use crate::variant::Variant;
+pub use crate::variant_ref::VariantRef;
+pub use crate::variant_type::{BasicVariantType, StaticVariantType, VariantType};
+pub use crate::walk::{BasicValue, GVariantVisitor};
 
 /// Get a default configuration object.
 ///
 /// ### Default Configuration
 ///  * Nesting limit: none
+///  * Canonical mode: disabled
 ///  * Endianness: little
+///  * Input length limit (non-seekable readers): 128 MiB
+///  * Trailing defaults: disabled
+///  * `char` type: `u`
+///  * `i8` type: `n`
+///  * Signature: none, only `v` values are self-describing
 pub fn config() -> config::Config {
     config::Config::new()
 }
 
 /// Serializes a serializable object into a `Vec` of bytes using the default configuration.
-pub fn to_vec<T: ?Sized>(value: &T) -> errors::Result<Vec<u8>>
+pub fn to_vec<T>(value: &T) -> errors::Result<Vec<u8>>
 where
-    T: serde::Serialize,
+    T: ?Sized + serde::Serialize,
 {
     config().serialize(value)
 }
 
+/// Serializes a serializable object into a `Vec` of bytes, along with its type signature.
+///
+/// Values with empty containers or `None` are rejected, as their signature is
+/// not known: see `Config::serialize_with_signature`.
+pub fn to_vec_with_signature<T>(value: &T) -> errors::Result<(Vec<u8>, String)>
+where
+    T: ?Sized + serde::Serialize,
+{
+    config().serialize_with_signature(value)
+}
+
+/// Converts a serializable object into a `Variant` tree, without encoding it.
+///
+/// Structs and tuples become `Structure`, sequences `Array`, maps `Dictionary`
+/// and options `Option`. A `Variant` value is itself boxed as a `v` container.
+///
+/// Element types of empty sequences and maps, and inner types of `None`, are
+/// not known to serde: they default to `v` (and `s` for keys) unless sibling
+/// values tell otherwise.
+pub fn to_variant<T>(value: &T) -> errors::Result<Variant>
+where
+    T: ?Sized + serde::Serialize,
+{
+    config().serialize_variant(value)
+}
+
+/// Deserializes a `Variant` tree into an instance of `T`.
+///
+/// This is the reverse of `to_variant`: a `v` container is transparently
+/// unboxed, and dictionaries can also be mapped onto structs by field name.
+pub fn from_variant<T>(value: Variant) -> errors::Result<T>
+where
+    T: serde::de::DeserializeOwned,
+{
+    T::deserialize(value)
+}
+
 /// Deserializes an object directly from a `Read`er using the default configuration.
+#[cfg(feature = "std")]
 pub fn from_reader<R, T>(reader: R) -> errors::Result<T>
 where
     R: std::io::Read + std::io::Seek,
@@ -69,6 +204,70 @@ where
     config().deserialize_reader(reader)
 }
 
+/// Deserializes an object from a non-seekable `Read`er using the default configuration.
+///
+/// Input is buffered in memory first, see `Config::max_input_len`.
+#[cfg(feature = "std")]
+pub fn from_read<R, T>(reader: R) -> errors::Result<T>
+where
+    R: std::io::Read,
+    T: serde::de::DeserializeOwned,
+{
+    config().deserialize_read(reader)
+}
+
+/// Maps a file in memory, for borrowed deserialization of its content.
+///
+/// # Safety
+///
+/// The file must not be modified or truncated while it is mapped.
+#[cfg(feature = "mmap")]
+pub unsafe fn open_mmap<P>(path: P) -> errors::Result<MappedFile>
+where
+    P: AsRef<std::path::Path>,
+{
+    MappedFile::open(path)
+}
+
+/// Deserializes the content of a file into an instance of `T` using the default configuration.
+///
+/// The file is memory-mapped rather than read, and unmapped before returning:
+/// `T` cannot borrow from it. To decode borrowed strings and byte arrays, keep
+/// the mapping around with `open_mmap` and use `MappedFile::deserialize`.
+/// Files which may be written to concurrently are better read with `from_read`.
+///
+/// # Safety
+///
+/// The mapping is decoded as a plain byte slice, which Rust assumes does not
+/// change while it is borrowed. The file must thus not be modified or
+/// truncated (by this process or any other) while it is being deserialized:
+/// this is undefined behavior, and truncation may raise `SIGBUS`.
+#[cfg(feature = "mmap")]
+pub unsafe fn from_file<P, T>(path: P) -> errors::Result<T>
+where
+    P: AsRef<std::path::Path>,
+    T: serde::de::DeserializeOwned,
+{
+    open_mmap(path)?.deserialize()
+}
+
+/// Walks a serialized value of type `ty`, calling back `visitor` on each part of it.
+///
+/// Nothing is decoded beyond basic values, which are borrowed from `bytes`.
+pub fn walk<'a, V>(bytes: &'a [u8], ty: &VariantType, visitor: &mut V) -> errors::Result<()>
+where
+    V: GVariantVisitor<'a> + ?Sized,
+{
+    config().walk(bytes, ty, visitor)
+}
+
+/// Labels each byte of a value serialized as type `signature`, for debugging.
+///
+/// Invalid or non-normal data is reported, not rejected: see `Report`.
+pub fn explain<'a>(bytes: &'a [u8], signature: &str) -> Report<'a> {
+    config().explain(bytes, signature)
+}
+
 /// Deserializes a slice of bytes into an instance of `T` using the default configuration.
 pub fn from_slice<'a, T>(bytes: &'a [u8]) -> errors::Result<T>
 where
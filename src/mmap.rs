@@ -0,0 +1,63 @@
+//! Memory-mapped files, for borrowed decoding of large inputs.
+
+use crate::errors;
+use std::fs;
+use std::io;
+use std::ops::Deref;
+use std::path::Path;
+
+/// A read-only memory-mapped file, dereferencing to its content.
+///
+/// Values deserialized from it (e.g. through `Config::deserialize_slice`)
+/// can borrow strings and byte arrays straight from the mapping.
+#[derive(Debug)]
+pub struct MappedFile {
+    map: memmap2::Mmap,
+}
+
+impl MappedFile {
+    /// Map the file at `path`.
+    ///
+    /// # Safety
+    ///
+    /// The file must not be modified or truncated while it is mapped,
+    /// otherwise its content may change under borrowed values.
+    pub unsafe fn open<P: AsRef<Path>>(path: P) -> errors::Result<Self> {
+        let path = path.as_ref();
+        let context = |action, err: io::Error| {
+            let msg = format!("failed to {} '{}': {}", action, path.display(), err);
+            io::Error::new(err.kind(), msg)
+        };
+        let fp = fs::File::open(path).map_err(|e| context("open", e))?;
+        let map = memmap2::Mmap::map(&fp).map_err(|e| context("map", e))?;
+        Ok(Self { map })
+    }
+
+    /// Return the file content.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.map
+    }
+
+    /// Deserializes the file content into an instance of `T`, using the
+    /// default configuration.
+    pub fn deserialize<'a, T>(&'a self) -> errors::Result<T>
+    where
+        T: serde::Deserialize<'a>,
+    {
+        crate::from_slice(self.as_bytes())
+    }
+}
+
+impl Deref for MappedFile {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        self.as_bytes()
+    }
+}
+
+impl AsRef<[u8]> for MappedFile {
+    fn as_ref(&self) -> &[u8] {
+        self.as_bytes()
+    }
+}
@@ -1,24 +1,96 @@
 use crate::config;
-use crate::errors::{self, ResultExt};
-use byteorder::{BigEndian, LittleEndian, WriteBytesExt};
+use crate::errors;
+use crate::framing;
+use crate::io;
+use crate::io::WriteBytesExt;
+use crate::variant::encode::Encoder;
+use crate::variant::ser::{
+    merge, retype, VariantSerializer, HANDLE_TOKEN, OBJECT_PATH_TOKEN, SIGNATURE_TOKEN,
+    TYPED_TOKEN, VALUE_TOKEN, VARIANT_TOKEN,
+};
+use crate::variant::{Structure, Variant};
+use crate::variant_type::VariantType;
+use alloc::boxed::Box;
+use alloc::collections::BTreeMap;
+use alloc::format;
+use alloc::vec;
+use alloc::vec::Vec;
+use byteorder::{BigEndian, LittleEndian};
+use core::mem;
 use serde::Serialize;
-use serde::{self, ser, ser::Error};
-use std::io;
+use serde::{self, ser};
 
 #[derive(Debug)]
 pub(crate) struct Properties {
     pub(crate) fixed_size: bool,
     pub(crate) size: u64,
+    pub(crate) ty: VariantType,
 }
 
+/// Whether all `v` in the `guess` type of an element are told by `merged`.
+///
+/// Actual `v` values are not told apart from guesses, so some arrays are
+/// deemed to have unknown types while they do not.
+fn told(guess: &VariantType, merged: &VariantType) -> bool {
+    match (guess, merged) {
+        (VariantType::Variant, merged) => *merged != VariantType::Variant,
+        (VariantType::Vec(a), VariantType::Vec(b)) => told(a, b),
+        (VariantType::Option(a), VariantType::Option(b)) => told(a, b),
+        (VariantType::Structure(a), VariantType::Structure(b)) => {
+            a.iter().zip(b).all(|(a, b)| told(a, b))
+        }
+        (VariantType::DictEntry(_, a), VariantType::DictEntry(_, b)) => told(a, b),
+        _ => true,
+    }
+}
+
+/// Build the tree of a value whose type was partly guessed, so that it can
+/// be laid out again if siblings tell its type.
+///
+/// The file descriptors of `Fd` values would be collected twice: values
+/// holding some are not kept.
+fn guessed_tree<T>(value: &T, options: &config::Config) -> Option<Variant>
+where
+    T: ?Sized + Serialize,
+{
+    let serializer = VariantSerializer::new(options);
+    #[cfg(all(feature = "std", unix))]
+    {
+        match crate::handle::collect_fds(|| value.serialize(serializer)) {
+            Ok((tree, fds)) if fds.is_empty() => Some(tree),
+            _ => None,
+        }
+    }
+    #[cfg(not(all(feature = "std", unix)))]
+    {
+        value.serialize(serializer).ok()
+    }
+}
+
+/// Error for a value laid out as a guessed type that siblings told otherwise.
+fn relayout_error(ty: &VariantType) -> errors::Error {
+    errors::Error::unsupported(format!(
+        "'{}', with values laid out before their type was known",
+        ty
+    ))
+}
+
+/// Containers are written apart first, as their alignment (and so the
+/// padding before them) depends on their content.
 #[derive(Debug)]
 pub(crate) struct SerSeq<'a, W: 'a> {
-    pub(crate) cur_offset: u64,
+    pub(crate) buffer: Serializer<Vec<u8>>,
     pub(crate) framing_offsets: Vec<u64>,
-    // PERFOPT(lucab): write-once
-    pub(crate) fixed_size: bool,
+    pub(crate) elem_type: Option<VariantType>,
+    /// Element type, when known from a signature.
+    pub(crate) expected: Option<VariantType>,
+    /// Elements with guessed parts, which siblings may tell: their index,
+    /// type and tree, if it could be built.
+    pub(crate) guesses: Vec<(usize, VariantType, Option<Variant>)>,
+    /// Where variable-sized elements start, before their framing offsets.
+    pub(crate) starts: Vec<u64>,
+    pub(crate) len: usize,
     pub(crate) serializer: &'a mut Serializer<W>,
-    pub(crate) size: u64,
 }
 
 impl<'a, W> ser::SerializeSeq for SerSeq<'a, W>
@@ -28,70 +100,121 @@ where
     type Ok = Properties;
     type Error = errors::Error;
 
-    fn serialize_element<T: ?Sized>(&mut self, value: &T) -> errors::Result<()>
+    fn serialize_element<T>(&mut self, value: &T) -> errors::Result<()>
     where
-        T: Serialize,
+        T: ?Sized + Serialize,
     {
         // Serialize this element
-        let p = value
-            .serialize(&mut *self.serializer)
-            .chain_err(|| "failed to serialize array element")?;
-
-        // Update total array size
-        self.size = self
-            .size
-            .checked_add(p.size)
-            .ok_or_else(|| Self::Error::custom("array length overflowed"))?;
-
-        // Update current position/offset
-        self.cur_offset = self
-            .cur_offset
-            .checked_add(p.size)
-            .ok_or_else(|| Self::Error::custom("current offset overflowed"))?;
-
-        // Record whether elements are fixed size
-        self.fixed_size = p.fixed_size;
+        let p = self
+            .buffer
+            .serialize_expected(value, self.expected.clone())
+            .map_err(|e| e.index(self.len))?;
+        if mem::take(&mut self.buffer.guessed) {
+            let tree = guessed_tree(value, &self.buffer.options);
+            self.guesses.push((self.len, p.ty.clone(), tree));
+        }
+        // Same check as in `ArrayWriter::push`.
+        self.elem_type = Some(match self.elem_type.take() {
+            None => p.ty,
+            Some(ty) if merge(ty.clone(), p.ty.clone()) == merge(p.ty.clone(), ty.clone()) => {
+                merge(ty, p.ty)
+            }
+            Some(ty) => return Err(errors::Error::mismatch(&ty, &p.ty).index(self.len)),
+        });
+        self.len += 1;
 
         // If element is variable-sized, records where it ends
         if !p.fixed_size {
-            self.framing_offsets.push(self.cur_offset);
-            // Add value length to total size
-            self.size = self
-                .size
-                .checked_add(1)
-                .ok_or_else(|| Self::Error::custom("array length overflowed"))?;
+            self.starts.push(self.buffer.current_pos - p.size);
+            self.framing_offsets.push(self.buffer.current_pos);
         }
 
         Ok(())
     }
 
-    // TODO(lucab): lengths longer that u8::MAX
-    fn end(self) -> errors::Result<Properties> {
-        // If variable-sized, append all framings offsets.
-        // Framing offsets are unaligned and little-endian.
-        for off in self.framing_offsets {
-            self.serializer
-                .writer
-                .write_u8(off as u8)
-                .chain_err(|| "failed to serialize array framings")?;
+    fn end(mut self) -> errors::Result<Properties> {
+        // Like empty arrays in `Variant` trees, unknown element types are `v`.
+        let elem = match self.elem_type.take() {
+            Some(elem) => elem,
+            None => {
+                self.buffer.guessed = true;
+                VariantType::Variant
+            }
+        };
+        let guessed = self.guesses.iter().any(|(_, ty, _)| !told(ty, &elem));
+        if self.guesses.iter().any(|(_, ty, _)| *ty != elem) {
+            self.relayout(&elem)?;
         }
+        self.buffer.guessed |= guessed;
+
+        // If variable-sized, append all framings offsets.
+        self.buffer.write_offsets(0, self.framing_offsets.iter())?;
 
+        // Arrays are never fixed-size, whatever their elements.
+        let ty = VariantType::Vec(Box::new(elem));
         let p = Properties {
-            fixed_size: self.fixed_size,
-            // Total size already accounts for framings too.
-            size: self.size,
+            fixed_size: false,
+            size: self.serializer.write_nested(self.buffer, &ty)?,
+            ty,
         };
         Ok(p)
     }
 }
 
+impl<'a, W> SerSeq<'a, W>
+where
+    W: io::Write,
+{
+    /// Lay out all elements again as `elem`, which siblings told the guessed
+    /// ones to be.
+    ///
+    /// Elements with guessed types were only aligned as their content is, so
+    /// they are written again from their trees, and the others moved along.
+    fn relayout(&mut self, elem: &VariantType) -> errors::Result<()> {
+        // Guessed types are never fixed-size, nor are the types they merge with.
+        if self.starts.len() != self.len {
+            return Err(relayout_error(elem));
+        }
+        let mut buffer = self.serializer.nested();
+        let mut framing_offsets = Vec::with_capacity(self.len);
+        let mut guesses = mem::take(&mut self.guesses).into_iter().peekable();
+        let bounds = self.starts.iter().zip(&self.framing_offsets);
+        for (index, (&start, &end)) in bounds.enumerate() {
+            match guesses.next_if(|(i, _, _)| *i == index) {
+                Some((_, _, Some(tree))) => {
+                    let tree = retype(tree, elem).map_err(|e| e.index(index))?;
+                    buffer.write_variant(&tree)?;
+                }
+                Some((_, _, None)) => return Err(relayout_error(elem).index(index)),
+                None => {
+                    let bytes = &self.buffer.writer[start as usize..end as usize];
+                    buffer.pad_align(elem.alignment() as u64)?;
+                    buffer.writer.extend_from_slice(bytes);
+                    buffer.current_pos += end - start;
+                }
+            }
+            framing_offsets.push(buffer.current_pos);
+        }
+        self.buffer = buffer;
+        self.framing_offsets = framing_offsets;
+        Ok(())
+    }
+}
+
 #[derive(Debug)]
 pub(crate) struct SerStruct<'a, W: 'a> {
-    pub(crate) cur_field: u64,
-    pub(crate) cur_offset: u64,
+    pub(crate) buffer: Serializer<Vec<u8>>,
     pub(crate) framing_offsets: Vec<u64>,
-    pub(crate) name: String,
-    pub(crate) num_fields: u64,
+    pub(crate) fixed_size: bool,
+    pub(crate) last_fixed: bool,
+    pub(crate) fields: Vec<VariantType>,
+    /// Field types, when known from a signature.
+    pub(crate) expected: Option<Vec<VariantType>>,
+    /// Whether this is a container paired with its signature.
+    pub(crate) typed: bool,
+    /// First field left out, which only trailing fields may be.
+    pub(crate) skipped: Option<&'static str>,
+    pub(crate) len: usize,
     pub(crate) serializer: &'a mut Serializer<W>,
 }
 
@@ -102,53 +225,297 @@ where
     type Ok = Properties;
     type Error = errors::Error;
 
-    fn serialize_field<T: ?Sized>(&mut self, _key: &'static str, value: &T) -> errors::Result<()>
+    fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> errors::Result<()>
     where
-        T: Serialize,
+        T: ?Sized + Serialize,
     {
-        // Serialize this field
-        let p = value.serialize(&mut *self.serializer)?;
-        self.cur_field = self
-            .cur_field
-            .checked_add(1)
-            .ok_or_else(|| Self::Error::custom("field count overflowed"))?;
-        self.cur_offset = self
-            .cur_offset
-            .checked_add(p.size)
-            .ok_or_else(|| Self::Error::custom("current offset overflowed"))?;
+        // Fields have no name in GVariant: they could not be told apart.
+        if let Some(skipped) = self.skipped {
+            let msg = format!("field '{}' skipped before '{}'", skipped, key);
+            return Err(errors::Error::unsupported(msg));
+        }
+        self.push(value).map_err(|e| e.field(key))
+    }
+
+    fn skip_field(&mut self, key: &'static str) -> errors::Result<()> {
+        self.skipped = self.skipped.or(Some(key));
+        Ok(())
+    }
+
+    fn end(self) -> errors::Result<Properties> {
+        self.finish()
+    }
+}
 
-        // If variable-sized and not the last field, records where it ends
-        let last = self.cur_field == self.num_fields;
-        if !p.fixed_size && !last {
-            self.framing_offsets.push(self.cur_offset);
+impl<'a, W> SerStruct<'a, W>
+where
+    W: io::Write,
+{
+    /// Serialize the next field.
+    fn push<T>(&mut self, value: &T) -> errors::Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        let expected = self
+            .expected
+            .as_ref()
+            .and_then(|fields| fields.get(self.len).cloned());
+        let p = self.buffer.serialize_expected(value, expected)?;
+        self.fixed_size &= p.fixed_size;
+        self.last_fixed = p.fixed_size;
+        self.fields.push(p.ty);
+        self.len += 1;
+
+        // If variable-sized, records where it ends
+        if !p.fixed_size {
+            self.framing_offsets.push(self.buffer.current_pos);
         }
 
         Ok(())
     }
 
+    fn finish(mut self) -> errors::Result<Properties> {
+        // Like `()`, a structure without fields is a single 0x00 byte.
+        if self.len == 0 {
+            return ser::Serializer::serialize_unit(self.serializer);
+        }
+
+        // Fixed-size structures are unframed, and padded to their alignment.
+        let ty = VariantType::Structure(self.fields);
+        if self.fixed_size {
+            self.buffer.pad_align(ty.alignment() as u64)?;
+        }
+
+        // Fields skipped through serde attributes are never written, so the
+        // last one is only known here: its end is the end of the structure.
+        if !self.last_fixed {
+            self.framing_offsets.pop();
+        }
+
+        // Append all framings offsets, in reverse order.
+        self.buffer
+            .write_offsets(0, self.framing_offsets.iter().rev())?;
+
+        let p = Properties {
+            fixed_size: self.fixed_size,
+            size: self.serializer.write_nested(self.buffer, &ty)?,
+            ty,
+        };
+        Ok(p)
+    }
+}
+
+impl<'a, W> ser::SerializeTuple for SerStruct<'a, W>
+where
+    W: io::Write,
+{
+    type Ok = Properties;
+    type Error = errors::Error;
+
+    fn serialize_element<T>(&mut self, value: &T) -> errors::Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        let index = self.len;
+        self.push(value).map_err(|e| e.index(index))
+    }
+
     fn end(self) -> errors::Result<Properties> {
-        // Fixed size
-        if self.framing_offsets.is_empty() {
-            let p = Properties {
-                fixed_size: true,
-                size: self.cur_offset as u64,
+        self.finish()
+    }
+}
+
+impl<'a, W> ser::SerializeTupleStruct for SerStruct<'a, W>
+where
+    W: io::Write,
+{
+    type Ok = Properties;
+    type Error = errors::Error;
+
+    fn serialize_field<T>(&mut self, value: &T) -> errors::Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        // The signature is not written, but sets the type of the container.
+        if self.typed && self.expected.is_none() {
+            let options = &self.buffer.options;
+            let ty = match value.serialize(VariantSerializer::new(options))? {
+                Variant::String(signature) => VariantType::parse(&signature)?,
+                other => return Err(errors::Error::mismatch("s", other.signature())),
             };
-            return Ok(p);
+            self.expected = Some(vec![ty]);
+            return Ok(());
+        }
+        ser::SerializeTuple::serialize_element(self, value)
+    }
+
+    fn end(mut self) -> errors::Result<Properties> {
+        if !self.typed {
+            return self.finish();
+        }
+        let ty = self
+            .fields
+            .pop()
+            .ok_or_else(|| errors::Error::from("variant: container without signature"))?;
+        let p = Properties {
+            fixed_size: false,
+            size: self.serializer.write_nested(self.buffer, &ty)?,
+            ty,
+        };
+        Ok(p)
+    }
+}
+
+/// An entry written apart, with the tree of its value if of a guessed type.
+type Entry = (Serializer<Vec<u8>>, Properties, Option<Variant>);
+
+/// Entries are written apart first, so that they are laid out sorted by key,
+/// whatever the iteration order of the map.
+pub(crate) struct SerMap<'a, W: 'a> {
+    /// Serialized entries, by key.
+    pub(crate) entries: BTreeMap<Variant, Entry>,
+    /// Key of the entry being serialized, and the entry so far.
+    pub(crate) next: Option<(Variant, Serializer<Vec<u8>>, Properties)>,
+    pub(crate) entry_type: Option<VariantType>,
+    /// Key and value types, when known from a signature.
+    pub(crate) expected: Option<(VariantType, VariantType)>,
+    /// Types of entries with guessed parts, which siblings may tell.
+    pub(crate) guesses: Vec<VariantType>,
+    pub(crate) serializer: &'a mut Serializer<W>,
+}
+
+impl<'a, W> ser::SerializeMap for SerMap<'a, W>
+where
+    W: io::Write,
+{
+    type Ok = Properties;
+    type Error = errors::Error;
+
+    fn serialize_key<T>(&mut self, key: &T) -> errors::Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        let index = self.entries.len();
+        // Keys are also built as a tree, to sort entries by.
+        let options = &self.serializer.options;
+        let name = key
+            .serialize(VariantSerializer::new(options))
+            .map_err(|e| e.index(index))?;
+        let mut entry = self.serializer.nested();
+        let expected = self.expected.as_ref().map(|(k, _)| k.clone());
+        let p = entry
+            .serialize_expected(key, expected)
+            .map_err(|e| e.index(index))?;
+        if !p.ty.is_basic() {
+            let msg = format!("dictionary: non-basic key type '{}'", p.ty);
+            return Err(errors::Error::signature(msg).index(index));
+        }
+        self.next = Some((name, entry, p));
+        Ok(())
+    }
+
+    fn serialize_value<T>(&mut self, value: &T) -> errors::Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        let (key, mut entry, k) = self
+            .next
+            .take()
+            .ok_or_else(|| errors::Error::from("dictionary value without key"))?;
+        let index = self.entries.len();
+        let path = |e: errors::Error| match key.as_str() {
+            Some(name) => e.field(name),
+            None => e.index(index),
         };
+        let expected = self.expected.as_ref().map(|(_, v)| v.clone());
+        let v = entry.serialize_expected(value, expected).map_err(path)?;
 
-        // Non-fixed size, append all framings offsets except the last one.
-        // Framing offsets are unaligned and little-endian.
-        let size = self.framing_offsets.last().cloned().unwrap();
-        if size > u64::from(::std::u8::MAX) {
-            return Err(Self::Error::custom("unsupported"));
+        // Keys are never guessed, so they must all have the very same type.
+        if let Some(VariantType::DictEntry(ref prev, _)) = self.entry_type {
+            if **prev != k.ty {
+                return Err(path(errors::Error::mismatch(prev, &k.ty)));
+            }
         }
-        for off in self.framing_offsets {
-            self.serializer.writer.write_u8(off as u8)?;
+
+        // Entries are laid out as two-field structures.
+        let fixed_size = k.fixed_size && v.fixed_size;
+        let ty = VariantType::DictEntry(Box::new(k.ty), Box::new(v.ty));
+        if fixed_size {
+            entry.pad_align(ty.alignment() as u64)?;
+        } else if !k.fixed_size {
+            // The key starts the entry, so it ends at its size.
+            entry.write_offsets(0, [k.size].iter())?;
         }
+        let tree = if entry.guessed {
+            self.guesses.push(ty.clone());
+            guessed_tree(value, &entry.options)
+        } else {
+            None
+        };
+
+        // Same check as in `SerSeq`. Heterogeneous values (e.g. from
+        // `#[serde(flatten)]`) are not boxed as in `to_variant`, as the type
+        // of the map would then depend on its content.
+        self.entry_type = Some(match self.entry_type.take() {
+            None => ty.clone(),
+            Some(prev) if merge(prev.clone(), ty.clone()) == merge(ty.clone(), prev.clone()) => {
+                merge(prev, ty.clone())
+            }
+            Some(prev) => {
+                let msg = format!("dictionary with both '{}' and '{}' entries", prev, ty);
+                return Err(path(errors::Error::unsupported(msg)));
+            }
+        });
 
+        let p = Properties {
+            fixed_size,
+            size: entry.current_pos,
+            ty,
+        };
+        self.entries.insert(key, (entry, p, tree));
+        Ok(())
+    }
+
+    fn end(self) -> errors::Result<Properties> {
+        // Like empty sequences, empty maps of unknown type are `a{sv}`.
+        let (entry, mut guessed) = match self.entry_type {
+            Some(entry) => (entry, false),
+            None => {
+                let k = Box::new(VariantType::String);
+                (
+                    VariantType::DictEntry(k, Box::new(VariantType::Variant)),
+                    true,
+                )
+            }
+        };
+        guessed |= self.guesses.iter().any(|ty| !told(ty, &entry));
+
+        let mut buffer = self.serializer.nested();
+        let mut framing_offsets = Vec::with_capacity(self.entries.len());
+        for (key, (nested, p, tree)) in self.entries {
+            if !nested.guessed || p.ty == entry {
+                buffer.write_nested(nested, &p.ty)?;
+            } else if let (Some(value), VariantType::DictEntry(_, v)) = (tree, &entry) {
+                // Only aligned as its content is: written again as told by
+                // siblings, entries being laid out as two-field structures.
+                let value = retype(value, v)?;
+                buffer.write_variant(&Variant::Structure(Structure::new(vec![key, value])))?;
+            } else {
+                return Err(relayout_error(&VariantType::Vec(Box::new(entry))));
+            }
+            // If variable-sized, records where it ends
+            if !p.fixed_size {
+                framing_offsets.push(buffer.current_pos);
+            }
+        }
+        buffer.guessed = guessed;
+        buffer.write_offsets(0, framing_offsets.iter())?;
+
+        let ty = VariantType::Vec(Box::new(entry));
         let p = Properties {
             fixed_size: false,
-            size: size as u64,
+            size: self.serializer.write_nested(buffer, &ty)?,
+            ty,
         };
         Ok(p)
     }
@@ -159,27 +526,142 @@ pub(crate) struct Serializer<W> {
     pub(crate) current_pos: u64,
     pub(crate) writer: W,
     pub(crate) options: config::Config,
+    /// Type of the next value, when known from a signature.
+    pub(crate) expected: Option<VariantType>,
+    /// Whether the type of an empty container or `None` was not known.
+    pub(crate) guessed: bool,
+    /// Largest alignment of what was written so far.
+    pub(crate) alignment: u64,
 }
 
 impl<W> Serializer<W>
 where
     W: io::Write,
 {
+    /// Serialize a child value, checking it against its `expected` type.
+    pub(crate) fn serialize_expected<T>(
+        &mut self,
+        value: &T,
+        expected: Option<VariantType>,
+    ) -> errors::Result<Properties>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.expected = expected;
+        let expected = self.expected.clone();
+        let p = value.serialize(&mut *self)?;
+        match expected {
+            Some(ty) if ty != p.ty => Err(errors::Error::mismatch(&ty, &p.ty)),
+            _ => Ok(p),
+        }
+    }
+
+    /// Start a serializer for a container, written apart at an aligned position.
+    fn nested(&self) -> Serializer<Vec<u8>> {
+        Serializer {
+            current_pos: 0,
+            writer: vec![],
+            options: self.options.clone(),
+            expected: None,
+            guessed: false,
+            alignment: 1,
+        }
+    }
+
+    /// Write a container serialized apart, aligned to its type, and return its size.
+    ///
+    /// If parts of its type were guessed, the container is only aligned as
+    /// its content is, which is the best guess of its actual alignment.
+    fn write_nested(
+        &mut self,
+        nested: Serializer<Vec<u8>>,
+        ty: &VariantType,
+    ) -> errors::Result<u64> {
+        let alignment = if nested.guessed {
+            nested.alignment
+        } else {
+            ty.alignment() as u64
+        };
+        self.pad_align(alignment)?;
+        self.writer.write_all(&nested.writer)?;
+        self.current_pos = self
+            .current_pos
+            .checked_add(nested.current_pos)
+            .ok_or_else(|| errors::Error::limit("container length overflowed"))?;
+        self.guessed |= nested.guessed;
+        Ok(nested.current_pos)
+    }
+
     fn pad_align(&mut self, alignment: u64) -> errors::Result<u64> {
+        self.alignment = self.alignment.max(alignment);
         if alignment <= 1 {
             return Ok(0);
         }
         let padding = (alignment - (self.current_pos % alignment)) % alignment;
         // TODO(lucab): buffer writes
         for _ in 0..padding {
-            self.writer.write_u8(0x00).chain_err(|| "failed to pad")?;
+            self.writer.write_u8(0x00)?;
         }
         self.current_pos = self
             .current_pos
             .checked_add(padding)
-            .ok_or_else(|| errors::Error::custom("alignment padding overflowed"))?;
+            .ok_or_else(|| errors::Error::limit("alignment padding overflowed"))?;
         Ok(padding)
     }
+
+    /// Write the framing offsets of the container starting at `start`.
+    ///
+    /// Framing offsets are unaligned and little-endian, and as wide as the
+    /// whole container requires.
+    fn write_offsets<'o>(
+        &mut self,
+        start: u64,
+        offsets: impl ExactSizeIterator<Item = &'o u64>,
+    ) -> errors::Result<()> {
+        let count = offsets.len() as u64;
+        let width = framing::offsets_width(self.current_pos - start, count);
+        for off in offsets {
+            self.writer.write_all(&off.to_le_bytes()[..width])?;
+        }
+        self.current_pos += count * width as u64;
+        Ok(())
+    }
+
+    /// In normal form, all NaNs are the same and zero is never negative.
+    fn normalize_f64(&self, v: f64) -> f64 {
+        if !self.options.canonical {
+            v
+        } else if v.is_nan() {
+            f64::NAN
+        } else if v == 0.0 {
+            0.0
+        } else {
+            v
+        }
+    }
+
+    /// Write a value built as a tree, aligned to its type.
+    fn write_variant(&mut self, value: &Variant) -> errors::Result<Properties> {
+        let ty = value.variant_type();
+        self.pad_align(ty.alignment() as u64)?;
+        let encoder = Encoder {
+            network_endian: self.options.network_endian,
+        };
+        let bytes = if self.options.canonical {
+            let mut value = value.clone();
+            value.normalize();
+            encoder.encode(&value)
+        } else {
+            encoder.encode(value)
+        };
+        self.writer.write_all(&bytes)?;
+        self.current_pos += bytes.len() as u64;
+        Ok(Properties {
+            fixed_size: ty.fixed_size().is_some(),
+            size: bytes.len() as u64,
+            ty,
+        })
+    }
 }
 
 impl<'a, W> serde::Serializer for &'a mut Serializer<W>
@@ -190,31 +672,31 @@ where
     type Error = errors::Error;
 
     type SerializeSeq = SerSeq<'a, W>;
-    type SerializeTuple = ser::Impossible<Self::Ok, Self::Error>;
-    type SerializeTupleStruct = ser::Impossible<Self::Ok, Self::Error>;
+    type SerializeTuple = SerStruct<'a, W>;
+    type SerializeTupleStruct = SerStruct<'a, W>;
     type SerializeTupleVariant = ser::Impossible<Self::Ok, Self::Error>;
-    type SerializeMap = ser::Impossible<Self::Ok, Self::Error>;
+    type SerializeMap = SerMap<'a, W>;
     type SerializeStruct = SerStruct<'a, W>;
     type SerializeStructVariant = ser::Impossible<Self::Ok, Self::Error>;
 
     fn serialize_unit(self) -> errors::Result<Self::Ok> {
-        self.writer
-            .write_u8(0x00)
-            .chain_err(|| "failed to serialize unit")?;
+        self.writer.write_u8(0x00)?;
+        self.current_pos += 1;
         let p = Properties {
             fixed_size: true,
             size: 1,
+            ty: VariantType::Structure(vec![]),
         };
         Ok(p)
     }
 
     fn serialize_unit_struct(self, _: &'static str) -> errors::Result<Self::Ok> {
-        self.writer
-            .write_u8(0x00)
-            .chain_err(|| "failed to serialize unit struct")?;
+        self.writer.write_u8(0x00)?;
+        self.current_pos += 1;
         let p = Properties {
             fixed_size: true,
             size: 1,
+            ty: VariantType::Structure(vec![]),
         };
         Ok(p)
     }
@@ -223,12 +705,11 @@ where
         let size = 1;
         let _pad = self.pad_align(size)?;
         let byte: u8 = if v { 1 } else { 0 };
-        self.writer
-            .write_u8(byte)
-            .chain_err(|| "failed to serialize bool")?;
+        self.writer.write_u8(byte)?;
         let p = Properties {
             fixed_size: true,
             size,
+            ty: VariantType::Bool,
         };
         self.current_pos += size;
         Ok(p)
@@ -237,12 +718,11 @@ where
     fn serialize_u8(self, v: u8) -> errors::Result<Self::Ok> {
         let size = 1;
         let _pad = self.pad_align(size)?;
-        self.writer
-            .write_u8(v)
-            .chain_err(|| "failed to serialize u8")?;
+        self.writer.write_u8(v)?;
         let p = Properties {
             fixed_size: true,
             size,
+            ty: VariantType::U8,
         };
         self.current_pos += size;
         Ok(p)
@@ -252,17 +732,14 @@ where
         let size = 2;
         let _pad = self.pad_align(size)?;
         if self.options.network_endian {
-            self.writer
-                .write_u16::<BigEndian>(v)
-                .chain_err(|| "failed to serialize u16")?;
+            self.writer.write_u16::<BigEndian>(v)?;
         } else {
-            self.writer
-                .write_u16::<LittleEndian>(v)
-                .chain_err(|| "failed to serialize u16")?;
+            self.writer.write_u16::<LittleEndian>(v)?;
         }
         let p = Properties {
             fixed_size: true,
             size,
+            ty: VariantType::U16,
         };
         self.current_pos += size;
         Ok(p)
@@ -272,17 +749,14 @@ where
         let size = 4;
         let _pad = self.pad_align(size)?;
         if self.options.network_endian {
-            self.writer
-                .write_u32::<BigEndian>(v)
-                .chain_err(|| "failed to serialize u32")?;
+            self.writer.write_u32::<BigEndian>(v)?;
         } else {
-            self.writer
-                .write_u32::<LittleEndian>(v)
-                .chain_err(|| "failed to serialize u32")?;
+            self.writer.write_u32::<LittleEndian>(v)?;
         }
         let p = Properties {
             fixed_size: true,
             size,
+            ty: VariantType::U32,
         };
         self.current_pos += size;
         Ok(p)
@@ -292,51 +766,38 @@ where
         let size = 8;
         let _pad = self.pad_align(size)?;
         if self.options.network_endian {
-            self.writer
-                .write_u64::<BigEndian>(v)
-                .chain_err(|| "failed to serialize u64")?;
+            self.writer.write_u64::<BigEndian>(v)?;
         } else {
-            self.writer
-                .write_u64::<LittleEndian>(v)
-                .chain_err(|| "failed to serialize u64")?;
+            self.writer.write_u64::<LittleEndian>(v)?;
         }
         let p = Properties {
             fixed_size: true,
             size,
+            ty: VariantType::U64,
         };
         self.current_pos += size;
         Ok(p)
     }
 
     fn serialize_i8(self, v: i8) -> errors::Result<Self::Ok> {
-        let size = 1;
-        let _pad = self.pad_align(size)?;
-        self.writer
-            .write_i8(v)
-            .chain_err(|| "failed to serialize u8")?;
-        let p = Properties {
-            fixed_size: true,
-            size,
-        };
-        self.current_pos += size;
-        Ok(p)
+        match self.options.i8_type {
+            config::I8Type::I16 => self.serialize_i16(i16::from(v)),
+            config::I8Type::Reject => Err(errors::Error::unsupported("i8")),
+        }
     }
 
     fn serialize_i16(self, v: i16) -> errors::Result<Self::Ok> {
         let size = 2;
         let _pad = self.pad_align(size)?;
         if self.options.network_endian {
-            self.writer
-                .write_i16::<BigEndian>(v)
-                .chain_err(|| "failed to serialize i16")?;
+            self.writer.write_i16::<BigEndian>(v)?;
         } else {
-            self.writer
-                .write_i16::<LittleEndian>(v)
-                .chain_err(|| "failed to serialize i16")?;
+            self.writer.write_i16::<LittleEndian>(v)?;
         }
         let p = Properties {
             fixed_size: true,
             size,
+            ty: VariantType::I16,
         };
         self.current_pos += size;
         Ok(p)
@@ -346,17 +807,14 @@ where
         let size = 4;
         let _pad = self.pad_align(size)?;
         if self.options.network_endian {
-            self.writer
-                .write_i32::<BigEndian>(v)
-                .chain_err(|| "failed to serialize i32")?;
+            self.writer.write_i32::<BigEndian>(v)?;
         } else {
-            self.writer
-                .write_i32::<LittleEndian>(v)
-                .chain_err(|| "failed to serialize i32")?;
+            self.writer.write_i32::<LittleEndian>(v)?;
         }
         let p = Properties {
             fixed_size: true,
             size,
+            ty: VariantType::I32,
         };
         self.current_pos += size;
         Ok(p)
@@ -366,17 +824,14 @@ where
         let size = 8;
         let _pad = self.pad_align(size)?;
         if self.options.network_endian {
-            self.writer
-                .write_i64::<BigEndian>(v)
-                .chain_err(|| "failed to serialize i64")?;
+            self.writer.write_i64::<BigEndian>(v)?;
         } else {
-            self.writer
-                .write_i64::<LittleEndian>(v)
-                .chain_err(|| "failed to serialize i64")?;
+            self.writer.write_i64::<LittleEndian>(v)?;
         }
         let p = Properties {
             fixed_size: true,
             size,
+            ty: VariantType::I64,
         };
         self.current_pos += size;
         Ok(p)
@@ -386,19 +841,16 @@ where
         // Internally promote to f64.
         let size = 8;
         let _pad = self.pad_align(size)?;
-        let double = f64::from(v);
+        let double = self.normalize_f64(f64::from(v));
         if self.options.network_endian {
-            self.writer
-                .write_f64::<BigEndian>(double)
-                .chain_err(|| "failed to serialize f64")?;
+            self.writer.write_f64::<BigEndian>(double)?;
         } else {
-            self.writer
-                .write_f64::<LittleEndian>(double)
-                .chain_err(|| "failed to serialize f64")?;
+            self.writer.write_f64::<LittleEndian>(double)?;
         }
         let p = Properties {
             fixed_size: true,
             size,
+            ty: VariantType::F64,
         };
         self.current_pos += size;
         Ok(p)
@@ -407,58 +859,56 @@ where
     fn serialize_f64(self, v: f64) -> errors::Result<Self::Ok> {
         let size = 8;
         let _pad = self.pad_align(size)?;
+        let v = self.normalize_f64(v);
         if self.options.network_endian {
-            self.writer
-                .write_f64::<BigEndian>(v)
-                .chain_err(|| "failed to serialize f64")?;
+            self.writer.write_f64::<BigEndian>(v)?;
         } else {
-            self.writer
-                .write_f64::<LittleEndian>(v)
-                .chain_err(|| "failed to serialize f64")?;
+            self.writer.write_f64::<LittleEndian>(v)?;
         }
         let p = Properties {
             fixed_size: true,
             size,
+            ty: VariantType::F64,
         };
         self.current_pos += size;
         Ok(p)
     }
 
     fn serialize_str(self, v: &str) -> errors::Result<Self::Ok> {
-        let size =
-            v.len()
-                .checked_add(1)
-                .ok_or_else(|| Self::Error::custom("string length overflowed"))? as u64;
+        let size = v
+            .len()
+            .checked_add(1)
+            .ok_or_else(|| errors::Error::limit("string length overflowed"))?
+            as u64;
         for b in v.as_bytes() {
-            self.writer
-                .write_u8(*b)
-                .chain_err(|| "failed to serialize string character")?;
+            self.writer.write_u8(*b)?;
         }
-        self.writer
-            .write_u8(0x00)
-            .chain_err(|| "failed to serialize string terminator")?;
+        self.writer.write_u8(0x00)?;
         let p = Properties {
             fixed_size: false,
             size,
+            ty: VariantType::String,
         };
         self.current_pos += size;
         Ok(p)
     }
 
-    fn serialize_char(self, _c: char) -> errors::Result<Self::Ok> {
-        Err(Self::Error::custom("unsupported"))
+    fn serialize_char(self, c: char) -> errors::Result<Self::Ok> {
+        match self.options.char_type {
+            config::CharType::U32 => self.serialize_u32(u32::from(c)),
+            config::CharType::String => self.serialize_str(c.encode_utf8(&mut [0; 4])),
+        }
     }
 
     fn serialize_bytes(self, v: &[u8]) -> errors::Result<Self::Ok> {
         let size = v.len() as u64;
         for b in v {
-            self.writer
-                .write_u8(*b)
-                .chain_err(|| "failed to serialize byte element")?;
+            self.writer.write_u8(*b)?;
         }
         let p = Properties {
             fixed_size: false,
             size,
+            ty: VariantType::Vec(Box::new(VariantType::U8)),
         };
         self.current_pos += size;
         Ok(p)
@@ -467,38 +917,47 @@ where
     fn serialize_none(self) -> errors::Result<Self::Ok> {
         // Fixed-Size inner: empty byte sequence.
         // Non-Fixed-Size inner: empty byte sequence.
+        // Like `None` in `Variant` trees, the unknown inner type is `v`.
+        // Maybe types are never fixed-size.
+        // Like other containers, it is aligned to its type, if known.
+        let ty = match self.expected.take() {
+            Some(ty @ VariantType::Option(_)) => {
+                self.pad_align(ty.alignment() as u64)?;
+                ty
+            }
+            _ => {
+                self.guessed = true;
+                VariantType::Option(Box::new(VariantType::Variant))
+            }
+        };
         let p = Properties {
-            fixed_size: true,
+            fixed_size: false,
             size: 0,
+            ty,
         };
         Ok(p)
     }
 
-    fn serialize_some<T: ?Sized>(self, value: &T) -> errors::Result<Self::Ok>
+    fn serialize_some<T>(self, value: &T) -> errors::Result<Self::Ok>
     where
-        T: ser::Serialize,
+        T: ?Sized + ser::Serialize,
     {
-        let buf: Vec<u8> = Vec::new();
-
-        let mut first = Serializer {
-            current_pos: self.current_pos,
-            writer: buf,
-            options: self.options.clone(),
-        };
+        let mut first = self.nested();
 
         // Fixed-Size inner: just data.
         // Non-Fixed-Size inner: data + 0x00.
-        let mut prop = value.serialize(&mut first)?;
+        let expected = match self.expected.take() {
+            Some(VariantType::Option(inner)) => Some(*inner),
+            _ => None,
+        };
+        let mut prop = first.serialize_expected(value, expected)?;
         if !prop.fixed_size {
             let terminator = 0u8;
             terminator.serialize(&mut first)?;
-            prop.size = prop
-                .size
-                .checked_add(1)
-                .ok_or_else(|| Self::Error::custom("option-some length overflowed"))?;
         };
-        self.writer.write_all(&first.writer)?;
-        self.current_pos += prop.size;
+        prop.ty = VariantType::Option(Box::new(prop.ty));
+        prop.size = self.write_nested(first, &prop.ty)?;
+        prop.fixed_size = false;
         Ok(prop)
     }
 
@@ -508,39 +967,59 @@ where
             None => vec![],
         };
 
+        let expected = match self.expected.take() {
+            Some(VariantType::Vec(elem)) => Some(*elem),
+            _ => None,
+        };
         let s = Self::SerializeSeq {
-            cur_offset: 0,
+            buffer: self.nested(),
             framing_offsets: framings,
-            fixed_size: false,
+            elem_type: expected.clone(),
+            expected,
+            guesses: vec![],
+            starts: vec![],
+            len: 0,
             serializer: self,
-            size: 0,
         };
         Ok(s)
     }
 
-    fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple, Self::Error> {
-        Err(Self::Error::custom("unsupported"))
+    fn serialize_tuple(self, len: usize) -> Result<Self::SerializeTuple, Self::Error> {
+        self.serialize_struct("tuple", len)
     }
 
     fn serialize_tuple_struct(
         self,
-        _name: &'static str,
-        _len: usize,
+        name: &'static str,
+        len: usize,
     ) -> Result<Self::SerializeTupleStruct, Self::Error> {
-        Err(Self::Error::custom("unsupported"))
+        let mut s = self.serialize_struct(name, len)?;
+        if name == TYPED_TOKEN {
+            s.typed = true;
+            s.expected = None;
+        }
+        Ok(s)
     }
 
     fn serialize_struct(
         self,
-        name: &'static str,
+        _name: &'static str,
         len: usize,
     ) -> Result<Self::SerializeStruct, Self::Error> {
+        let expected = match self.expected.take() {
+            Some(VariantType::Structure(fields)) => Some(fields),
+            _ => None,
+        };
         let s = Self::SerializeStruct {
-            cur_field: 0,
-            cur_offset: 0,
-            framing_offsets: vec![],
-            name: name.to_string(),
-            num_fields: len as u64,
+            buffer: self.nested(),
+            framing_offsets: Vec::with_capacity(len),
+            fixed_size: true,
+            last_fixed: true,
+            fields: Vec::with_capacity(len),
+            expected,
+            typed: false,
+            skipped: None,
+            len: 0,
             serializer: self,
         };
         Ok(s)
@@ -553,11 +1032,27 @@ where
         _variant: &'static str,
         _len: usize,
     ) -> Result<Self::SerializeTupleVariant, Self::Error> {
-        Err(Self::Error::custom("unsupported: tuple variant"))
+        Err(errors::Error::unsupported("tuple variant"))
     }
 
     fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap, Self::Error> {
-        Err(Self::Error::custom("unsupported: map"))
+        let expected = match self.expected.take() {
+            Some(VariantType::Vec(entry)) => match *entry {
+                VariantType::DictEntry(k, v) => Some((*k, *v)),
+                _ => None,
+            },
+            _ => None,
+        };
+        Ok(SerMap {
+            entries: BTreeMap::new(),
+            next: None,
+            entry_type: expected
+                .clone()
+                .map(|(k, v)| VariantType::DictEntry(Box::new(k), Box::new(v))),
+            expected,
+            guesses: vec![],
+            serializer: self,
+        })
     }
 
     fn serialize_struct_variant(
@@ -567,31 +1062,50 @@ where
         _variant: &'static str,
         _len: usize,
     ) -> Result<Self::SerializeStructVariant, Self::Error> {
-        Err(Self::Error::custom("unsupported: struct variant"))
+        Err(errors::Error::unsupported("struct variant"))
     }
 
-    fn serialize_newtype_struct<T: ?Sized>(
-        self,
-        _name: &'static str,
-        _value: &T,
-    ) -> errors::Result<Self::Ok>
+    fn serialize_newtype_struct<T>(self, name: &'static str, value: &T) -> errors::Result<Self::Ok>
     where
-        T: ser::Serialize,
+        T: ?Sized + ser::Serialize,
     {
-        Err(Self::Error::custom("unsupported: newtype struct"))
+        let ty = match name {
+            OBJECT_PATH_TOKEN => VariantType::ObjectPath,
+            SIGNATURE_TOKEN => VariantType::Signature,
+            HANDLE_TOKEN => VariantType::Handle,
+            // Other newtypes are transparent.
+            _ => return value.serialize(self),
+        };
+        // Object paths, signatures and handles are written as their content.
+        let mut p = value.serialize(self)?;
+        p.ty = ty;
+        Ok(p)
     }
 
-    fn serialize_newtype_variant<T: ?Sized>(
+    fn serialize_newtype_variant<T>(
         self,
-        _name: &'static str,
+        name: &'static str,
         _variant_index: u32,
         _variant: &'static str,
-        _value: &T,
+        value: &T,
     ) -> errors::Result<Self::Ok>
     where
-        T: ser::Serialize,
+        T: ?Sized + ser::Serialize,
     {
-        Err(Self::Error::custom("unsupported: newtype variant"))
+        match name {
+            // The signature of a `v` follows its content, and structures have
+            // no streaming form here: the content is built as a tree first.
+            VARIANT_TOKEN => {
+                let content = value.serialize(VariantSerializer::new(&self.options))?;
+                self.write_variant(&Variant::Variant(Box::new(content)))
+            }
+            // Children of `Variant` containers are `Variant` trees already.
+            VALUE_TOKEN => {
+                let content = value.serialize(VariantSerializer::new(&self.options))?;
+                self.write_variant(&content)
+            }
+            _ => Err(errors::Error::unsupported("newtype variant")),
+        }
     }
 
     fn serialize_unit_variant(
@@ -600,6 +1114,6 @@ where
         _variant_index: u32,
         _variant: &'static str,
     ) -> errors::Result<Self::Ok> {
-        Err(Self::Error::custom("unsupported: unit variant"))
+        Err(errors::Error::unsupported("unit variant"))
     }
 }
@@ -0,0 +1,402 @@
+//! Collection API for `Array`, `Dictionary` and `Structure`.
+//!
+//! Elements of arrays and dictionaries are checked against the declared
+//! types on insertion. `FromIterator` takes statically-typed items, boxing
+//! `Variant` ones as `v`, while `try_from_iter` takes the types of the items.
+//! `try_extend` fits items to the declared types: `v` items of those types
+//! are unboxed, and other items are boxed into containers of `v`. Arrays and
+//! dictionaries have no `Extend`, as items may not fit their declared types.
+
+use super::convert::typed;
+use super::{check_type, Array, Dictionary, Structure, Variant};
+use crate::errors;
+use crate::variant_type::{BasicVariantType, StaticVariantType, VariantType};
+use alloc::boxed::Box;
+use alloc::collections::{btree_map, BTreeMap};
+use alloc::vec;
+use alloc::vec::Vec;
+use core::convert::TryFrom;
+use core::iter::FromIterator;
+use core::ops::Index;
+use core::slice;
+
+/// Fit `value` to the `expected` type, unboxing or boxing it as a `v`.
+fn fit(expected: &VariantType, value: Variant) -> errors::Result<Variant> {
+    let value = match value {
+        Variant::Variant(inner) if inner.variant_type() == *expected => *inner,
+        Variant::Variant(inner) => Variant::Variant(inner),
+        value if *expected == VariantType::Variant => Variant::Variant(Box::new(value)),
+        value => value,
+    };
+    check_type(expected, &value)?;
+    Ok(value)
+}
+
+impl Array {
+    /// Build an array, taking the element type from the first element.
+    ///
+    /// Like `from_elements`, from any iterator.
+    pub fn try_from_iter<I>(iter: I) -> errors::Result<Self>
+    where
+        I: IntoIterator<Item = Variant>,
+    {
+        Self::from_elements(iter.into_iter().collect())
+    }
+
+    /// Append elements, fitting them to the element type.
+    ///
+    /// Nothing is appended if any element is of another type.
+    pub fn try_extend<T, I>(&mut self, iter: I) -> errors::Result<()>
+    where
+        T: Into<Variant>,
+        I: IntoIterator<Item = T>,
+    {
+        let elements = iter
+            .into_iter()
+            .map(|value| fit(&self.elem_type, value.into()))
+            .collect::<errors::Result<Vec<_>>>()?;
+        self.inner.extend(elements);
+        Ok(())
+    }
+
+    /// Return the number of elements.
+    pub fn len(&self) -> usize {
+        self.inner.len()
+    }
+
+    /// Return whether the array has no elements.
+    pub fn is_empty(&self) -> bool {
+        self.inner.is_empty()
+    }
+
+    /// Return the element at `index`, if any.
+    pub fn get(&self, index: usize) -> Option<&Variant> {
+        self.inner.get(index)
+    }
+
+    /// Iterate over the elements.
+    pub fn iter(&self) -> slice::Iter<'_, Variant> {
+        self.inner.iter()
+    }
+
+    /// Return the elements as a slice.
+    pub fn as_slice(&self) -> &[Variant] {
+        &self.inner
+    }
+
+    /// Append an element, checking that it is of the element type.
+    pub fn push(&mut self, value: Variant) -> errors::Result<()> {
+        check_type(&self.elem_type, &value)?;
+        self.inner.push(value);
+        Ok(())
+    }
+
+    /// Remove and return the last element, if any.
+    pub fn pop(&mut self) -> Option<Variant> {
+        self.inner.pop()
+    }
+
+    /// Transform self into its elements.
+    pub fn into_inner(self) -> Vec<Variant> {
+        self.inner
+    }
+}
+
+impl Index<usize> for Array {
+    type Output = Variant;
+
+    fn index(&self, index: usize) -> &Variant {
+        &self.inner[index]
+    }
+}
+
+impl IntoIterator for Array {
+    type Item = Variant;
+    type IntoIter = vec::IntoIter<Variant>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.inner.into_iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a Array {
+    type Item = &'a Variant;
+    type IntoIter = slice::Iter<'a, Variant>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.inner.iter()
+    }
+}
+
+impl<T> FromIterator<T> for Array
+where
+    T: Into<Variant> + StaticVariantType,
+{
+    fn from_iter<I>(iter: I) -> Self
+    where
+        I: IntoIterator<Item = T>,
+    {
+        Self {
+            elem_type: T::static_variant_type(),
+            inner: iter.into_iter().map(typed).collect(),
+        }
+    }
+}
+
+impl From<Array> for Vec<Variant> {
+    fn from(v: Array) -> Self {
+        v.inner
+    }
+}
+
+impl TryFrom<Vec<Variant>> for Array {
+    type Error = errors::Error;
+
+    fn try_from(v: Vec<Variant>) -> errors::Result<Self> {
+        Self::from_elements(v)
+    }
+}
+
+impl Dictionary {
+    /// Build a dictionary, taking key and value types from the first entry.
+    ///
+    /// Like `from_map`, from any iterator.
+    pub fn try_from_iter<I>(iter: I) -> errors::Result<Self>
+    where
+        I: IntoIterator<Item = (Variant, Variant)>,
+    {
+        Self::from_map(iter.into_iter().collect())
+    }
+
+    /// Insert entries, fitting them to the entry types.
+    ///
+    /// Nothing is inserted if any entry is of other types.
+    pub fn try_extend<K, V, I>(&mut self, iter: I) -> errors::Result<()>
+    where
+        K: Into<Variant>,
+        V: Into<Variant>,
+        I: IntoIterator<Item = (K, V)>,
+    {
+        let entries = iter
+            .into_iter()
+            .map(|(k, v)| {
+                let k = fit(&self.key_type, k.into())?;
+                Ok((k, fit(&self.value_type, v.into())?))
+            })
+            .collect::<errors::Result<Vec<_>>>()?;
+        self.inner.extend(entries);
+        Ok(())
+    }
+
+    /// Return the number of entries.
+    pub fn len(&self) -> usize {
+        self.inner.len()
+    }
+
+    /// Return whether the dictionary has no entries.
+    pub fn is_empty(&self) -> bool {
+        self.inner.is_empty()
+    }
+
+    /// Return the value for `key`, if any.
+    pub fn get(&self, key: &Variant) -> Option<&Variant> {
+        self.inner.get(key)
+    }
+
+    /// Return whether there is an entry for `key`.
+    pub fn contains_key(&self, key: &Variant) -> bool {
+        self.inner.contains_key(key)
+    }
+
+    /// Iterate over the entries, sorted by key.
+    pub fn iter(&self) -> btree_map::Iter<'_, Variant, Variant> {
+        self.inner.iter()
+    }
+
+    /// Iterate over the keys, in sorted order.
+    pub fn keys(&self) -> btree_map::Keys<'_, Variant, Variant> {
+        self.inner.keys()
+    }
+
+    /// Iterate over the values, sorted by key.
+    pub fn values(&self) -> btree_map::Values<'_, Variant, Variant> {
+        self.inner.values()
+    }
+
+    /// Insert an entry, checking that it is of the entry types.
+    ///
+    /// Returns the previous value for `key`, if any.
+    pub fn insert(&mut self, key: Variant, value: Variant) -> errors::Result<Option<Variant>> {
+        check_type(&self.key_type, &key)?;
+        check_type(&self.value_type, &value)?;
+        Ok(self.inner.insert(key, value))
+    }
+
+    /// Remove the entry for `key`, returning its value if any.
+    pub fn remove(&mut self, key: &Variant) -> Option<Variant> {
+        self.inner.remove(key)
+    }
+
+    /// Transform self into its entries.
+    pub fn into_inner(self) -> BTreeMap<Variant, Variant> {
+        self.inner
+    }
+}
+
+impl Index<&Variant> for Dictionary {
+    type Output = Variant;
+
+    fn index(&self, key: &Variant) -> &Variant {
+        &self.inner[key]
+    }
+}
+
+impl IntoIterator for Dictionary {
+    type Item = (Variant, Variant);
+    type IntoIter = btree_map::IntoIter<Variant, Variant>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.inner.into_iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a Dictionary {
+    type Item = (&'a Variant, &'a Variant);
+    type IntoIter = btree_map::Iter<'a, Variant, Variant>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.inner.iter()
+    }
+}
+
+impl<K, V> FromIterator<(K, V)> for Dictionary
+where
+    K: Into<Variant> + BasicVariantType,
+    V: Into<Variant> + StaticVariantType,
+{
+    fn from_iter<I>(iter: I) -> Self
+    where
+        I: IntoIterator<Item = (K, V)>,
+    {
+        Self {
+            key_type: K::static_variant_type(),
+            value_type: V::static_variant_type(),
+            inner: iter
+                .into_iter()
+                .map(|(k, v)| (typed(k), typed(v)))
+                .collect(),
+        }
+    }
+}
+
+impl From<Dictionary> for BTreeMap<Variant, Variant> {
+    fn from(v: Dictionary) -> Self {
+        v.inner
+    }
+}
+
+impl TryFrom<BTreeMap<Variant, Variant>> for Dictionary {
+    type Error = errors::Error;
+
+    fn try_from(v: BTreeMap<Variant, Variant>) -> errors::Result<Self> {
+        Self::from_map(v)
+    }
+}
+
+impl Structure {
+    /// Build a structure out of its fields.
+    pub fn new(fields: Vec<Variant>) -> Self {
+        Self { fields }
+    }
+
+    /// Return the number of fields.
+    pub fn len(&self) -> usize {
+        self.fields.len()
+    }
+
+    /// Return whether this is the unit structure.
+    pub fn is_empty(&self) -> bool {
+        self.fields.is_empty()
+    }
+
+    /// Return the field at `index`, if any.
+    pub fn get(&self, index: usize) -> Option<&Variant> {
+        self.fields.get(index)
+    }
+
+    /// Iterate over the fields.
+    pub fn iter(&self) -> slice::Iter<'_, Variant> {
+        self.fields.iter()
+    }
+
+    /// Return the fields as a slice.
+    pub fn as_slice(&self) -> &[Variant] {
+        &self.fields
+    }
+
+    /// Append a field.
+    pub fn push(&mut self, value: Variant) {
+        self.fields.push(value);
+    }
+
+    /// Transform self into its fields.
+    pub fn into_inner(self) -> Vec<Variant> {
+        self.fields
+    }
+}
+
+impl Index<usize> for Structure {
+    type Output = Variant;
+
+    fn index(&self, index: usize) -> &Variant {
+        &self.fields[index]
+    }
+}
+
+impl IntoIterator for Structure {
+    type Item = Variant;
+    type IntoIter = vec::IntoIter<Variant>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.fields.into_iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a Structure {
+    type Item = &'a Variant;
+    type IntoIter = slice::Iter<'a, Variant>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.fields.iter()
+    }
+}
+
+impl FromIterator<Variant> for Structure {
+    fn from_iter<I>(iter: I) -> Self
+    where
+        I: IntoIterator<Item = Variant>,
+    {
+        Self::new(iter.into_iter().collect())
+    }
+}
+
+impl Extend<Variant> for Structure {
+    fn extend<I>(&mut self, iter: I)
+    where
+        I: IntoIterator<Item = Variant>,
+    {
+        self.fields.extend(iter);
+    }
+}
+
+impl From<Structure> for Vec<Variant> {
+    fn from(v: Structure) -> Self {
+        v.fields
+    }
+}
+
+impl From<Vec<Variant>> for Structure {
+    fn from(v: Vec<Variant>) -> Self {
+        Self::new(v)
+    }
+}
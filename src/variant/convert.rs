@@ -0,0 +1,566 @@
+//! Conversions between Rust values and `Variant`.
+
+use super::{Array, Dictionary, Maybe, Structure, Variant};
+use crate::errors;
+use crate::handle::Handle;
+use crate::variant_type::{BasicVariantType, StaticVariantType, VariantType};
+use alloc::boxed::Box;
+use alloc::collections::BTreeMap;
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
+use core::convert::TryFrom;
+
+/// Build a signature-mismatch error for an unexpected value.
+fn mismatch(expected: &str, actual: &Variant) -> errors::Error {
+    errors::Error::mismatch(expected, actual.signature())
+}
+
+/// Convert a value into a `Variant` of its static type.
+///
+/// `Variant` values are of type `v`, thus they get boxed, unless already
+/// boxed (e.g. from a `Box<Variant>`).
+pub(super) fn typed<T>(value: T) -> Variant
+where
+    T: Into<Variant> + StaticVariantType,
+{
+    match value.into() {
+        Variant::Variant(inner) => Variant::Variant(inner),
+        value if T::static_variant_type() == VariantType::Variant => {
+            Variant::Variant(Box::new(value))
+        }
+        value => value,
+    }
+}
+
+impl Variant {
+    /// Extract a typed value, copying it out of this variant.
+    ///
+    /// Only the extracted values are copied, not the whole tree. On failure,
+    /// the error reports both the expected and the actual signature.
+    pub fn get<'a, T>(&'a self) -> errors::Result<T>
+    where
+        T: TryFrom<&'a Variant, Error = errors::Error>,
+    {
+        T::try_from(self)
+    }
+
+    /// Return the boolean value, if this is a `b`.
+    pub fn as_bool(&self) -> Option<bool> {
+        match *self {
+            Variant::Bool(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    /// Return the byte value, if this is a `y`.
+    pub fn as_u8(&self) -> Option<u8> {
+        match *self {
+            Variant::U8(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    /// Return the integer value, if this is a `q`.
+    pub fn as_u16(&self) -> Option<u16> {
+        match *self {
+            Variant::U16(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    /// Return the integer value, if this is a `u`.
+    pub fn as_u32(&self) -> Option<u32> {
+        match *self {
+            Variant::U32(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    /// Return the integer value, if this is a `t`.
+    pub fn as_u64(&self) -> Option<u64> {
+        match *self {
+            Variant::U64(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    /// Return the integer value, if this is a `n`.
+    pub fn as_i16(&self) -> Option<i16> {
+        match *self {
+            Variant::I16(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    /// Return the integer value, if this is a `i`.
+    pub fn as_i32(&self) -> Option<i32> {
+        match *self {
+            Variant::I32(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    /// Return the integer value, if this is a `x`.
+    pub fn as_i64(&self) -> Option<i64> {
+        match *self {
+            Variant::I64(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    /// Return the floating point value, if this is a `d`.
+    pub fn as_f64(&self) -> Option<f64> {
+        match *self {
+            Variant::F64(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    /// Return the string content, if this is a `s`, `o` or `g`.
+    pub fn as_str(&self) -> Option<&str> {
+        match *self {
+            Variant::String(ref v) | Variant::ObjectPath(ref v) | Variant::Signature(ref v) => {
+                Some(v)
+            }
+            _ => None,
+        }
+    }
+
+    /// Return the handle, if this is a `h`.
+    pub fn as_handle(&self) -> Option<Handle> {
+        match *self {
+            Variant::Handle(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    /// Return the boxed value, if this is a `v`.
+    pub fn as_variant(&self) -> Option<&Variant> {
+        match *self {
+            Variant::Variant(ref v) => Some(v),
+            _ => None,
+        }
+    }
+
+    /// Return the optional value, if this is a maybe type.
+    pub fn as_option(&self) -> Option<Option<&Variant>> {
+        match *self {
+            Variant::Option(ref v) => Some(v.value()),
+            _ => None,
+        }
+    }
+
+    /// Return the array, if this is an array.
+    pub fn as_array(&self) -> Option<&Array> {
+        match *self {
+            Variant::Vec(ref v) => Some(v),
+            _ => None,
+        }
+    }
+
+    /// Return the dictionary, if this is a dictionary.
+    pub fn as_dictionary(&self) -> Option<&Dictionary> {
+        match *self {
+            Variant::Dictionary(ref v) => Some(v),
+            _ => None,
+        }
+    }
+
+    /// Return the structure, if this is a structure.
+    pub fn as_structure(&self) -> Option<&Structure> {
+        match *self {
+            Variant::Structure(ref v) => Some(v),
+            _ => None,
+        }
+    }
+}
+
+// Basic types, mapped one-to-one on `Variant` arms.
+macro_rules! impl_basic {
+    ($ty:ty, $arm:ident, $sig:expr) => {
+        impl From<$ty> for Variant {
+            fn from(v: $ty) -> Self {
+                Variant::$arm(v)
+            }
+        }
+
+        impl TryFrom<Variant> for $ty {
+            type Error = errors::Error;
+
+            fn try_from(v: Variant) -> errors::Result<Self> {
+                match v {
+                    Variant::$arm(inner) => Ok(inner),
+                    other => Err(mismatch($sig, &other)),
+                }
+            }
+        }
+
+        impl TryFrom<Variant> for Option<$ty> {
+            type Error = errors::Error;
+
+            fn try_from(v: Variant) -> errors::Result<Self> {
+                match v {
+                    Variant::Option(m) => {
+                        if *m.inner_type() != VariantType::$arm {
+                            return Err(mismatch(concat!("m", $sig), &Variant::Option(m)));
+                        }
+                        m.into_value().map(<$ty>::try_from).transpose()
+                    }
+                    other => Err(mismatch(concat!("m", $sig), &other)),
+                }
+            }
+        }
+
+        impl<'a> TryFrom<&'a Variant> for $ty {
+            type Error = errors::Error;
+
+            fn try_from(v: &'a Variant) -> errors::Result<Self> {
+                match *v {
+                    Variant::$arm(ref inner) => Ok(inner.clone()),
+                    ref other => Err(mismatch($sig, other)),
+                }
+            }
+        }
+
+        impl<'a> TryFrom<&'a Variant> for Option<$ty> {
+            type Error = errors::Error;
+
+            fn try_from(v: &'a Variant) -> errors::Result<Self> {
+                match *v {
+                    Variant::Option(ref m) if *m.inner_type() == VariantType::$arm => {
+                        m.value().map(<$ty>::try_from).transpose()
+                    }
+                    ref other => Err(mismatch(concat!("m", $sig), other)),
+                }
+            }
+        }
+    };
+}
+
+impl_basic!(bool, Bool, "b");
+impl_basic!(u8, U8, "y");
+impl_basic!(u16, U16, "q");
+impl_basic!(u32, U32, "u");
+impl_basic!(u64, U64, "t");
+impl_basic!(i16, I16, "n");
+impl_basic!(i32, I32, "i");
+impl_basic!(i64, I64, "x");
+impl_basic!(f64, F64, "d");
+impl_basic!(String, String, "s");
+impl_basic!(Handle, Handle, "h");
+
+/// Promoted to `d`, like serialized `f32` values.
+impl From<f32> for Variant {
+    fn from(v: f32) -> Self {
+        Variant::F64(f64::from(v))
+    }
+}
+
+/// A `u` holding the code point, like serialized `char` values by default.
+impl From<char> for Variant {
+    fn from(v: char) -> Self {
+        Variant::U32(u32::from(v))
+    }
+}
+
+impl<'a> From<&'a str> for Variant {
+    fn from(v: &'a str) -> Self {
+        Variant::String(v.to_string())
+    }
+}
+
+impl From<Box<Variant>> for Variant {
+    fn from(v: Box<Variant>) -> Self {
+        Variant::Variant(v)
+    }
+}
+
+impl From<Array> for Variant {
+    fn from(v: Array) -> Self {
+        Variant::Vec(v)
+    }
+}
+
+impl From<Dictionary> for Variant {
+    fn from(v: Dictionary) -> Self {
+        Variant::Dictionary(v)
+    }
+}
+
+impl From<Structure> for Variant {
+    fn from(v: Structure) -> Self {
+        Variant::Structure(v)
+    }
+}
+
+impl From<Maybe> for Variant {
+    fn from(v: Maybe) -> Self {
+        Variant::Option(v)
+    }
+}
+
+impl<T> From<Option<T>> for Variant
+where
+    T: Into<Variant> + StaticVariantType,
+{
+    fn from(v: Option<T>) -> Self {
+        Variant::Option(Maybe {
+            inner_type: T::static_variant_type(),
+            value: v.map(|inner| Box::new(typed(inner))),
+        })
+    }
+}
+
+impl<T> From<Vec<T>> for Variant
+where
+    T: Into<Variant> + StaticVariantType,
+{
+    fn from(v: Vec<T>) -> Self {
+        let inner = v.into_iter().map(typed).collect();
+        Variant::Vec(Array {
+            elem_type: T::static_variant_type(),
+            inner,
+        })
+    }
+}
+
+/// Maps with `Variant` keys are not statically known to have basic keys: go
+/// through `Dictionary::try_from` instead.
+impl<K, V> From<BTreeMap<K, V>> for Variant
+where
+    K: Into<Variant> + BasicVariantType,
+    V: Into<Variant> + StaticVariantType,
+{
+    fn from(v: BTreeMap<K, V>) -> Self {
+        let inner = v.into_iter().map(|(k, v)| (typed(k), typed(v))).collect();
+        Variant::Dictionary(Dictionary {
+            key_type: K::static_variant_type(),
+            value_type: V::static_variant_type(),
+            inner,
+        })
+    }
+}
+
+/// Any value converts, unboxed once if it is a `v`, so that values boxed by
+/// `From<Vec<Variant>>` and similar conversions come back as they went in.
+impl<'a> TryFrom<&'a Variant> for Variant {
+    type Error = errors::Error;
+
+    fn try_from(v: &'a Variant) -> errors::Result<Self> {
+        match *v {
+            Variant::Variant(ref inner) => Ok((**inner).clone()),
+            ref other => Ok(other.clone()),
+        }
+    }
+}
+
+impl TryFrom<Variant> for Array {
+    type Error = errors::Error;
+
+    fn try_from(v: Variant) -> errors::Result<Self> {
+        match v {
+            Variant::Vec(inner) => Ok(inner),
+            other => Err(mismatch("a*", &other)),
+        }
+    }
+}
+
+impl<'a> TryFrom<&'a Variant> for Array {
+    type Error = errors::Error;
+
+    fn try_from(v: &'a Variant) -> errors::Result<Self> {
+        match *v {
+            Variant::Vec(ref inner) => Ok(inner.clone()),
+            ref other => Err(mismatch("a*", other)),
+        }
+    }
+}
+
+impl TryFrom<Variant> for Dictionary {
+    type Error = errors::Error;
+
+    fn try_from(v: Variant) -> errors::Result<Self> {
+        match v {
+            Variant::Dictionary(inner) => Ok(inner),
+            other => Err(mismatch("a{?*}", &other)),
+        }
+    }
+}
+
+impl<'a> TryFrom<&'a Variant> for Dictionary {
+    type Error = errors::Error;
+
+    fn try_from(v: &'a Variant) -> errors::Result<Self> {
+        match *v {
+            Variant::Dictionary(ref inner) => Ok(inner.clone()),
+            ref other => Err(mismatch("a{?*}", other)),
+        }
+    }
+}
+
+impl TryFrom<Variant> for Maybe {
+    type Error = errors::Error;
+
+    fn try_from(v: Variant) -> errors::Result<Self> {
+        match v {
+            Variant::Option(inner) => Ok(inner),
+            other => Err(mismatch("m*", &other)),
+        }
+    }
+}
+
+impl<'a> TryFrom<&'a Variant> for Maybe {
+    type Error = errors::Error;
+
+    fn try_from(v: &'a Variant) -> errors::Result<Self> {
+        match *v {
+            Variant::Option(ref inner) => Ok(inner.clone()),
+            ref other => Err(mismatch("m*", other)),
+        }
+    }
+}
+
+impl TryFrom<Variant> for Structure {
+    type Error = errors::Error;
+
+    fn try_from(v: Variant) -> errors::Result<Self> {
+        match v {
+            Variant::Structure(inner) => Ok(inner),
+            other => Err(mismatch("r", &other)),
+        }
+    }
+}
+
+impl<'a> TryFrom<&'a Variant> for Structure {
+    type Error = errors::Error;
+
+    fn try_from(v: &'a Variant) -> errors::Result<Self> {
+        match *v {
+            Variant::Structure(ref inner) => Ok(inner.clone()),
+            ref other => Err(mismatch("r", other)),
+        }
+    }
+}
+
+impl<T> TryFrom<Variant> for Vec<T>
+where
+    T: TryFrom<Variant, Error = errors::Error>,
+{
+    type Error = errors::Error;
+
+    fn try_from(v: Variant) -> errors::Result<Self> {
+        match v {
+            Variant::Vec(array) => array.inner.into_iter().map(T::try_from).collect(),
+            other => Err(mismatch("a*", &other)),
+        }
+    }
+}
+
+impl<'a, T> TryFrom<&'a Variant> for Vec<T>
+where
+    T: TryFrom<&'a Variant, Error = errors::Error>,
+{
+    type Error = errors::Error;
+
+    fn try_from(v: &'a Variant) -> errors::Result<Self> {
+        match *v {
+            Variant::Vec(ref array) => array.inner.iter().map(T::try_from).collect(),
+            ref other => Err(mismatch("a*", other)),
+        }
+    }
+}
+
+impl<K, V> TryFrom<Variant> for BTreeMap<K, V>
+where
+    K: TryFrom<Variant, Error = errors::Error> + Ord,
+    V: TryFrom<Variant, Error = errors::Error>,
+{
+    type Error = errors::Error;
+
+    fn try_from(v: Variant) -> errors::Result<Self> {
+        match v {
+            Variant::Dictionary(dict) => dict
+                .inner
+                .into_iter()
+                .map(|(k, v)| Ok((K::try_from(k)?, V::try_from(v)?)))
+                .collect(),
+            other => Err(mismatch("a{?*}", &other)),
+        }
+    }
+}
+
+impl<'a, K, V> TryFrom<&'a Variant> for BTreeMap<K, V>
+where
+    K: TryFrom<&'a Variant, Error = errors::Error> + Ord,
+    V: TryFrom<&'a Variant, Error = errors::Error>,
+{
+    type Error = errors::Error;
+
+    fn try_from(v: &'a Variant) -> errors::Result<Self> {
+        match *v {
+            Variant::Dictionary(ref dict) => dict
+                .inner
+                .iter()
+                .map(|(k, v)| Ok((K::try_from(k)?, V::try_from(v)?)))
+                .collect(),
+            ref other => Err(mismatch("a{?*}", other)),
+        }
+    }
+}
+
+// Tuples, mapped to structures.
+macro_rules! impl_tuple {
+    ($len:expr => $($name:ident)+) => {
+        impl<$($name),+> From<($($name,)+)> for Variant
+        where
+            $($name: Into<Variant> + StaticVariantType,)+
+        {
+            #[allow(non_snake_case)]
+            fn from(v: ($($name,)+)) -> Self {
+                let ($($name,)+) = v;
+                let fields = vec![$(typed($name)),+];
+                Variant::Structure(Structure { fields })
+            }
+        }
+
+        impl<$($name),+> TryFrom<Variant> for ($($name,)+)
+        where
+            $($name: TryFrom<Variant, Error = errors::Error>,)+
+        {
+            type Error = errors::Error;
+
+            fn try_from(v: Variant) -> errors::Result<Self> {
+                let mut fields = match v {
+                    Variant::Structure(s) if s.fields.len() == $len => s.fields.into_iter(),
+                    other => return Err(mismatch("r", &other)),
+                };
+                Ok(($($name::try_from(fields.next().unwrap())?,)+))
+            }
+        }
+
+        impl<'a, $($name),+> TryFrom<&'a Variant> for ($($name,)+)
+        where
+            $($name: TryFrom<&'a Variant, Error = errors::Error>,)+
+        {
+            type Error = errors::Error;
+
+            fn try_from(v: &'a Variant) -> errors::Result<Self> {
+                let mut fields = match *v {
+                    Variant::Structure(ref s) if s.fields.len() == $len => s.fields.iter(),
+                    ref other => return Err(mismatch("r", other)),
+                };
+                Ok(($($name::try_from(fields.next().unwrap())?,)+))
+            }
+        }
+    };
+}
+
+impl_tuple!(1 => A);
+impl_tuple!(2 => A B);
+impl_tuple!(3 => A B C);
+impl_tuple!(4 => A B C D);
+impl_tuple!(5 => A B C D E);
+impl_tuple!(6 => A B C D E F);
+impl_tuple!(7 => A B C D E F G);
+impl_tuple!(8 => A B C D E F G H);
@@ -0,0 +1,1032 @@
+//! Deserialization out of `Variant` trees.
+//!
+//! Both `Variant` and `&Variant` act as self-describing deserializers, so that
+//! a decoded tree can be mapped directly onto a typed Rust value. They follow
+//! the default `Config`; `Config::deserialize_variant` applies another one.
+//!
+//! `Variant` itself is rebuilt through a private protocol: it is requested as
+//! an enum keyed by arm index, and containers are requested as a newtype over
+//! their signature followed by their children. This keeps element types of
+//! empty containers, which plain serde data would lose.
+//!
+//! Other formats hand over the externally tagged form written by `Serialize`:
+//! arms by name, and containers as their plain content. Arrays, dictionaries
+//! and maybes come paired with their signature, structures do not need one.
+
+use super::ser::{VARIANTS, VARIANT_TOKEN};
+use super::{Array, Dictionary, Maybe, Structure, Variant};
+use crate::config::{Config, I8Type};
+use crate::de::{CharVisitor, F32Visitor, I8Visitor};
+use crate::errors;
+use crate::variant_type::VariantType;
+use alloc::borrow::ToOwned;
+use alloc::boxed::Box;
+use alloc::collections::BTreeMap;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::convert::TryFrom;
+use core::fmt;
+use serde::de::{self, Error, IntoDeserializer};
+
+/// Enum name requesting a `Variant` as is, without unwrapping a `v`.
+const EXACT_TOKEN: &str = "$serde_gvariant::private::ExactVariant";
+/// Newtype name requesting the signature and children of a container.
+const CONTAINER_TOKEN: &str = "$serde_gvariant::private::Container";
+
+impl<'de> de::Deserialize<'de> for Variant {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        deserializer.deserialize_enum(VARIANT_TOKEN, VARIANTS, VariantVisitor)
+    }
+}
+
+// Containers are deserialized as a whole `Variant`, then unpacked.
+macro_rules! impl_deserialize_container {
+    ($ty:ident) => {
+        impl<'de> de::Deserialize<'de> for $ty {
+            fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+            where
+                D: de::Deserializer<'de>,
+            {
+                let value = Variant::deserialize(deserializer)?;
+                $ty::try_from(value).map_err(D::Error::custom)
+            }
+        }
+    };
+}
+
+impl_deserialize_container!(Array);
+impl_deserialize_container!(Dictionary);
+impl_deserialize_container!(Maybe);
+impl_deserialize_container!(Structure);
+
+struct VariantVisitor;
+
+impl<'de> de::Visitor<'de> for VariantVisitor {
+    type Value = Variant;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a GVariant value")
+    }
+
+    fn visit_enum<A>(self, data: A) -> Result<Variant, A::Error>
+    where
+        A: de::EnumAccess<'de>,
+    {
+        use serde::de::VariantAccess;
+        let (discriminant, access) = data.variant_seed(ArmSeed)?;
+        match discriminant {
+            0 => access.newtype_variant().map(Variant::Bool),
+            1 => access.newtype_variant().map(Variant::U8),
+            2 => access.newtype_variant().map(Variant::U16),
+            3 => access.newtype_variant().map(Variant::U32),
+            4 => access.newtype_variant().map(Variant::U64),
+            5 => access.newtype_variant().map(Variant::I16),
+            6 => access.newtype_variant().map(Variant::I32),
+            7 => access.newtype_variant().map(Variant::I64),
+            8 => access.newtype_variant().map(Variant::F64),
+            9 => access.newtype_variant().map(Variant::String),
+            10 => access.newtype_variant().map(Variant::ObjectPath),
+            11 => access.newtype_variant().map(Variant::Signature),
+            12 => access
+                .newtype_variant()
+                .map(|v| Variant::Variant(Box::new(v))),
+            13..=16 => access.newtype_variant_seed(ContainerSeed(discriminant)),
+            17 => access.newtype_variant().map(Variant::Handle),
+            n => Err(A::Error::invalid_value(de::Unexpected::Unsigned(n), &self)),
+        }
+    }
+}
+
+/// Seed for the arm of a `Variant`, by index or by name.
+struct ArmSeed;
+
+impl<'de> de::DeserializeSeed<'de> for ArmSeed {
+    type Value = u64;
+
+    fn deserialize<D>(self, deserializer: D) -> Result<u64, D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        deserializer.deserialize_identifier(self)
+    }
+}
+
+impl<'de> de::Visitor<'de> for ArmSeed {
+    type Value = u64;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a GVariant arm")
+    }
+
+    fn visit_u64<E>(self, v: u64) -> Result<u64, E>
+    where
+        E: de::Error,
+    {
+        Ok(v)
+    }
+
+    fn visit_str<E>(self, v: &str) -> Result<u64, E>
+    where
+        E: de::Error,
+    {
+        match VARIANTS.iter().position(|name| *name == v) {
+            Some(discriminant) => Ok(discriminant as u64),
+            None => Err(E::unknown_variant(v, VARIANTS)),
+        }
+    }
+}
+
+/// Seed for a child of a container, taken exactly as it is.
+struct ExactSeed;
+
+impl<'de> de::DeserializeSeed<'de> for ExactSeed {
+    type Value = Variant;
+
+    fn deserialize<D>(self, deserializer: D) -> Result<Variant, D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        deserializer.deserialize_enum(EXACT_TOKEN, VARIANTS, VariantVisitor)
+    }
+}
+
+/// Seed for a container, rebuilt from its signature and children.
+///
+/// Holds the discriminant of the container arm.
+struct ContainerSeed(u64);
+
+impl<'de> de::DeserializeSeed<'de> for ContainerSeed {
+    type Value = Variant;
+
+    fn deserialize<D>(self, deserializer: D) -> Result<Variant, D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        deserializer.deserialize_newtype_struct(CONTAINER_TOKEN, ContainerVisitor(self.0))
+    }
+}
+
+struct ContainerVisitor(u64);
+
+impl<'de> de::Visitor<'de> for ContainerVisitor {
+    type Value = Variant;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a GVariant container")
+    }
+
+    fn visit_seq<A>(self, mut seq: A) -> Result<Variant, A::Error>
+    where
+        A: de::SeqAccess<'de>,
+    {
+        let signature: String = seq
+            .next_element()?
+            .ok_or_else(|| A::Error::invalid_length(0, &self))?;
+        let ty = VariantType::parse(&signature).map_err(A::Error::custom)?;
+        let container = match ty {
+            VariantType::Vec(elem) => match *elem {
+                VariantType::DictEntry(k, v) => {
+                    let mut inner = BTreeMap::new();
+                    while let Some(key) = seq.next_element_seed(ExactSeed)? {
+                        let value = seq
+                            .next_element_seed(ExactSeed)?
+                            .ok_or_else(|| A::Error::custom("variant: key without value"))?;
+                        inner.insert(key, value);
+                    }
+                    Dictionary::with_types(*k, *v, inner).map(Variant::Dictionary)
+                }
+                elem => {
+                    let mut inner = vec![];
+                    while let Some(value) = seq.next_element_seed(ExactSeed)? {
+                        inner.push(value);
+                    }
+                    Array::with_type(elem, inner).map(Variant::Vec)
+                }
+            },
+            VariantType::Option(inner) => {
+                let value = seq.next_element_seed(ExactSeed)?;
+                Maybe::with_type(*inner, value).map(Variant::Option)
+            }
+            VariantType::Structure(_) => {
+                let mut fields = vec![];
+                while let Some(value) = seq.next_element_seed(ExactSeed)? {
+                    fields.push(value);
+                }
+                Ok(Variant::Structure(Structure { fields }))
+            }
+            ty => Err(errors::Error::unsupported(format!("children of '{}'", ty))),
+        };
+        container.map_err(A::Error::custom)
+    }
+
+    // Other formats carry the plain content written by `Serialize`.
+    fn visit_newtype_struct<D>(self, deserializer: D) -> Result<Variant, D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        use serde::de::Deserialize;
+        match self.0 {
+            // The unit structure comes out as nothing, e.g. `null`.
+            15 => {
+                let fields = Option::<Vec<Exact>>::deserialize(deserializer)?;
+                let fields = fields.into_iter().flatten().map(|e| e.0).collect();
+                Ok(Variant::Structure(Structure { fields }))
+            }
+            arm => deserializer.deserialize_tuple(2, TypedVisitor(arm)),
+        }
+    }
+}
+
+/// Visitor for a container paired with its signature, as written by `Serialize`.
+///
+/// Holds the discriminant of the container arm.
+struct TypedVisitor(u64);
+
+impl<'de> de::Visitor<'de> for TypedVisitor {
+    type Value = Variant;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a GVariant signature and container")
+    }
+
+    fn visit_seq<A>(self, mut seq: A) -> Result<Variant, A::Error>
+    where
+        A: de::SeqAccess<'de>,
+    {
+        let signature: String = seq
+            .next_element()?
+            .ok_or_else(|| A::Error::invalid_length(0, &self))?;
+        let ty = VariantType::parse(&signature).map_err(A::Error::custom)?;
+        let missing = || A::Error::invalid_length(1, &self);
+        let container = match (self.0, ty) {
+            (13, VariantType::Option(inner)) => {
+                let value = seq.next_element::<Option<Exact>>()?.ok_or_else(missing)?;
+                Maybe::with_type(*inner, value.map(|e| e.0)).map(Variant::Option)
+            }
+            (14, VariantType::Vec(elem)) => {
+                let elements = seq.next_element::<Vec<Exact>>()?.ok_or_else(missing)?;
+                let inner = elements.into_iter().map(|e| e.0).collect();
+                Array::with_type(*elem, inner).map(Variant::Vec)
+            }
+            (16, VariantType::Vec(entry)) => match *entry {
+                VariantType::DictEntry(k, v) => {
+                    let entries = seq
+                        .next_element::<BTreeMap<Exact, Exact>>()?
+                        .ok_or_else(missing)?;
+                    let inner = entries.into_iter().map(|(k, v)| (k.0, v.0)).collect();
+                    Dictionary::with_types(*k, *v, inner).map(Variant::Dictionary)
+                }
+                entry => Err(errors::Error::signature(format!(
+                    "'a{}' is not a dictionary",
+                    entry
+                ))),
+            },
+            (arm, ty) => Err(errors::Error::signature(format!(
+                "'{}' is not the type of a {}",
+                ty, VARIANTS[arm as usize]
+            ))),
+        };
+        container.map_err(A::Error::custom)
+    }
+}
+
+/// A child of a container, taken exactly as it is.
+#[derive(PartialEq, Eq, PartialOrd, Ord)]
+struct Exact(Variant);
+
+impl<'de> de::Deserialize<'de> for Exact {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        de::DeserializeSeed::deserialize(ExactSeed, deserializer).map(Exact)
+    }
+}
+
+/// Sequence access over a container: its signature, then its children.
+struct ContainerAccess<I> {
+    signature: Option<String>,
+    children: I,
+    mapping: Mapping,
+}
+
+impl<'de, I> de::SeqAccess<'de> for ContainerAccess<I>
+where
+    I: Iterator,
+    VariantDeserializer<I::Item>: de::Deserializer<'de, Error = errors::Error>,
+{
+    type Error = errors::Error;
+
+    fn next_element_seed<S>(&mut self, seed: S) -> errors::Result<Option<S::Value>>
+    where
+        S: de::DeserializeSeed<'de>,
+    {
+        if let Some(signature) = self.signature.take() {
+            let de = IntoDeserializer::<errors::Error>::into_deserializer(signature);
+            return seed.deserialize(de).map(Some);
+        }
+        match self.children.next() {
+            Some(child) => seed.deserialize(self.mapping.wrap(child)).map(Some),
+            None => Ok(None),
+        }
+    }
+}
+
+impl Variant {
+    /// Unwrap a `v` container (once), returning the boxed value.
+    fn into_content(self) -> Variant {
+        match self {
+            Variant::Variant(inner) => *inner,
+            other => other,
+        }
+    }
+
+    /// Unwrap a `v` container (once), borrowing the boxed value.
+    fn content(&self) -> &Variant {
+        match *self {
+            Variant::Variant(ref inner) => inner,
+            ref other => other,
+        }
+    }
+
+    /// Split a container into its children, flattening dictionary entries.
+    fn into_children(self) -> errors::Result<Vec<Variant>> {
+        match self {
+            Variant::Option(maybe) => Ok(maybe.into_value().into_iter().collect()),
+            Variant::Vec(array) => Ok(array.inner),
+            Variant::Structure(s) => Ok(s.fields),
+            Variant::Dictionary(dict) => Ok(dict
+                .inner
+                .into_iter()
+                .flat_map(|(k, v)| vec![k, v])
+                .collect()),
+            other => Err(errors::Error::unsupported(format!(
+                "children of '{}'",
+                other.signature()
+            ))),
+        }
+    }
+
+    /// Split a container into its children, flattening dictionary entries.
+    fn children(&self) -> errors::Result<Vec<&Variant>> {
+        match *self {
+            Variant::Option(ref maybe) => Ok(maybe.value().into_iter().collect()),
+            Variant::Vec(ref array) => Ok(array.inner.iter().collect()),
+            Variant::Structure(ref s) => Ok(s.fields.iter().collect()),
+            Variant::Dictionary(ref dict) => {
+                Ok(dict.inner.iter().flat_map(|(k, v)| vec![k, v]).collect())
+            }
+            ref other => Err(errors::Error::unsupported(format!(
+                "children of '{}'",
+                other.signature()
+            ))),
+        }
+    }
+}
+
+impl<'de> IntoDeserializer<'de, errors::Error> for Variant {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self {
+        self
+    }
+}
+
+impl<'de> IntoDeserializer<'de, errors::Error> for &'de Variant {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self {
+        self
+    }
+}
+
+// The plain trees map Rust types as configured by default.
+macro_rules! forward_to_default {
+    ($($method:ident($($arg:ident: $ty:ty),*))*) => {
+        $(
+            fn $method<V>(self, $($arg: $ty,)* visitor: V) -> errors::Result<V::Value>
+            where
+                V: de::Visitor<'de>,
+            {
+                VariantDeserializer::new(self, &Config::new()).$method($($arg,)* visitor)
+            }
+        )*
+    };
+}
+
+impl<'de> de::Deserializer<'de> for Variant {
+    type Error = errors::Error;
+
+    forward_to_default! {
+        deserialize_any() deserialize_bool() deserialize_i8() deserialize_i16()
+        deserialize_i32() deserialize_i64() deserialize_i128() deserialize_u8()
+        deserialize_u16() deserialize_u32() deserialize_u64() deserialize_u128()
+        deserialize_f32() deserialize_f64() deserialize_char() deserialize_str()
+        deserialize_string() deserialize_bytes() deserialize_byte_buf()
+        deserialize_option() deserialize_unit() deserialize_seq() deserialize_map()
+        deserialize_identifier() deserialize_ignored_any()
+        deserialize_unit_struct(name: &'static str)
+        deserialize_newtype_struct(name: &'static str)
+        deserialize_tuple(len: usize)
+        deserialize_tuple_struct(name: &'static str, len: usize)
+        deserialize_struct(name: &'static str, fields: &'static [&'static str])
+        deserialize_enum(name: &'static str, variants: &'static [&'static str])
+    }
+}
+
+impl<'de> de::Deserializer<'de> for &'de Variant {
+    type Error = errors::Error;
+
+    forward_to_default! {
+        deserialize_any() deserialize_bool() deserialize_i8() deserialize_i16()
+        deserialize_i32() deserialize_i64() deserialize_i128() deserialize_u8()
+        deserialize_u16() deserialize_u32() deserialize_u64() deserialize_u128()
+        deserialize_f32() deserialize_f64() deserialize_char() deserialize_str()
+        deserialize_string() deserialize_bytes() deserialize_byte_buf()
+        deserialize_option() deserialize_unit() deserialize_seq() deserialize_map()
+        deserialize_identifier() deserialize_ignored_any()
+        deserialize_unit_struct(name: &'static str)
+        deserialize_newtype_struct(name: &'static str)
+        deserialize_tuple(len: usize)
+        deserialize_tuple_struct(name: &'static str, len: usize)
+        deserialize_struct(name: &'static str, fields: &'static [&'static str])
+        deserialize_enum(name: &'static str, variants: &'static [&'static str])
+    }
+}
+
+/// The `Config` settings that apply to `Variant` trees.
+#[derive(Clone, Copy)]
+struct Mapping {
+    i8_type: I8Type,
+    trailing_defaults: bool,
+}
+
+impl Mapping {
+    fn wrap<T>(self, value: T) -> VariantDeserializer<T> {
+        VariantDeserializer {
+            value,
+            mapping: self,
+        }
+    }
+}
+
+/// A `Variant`, owned or borrowed.
+trait Node {
+    fn variant(&self) -> &Variant;
+}
+
+impl Node for Variant {
+    fn variant(&self) -> &Variant {
+        self
+    }
+}
+
+impl Node for &Variant {
+    fn variant(&self) -> &Variant {
+        self
+    }
+}
+
+/// A deserializer of `Variant` trees, owned or borrowed, following the
+/// settings of a `Config`.
+pub(crate) struct VariantDeserializer<T> {
+    value: T,
+    mapping: Mapping,
+}
+
+impl<T> VariantDeserializer<T> {
+    pub(crate) fn new(value: T, options: &Config) -> Self {
+        let mapping = Mapping {
+            i8_type: options.i8_type,
+            trailing_defaults: options.trailing_defaults,
+        };
+        mapping.wrap(value)
+    }
+}
+
+/// Check that a structure holds the `len` fields expected, unless its
+/// trailing fields may be left to their defaults.
+fn check_len(
+    value: &Variant,
+    mapping: Mapping,
+    len: usize,
+    exp: &dyn de::Expected,
+) -> errors::Result<()> {
+    match *value.content() {
+        Variant::Structure(ref s) if s.fields.len() < len && !mapping.trailing_defaults => {
+            Err(errors::Error::invalid_length(s.fields.len(), exp))
+        }
+        _ => Ok(()),
+    }
+}
+
+// Implement the methods shared by owned and borrowed trees, which only differ
+// in how values are visited.
+macro_rules! impl_mapped {
+    () => {
+        fn deserialize_byte_buf<V>(self, visitor: V) -> errors::Result<V::Value>
+        where
+            V: de::Visitor<'de>,
+        {
+            self.deserialize_bytes(visitor)
+        }
+
+        fn deserialize_tuple<V>(self, len: usize, visitor: V) -> errors::Result<V::Value>
+        where
+            V: de::Visitor<'de>,
+        {
+            check_len(self.value.variant(), self.mapping, len, &visitor)?;
+            self.deserialize_any(visitor)
+        }
+
+        fn deserialize_tuple_struct<V>(
+            self,
+            _name: &'static str,
+            len: usize,
+            visitor: V,
+        ) -> errors::Result<V::Value>
+        where
+            V: de::Visitor<'de>,
+        {
+            check_len(self.value.variant(), self.mapping, len, &visitor)?;
+            self.deserialize_any(visitor)
+        }
+
+        fn deserialize_struct<V>(
+            self,
+            _name: &'static str,
+            fields: &'static [&'static str],
+            visitor: V,
+        ) -> errors::Result<V::Value>
+        where
+            V: de::Visitor<'de>,
+        {
+            check_len(self.value.variant(), self.mapping, fields.len(), &visitor)?;
+            self.deserialize_any(visitor)
+        }
+
+        fn deserialize_ignored_any<V>(self, visitor: V) -> errors::Result<V::Value>
+        where
+            V: de::Visitor<'de>,
+        {
+            visitor.visit_unit()
+        }
+
+        // No GVariant counterpart: accept whatever the configured mapping wrote.
+        fn deserialize_i8<V>(self, visitor: V) -> errors::Result<V::Value>
+        where
+            V: de::Visitor<'de>,
+        {
+            match self.mapping.i8_type {
+                I8Type::I16 => self.deserialize_any(I8Visitor(visitor)),
+                I8Type::Reject => Err(errors::Error::unsupported("i8")),
+            }
+        }
+
+        fn deserialize_f32<V>(self, visitor: V) -> errors::Result<V::Value>
+        where
+            V: de::Visitor<'de>,
+        {
+            self.deserialize_any(F32Visitor(visitor))
+        }
+
+        fn deserialize_char<V>(self, visitor: V) -> errors::Result<V::Value>
+        where
+            V: de::Visitor<'de>,
+        {
+            self.deserialize_any(CharVisitor(visitor))
+        }
+
+        forward_to_deserialize_any! {
+            bool i16 i32 i64 i128 u8 u16 u32 u64 u128 f64 str string
+            unit unit_struct seq map identifier
+        }
+    };
+}
+
+impl<'de> de::Deserializer<'de> for VariantDeserializer<Variant> {
+    type Error = errors::Error;
+
+    fn deserialize_any<V>(self, visitor: V) -> errors::Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        let mapping = self.mapping;
+        match self.value {
+            Variant::Bool(v) => visitor.visit_bool(v),
+            Variant::U8(v) => visitor.visit_u8(v),
+            Variant::U16(v) => visitor.visit_u16(v),
+            Variant::U32(v) => visitor.visit_u32(v),
+            Variant::U64(v) => visitor.visit_u64(v),
+            Variant::I16(v) => visitor.visit_i16(v),
+            Variant::I32(v) => visitor.visit_i32(v),
+            Variant::I64(v) => visitor.visit_i64(v),
+            Variant::F64(v) => visitor.visit_f64(v),
+            Variant::String(v) | Variant::ObjectPath(v) | Variant::Signature(v) => {
+                visitor.visit_string(v)
+            }
+            Variant::Variant(inner) => mapping.wrap(*inner).deserialize_any(visitor),
+            Variant::Option(maybe) => match maybe.into_value() {
+                None => visitor.visit_none(),
+                Some(inner) => visitor.visit_some(mapping.wrap(inner)),
+            },
+            Variant::Vec(array) => visit_seq(array.inner, mapping, visitor),
+            Variant::Structure(ref s) if s.fields.is_empty() => visitor.visit_unit(),
+            Variant::Structure(s) => visit_seq(s.fields, mapping, visitor),
+            Variant::Dictionary(dict) => visit_map(dict.inner, mapping, visitor),
+            Variant::Handle(v) => visitor.visit_i32(v.0),
+        }
+    }
+
+    fn deserialize_option<V>(self, visitor: V) -> errors::Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        let mapping = self.mapping;
+        match self.value.into_content() {
+            Variant::Option(maybe) => match maybe.into_value() {
+                None => visitor.visit_none(),
+                Some(inner) => visitor.visit_some(mapping.wrap(inner)),
+            },
+            other => visitor.visit_some(mapping.wrap(other)),
+        }
+    }
+
+    fn deserialize_newtype_struct<V>(
+        self,
+        name: &'static str,
+        visitor: V,
+    ) -> errors::Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        if name != CONTAINER_TOKEN {
+            return visitor.visit_newtype_struct(self);
+        }
+        visitor.visit_seq(ContainerAccess {
+            signature: Some(self.value.signature()),
+            children: self.value.into_children()?.into_iter(),
+            mapping: self.mapping,
+        })
+    }
+
+    fn deserialize_bytes<V>(self, visitor: V) -> errors::Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        let mapping = self.mapping;
+        match self.value.into_content() {
+            Variant::Vec(array) => match bytes(&array.inner) {
+                Some(buf) => visitor.visit_byte_buf(buf),
+                None => visit_seq(array.inner, mapping, visitor),
+            },
+            other => mapping.wrap(other).deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_enum<V>(
+        self,
+        name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> errors::Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        let mapping = self.mapping;
+        match name {
+            VARIANT_TOKEN => {
+                visitor.visit_enum(ContentAccess(mapping.wrap(self.value.into_content())))
+            }
+            EXACT_TOKEN => visitor.visit_enum(ContentAccess(self)),
+            _ => Err(errors::Error::unsupported("enum")),
+        }
+    }
+
+    impl_mapped!();
+}
+
+impl<'de> de::Deserializer<'de> for VariantDeserializer<&'de Variant> {
+    type Error = errors::Error;
+
+    fn deserialize_any<V>(self, visitor: V) -> errors::Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        let mapping = self.mapping;
+        match *self.value {
+            Variant::Bool(v) => visitor.visit_bool(v),
+            Variant::U8(v) => visitor.visit_u8(v),
+            Variant::U16(v) => visitor.visit_u16(v),
+            Variant::U32(v) => visitor.visit_u32(v),
+            Variant::U64(v) => visitor.visit_u64(v),
+            Variant::I16(v) => visitor.visit_i16(v),
+            Variant::I32(v) => visitor.visit_i32(v),
+            Variant::I64(v) => visitor.visit_i64(v),
+            Variant::F64(v) => visitor.visit_f64(v),
+            Variant::String(ref v) | Variant::ObjectPath(ref v) | Variant::Signature(ref v) => {
+                visitor.visit_borrowed_str(v)
+            }
+            Variant::Variant(ref inner) => mapping.wrap(&**inner).deserialize_any(visitor),
+            Variant::Option(ref maybe) => match maybe.value() {
+                None => visitor.visit_none(),
+                Some(inner) => visitor.visit_some(mapping.wrap(inner)),
+            },
+            Variant::Vec(ref array) => visit_seq(&array.inner, mapping, visitor),
+            Variant::Structure(ref s) if s.fields.is_empty() => visitor.visit_unit(),
+            Variant::Structure(ref s) => visit_seq(&s.fields, mapping, visitor),
+            Variant::Dictionary(ref dict) => visit_map(&dict.inner, mapping, visitor),
+            Variant::Handle(v) => visitor.visit_i32(v.0),
+        }
+    }
+
+    fn deserialize_option<V>(self, visitor: V) -> errors::Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        let mapping = self.mapping;
+        match *self.value.content() {
+            Variant::Option(ref maybe) => match maybe.value() {
+                None => visitor.visit_none(),
+                Some(inner) => visitor.visit_some(mapping.wrap(inner)),
+            },
+            ref other => visitor.visit_some(mapping.wrap(other)),
+        }
+    }
+
+    fn deserialize_newtype_struct<V>(
+        self,
+        name: &'static str,
+        visitor: V,
+    ) -> errors::Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        if name != CONTAINER_TOKEN {
+            return visitor.visit_newtype_struct(self);
+        }
+        visitor.visit_seq(ContainerAccess {
+            signature: Some(self.value.signature()),
+            children: self.value.children()?.into_iter(),
+            mapping: self.mapping,
+        })
+    }
+
+    fn deserialize_bytes<V>(self, visitor: V) -> errors::Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        let mapping = self.mapping;
+        match *self.value.content() {
+            Variant::Vec(ref array) => match bytes(&array.inner) {
+                Some(buf) => visitor.visit_byte_buf(buf),
+                None => visit_seq(&array.inner, mapping, visitor),
+            },
+            ref other => mapping.wrap(other).deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_enum<V>(
+        self,
+        name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> errors::Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        let mapping = self.mapping;
+        match name {
+            VARIANT_TOKEN => visitor.visit_enum(ContentAccess(mapping.wrap(self.value.content()))),
+            EXACT_TOKEN => visitor.visit_enum(ContentAccess(self)),
+            _ => Err(errors::Error::unsupported("enum")),
+        }
+    }
+
+    impl_mapped!();
+}
+
+/// Visit a sequence of values, checking that all of them are consumed.
+fn visit_seq<'de, I, V>(elements: I, mapping: Mapping, visitor: V) -> errors::Result<V::Value>
+where
+    I: IntoIterator,
+    VariantDeserializer<I::Item>: de::Deserializer<'de, Error = errors::Error>,
+    V: de::Visitor<'de>,
+{
+    let mut seq = ElementsAccess {
+        iter: elements.into_iter(),
+        mapping,
+        index: 0,
+    };
+    let value = visitor.visit_seq(&mut seq)?;
+    let remaining = seq.iter.count();
+    if remaining > 0 {
+        let len = seq.index + remaining;
+        return Err(de::Error::invalid_length(len, &"fewer elements"));
+    }
+    Ok(value)
+}
+
+/// Visit the entries of a dictionary, checking that all of them are consumed.
+fn visit_map<'de, I, K, V>(entries: I, mapping: Mapping, visitor: V) -> errors::Result<V::Value>
+where
+    I: IntoIterator<Item = (K, K)>,
+    K: Node,
+    VariantDeserializer<K>: de::Deserializer<'de, Error = errors::Error>,
+    V: de::Visitor<'de>,
+{
+    let mut map = EntriesAccess {
+        iter: entries.into_iter(),
+        mapping,
+        value: None,
+        index: 0,
+    };
+    let value = visitor.visit_map(&mut map)?;
+    let remaining = map.iter.count();
+    if remaining > 0 {
+        let len = map.index + remaining;
+        return Err(de::Error::invalid_length(len, &"fewer entries"));
+    }
+    Ok(value)
+}
+
+/// Sequence access over values, recording the index of failing ones.
+struct ElementsAccess<I> {
+    iter: I,
+    mapping: Mapping,
+    index: usize,
+}
+
+impl<'de, I> de::SeqAccess<'de> for ElementsAccess<I>
+where
+    I: Iterator,
+    VariantDeserializer<I::Item>: de::Deserializer<'de, Error = errors::Error>,
+{
+    type Error = errors::Error;
+
+    fn next_element_seed<S>(&mut self, seed: S) -> errors::Result<Option<S::Value>>
+    where
+        S: de::DeserializeSeed<'de>,
+    {
+        let elem = match self.iter.next() {
+            Some(elem) => elem,
+            None => return Ok(None),
+        };
+        let index = self.index;
+        self.index += 1;
+        seed.deserialize(self.mapping.wrap(elem))
+            .map(Some)
+            .map_err(|e| e.index(index))
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        match self.iter.size_hint() {
+            (lower, Some(upper)) if lower == upper => Some(upper),
+            _ => None,
+        }
+    }
+}
+
+/// Map access over dictionary entries, recording the key of failing values.
+struct EntriesAccess<I, K> {
+    iter: I,
+    mapping: Mapping,
+    value: Option<(K, Segment)>,
+    index: usize,
+}
+
+/// Path segment of a dictionary entry: its key if string-like, else its index.
+enum Segment {
+    Key(String),
+    Index(usize),
+}
+
+impl Segment {
+    fn locate(&self, err: errors::Error) -> errors::Error {
+        match *self {
+            Segment::Key(ref key) => err.field(key),
+            Segment::Index(index) => err.index(index),
+        }
+    }
+}
+
+impl<'de, I, K> de::MapAccess<'de> for EntriesAccess<I, K>
+where
+    I: Iterator<Item = (K, K)>,
+    K: Node,
+    VariantDeserializer<K>: de::Deserializer<'de, Error = errors::Error>,
+{
+    type Error = errors::Error;
+
+    fn next_key_seed<S>(&mut self, seed: S) -> errors::Result<Option<S::Value>>
+    where
+        S: de::DeserializeSeed<'de>,
+    {
+        let (key, value) = match self.iter.next() {
+            Some(entry) => entry,
+            None => return Ok(None),
+        };
+        let segment = match key.variant().as_str() {
+            Some(name) => Segment::Key(name.to_owned()),
+            None => Segment::Index(self.index),
+        };
+        self.index += 1;
+        let res = seed.deserialize(self.mapping.wrap(key));
+        let res = res.map(Some).map_err(|e| segment.locate(e));
+        self.value = Some((value, segment));
+        res
+    }
+
+    fn next_value_seed<S>(&mut self, seed: S) -> errors::Result<S::Value>
+    where
+        S: de::DeserializeSeed<'de>,
+    {
+        let (value, segment) = self
+            .value
+            .take()
+            .ok_or_else(|| errors::Error::from("variant: value without key"))?;
+        seed.deserialize(self.mapping.wrap(value))
+            .map_err(|e| segment.locate(e))
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        match self.iter.size_hint() {
+            (lower, Some(upper)) if lower == upper => Some(upper),
+            _ => None,
+        }
+    }
+}
+
+/// Collect the content of a byte array (`ay`).
+fn bytes(elements: &[Variant]) -> Option<Vec<u8>> {
+    elements.iter().map(Variant::as_u8).collect()
+}
+
+/// Enum access for a `Variant` target, keyed by the arm discriminant.
+///
+/// This is how the `Deserialize` for `Variant` gets its content back out of
+/// a tree: the arm is selected by index, and its payload is deserialized
+/// from the very same value.
+struct ContentAccess<T>(VariantDeserializer<T>);
+
+impl<'de, T> de::EnumAccess<'de> for ContentAccess<T>
+where
+    T: Node,
+    VariantDeserializer<T>: de::Deserializer<'de, Error = errors::Error>,
+{
+    type Error = errors::Error;
+    type Variant = Self;
+
+    fn variant_seed<S>(self, seed: S) -> errors::Result<(S::Value, Self)>
+    where
+        S: de::DeserializeSeed<'de>,
+    {
+        let discriminant = self.0.value.variant().discriminant();
+        let id = seed.deserialize(IntoDeserializer::<errors::Error>::into_deserializer(
+            discriminant,
+        ))?;
+        Ok((id, self))
+    }
+}
+
+impl<'de, T> de::VariantAccess<'de> for ContentAccess<T>
+where
+    T: Node,
+    VariantDeserializer<T>: de::Deserializer<'de, Error = errors::Error>,
+{
+    type Error = errors::Error;
+
+    fn unit_variant(self) -> errors::Result<()> {
+        Err(errors::Error::unsupported("unit variant"))
+    }
+
+    fn newtype_variant_seed<S>(self, seed: S) -> errors::Result<S::Value>
+    where
+        S: de::DeserializeSeed<'de>,
+    {
+        seed.deserialize(self.0)
+    }
+
+    fn tuple_variant<V>(self, _len: usize, _visitor: V) -> errors::Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        Err(errors::Error::unsupported("tuple variant"))
+    }
+
+    fn struct_variant<V>(
+        self,
+        _fields: &'static [&'static str],
+        _visitor: V,
+    ) -> errors::Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        Err(errors::Error::unsupported("struct variant"))
+    }
+}
@@ -0,0 +1,182 @@
+//! Type-directed decoding of serialized data into `Variant` trees.
+
+use super::{Array, Dictionary, Maybe, Structure, Variant};
+use crate::errors;
+use crate::framing;
+use crate::handle::Handle;
+use crate::variant_type::{VariantType, MAX_DEPTH};
+use alloc::borrow::ToOwned;
+use alloc::boxed::Box;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use byteorder::{BigEndian, ByteOrder, LittleEndian};
+
+/// Decoder of serialized values, whose type is known upfront.
+pub(crate) struct Decoder {
+    pub(crate) network_endian: bool,
+}
+
+macro_rules! read_number {
+    ($self:ident, $data:ident, $method:ident) => {
+        if $self.network_endian {
+            BigEndian::$method($data)
+        } else {
+            LittleEndian::$method($data)
+        }
+    };
+}
+
+impl Decoder {
+    /// Decode a serialized `v`, returning its content.
+    pub(crate) fn decode_boxed(&self, data: &[u8]) -> errors::Result<Variant> {
+        self.boxed(data, 0)
+    }
+
+    /// Decode a serialized value of type `ty`.
+    pub(crate) fn decode(&self, data: &[u8], ty: &VariantType) -> errors::Result<Variant> {
+        self.value(data, ty, 0)
+    }
+
+    fn boxed(&self, data: &[u8], depth: usize) -> errors::Result<Variant> {
+        let (value, signature) = framing::split_variant(data)?;
+        let ty = VariantType::parse(signature).map_err(|e| e.shift(value.len() as u64 + 1))?;
+        trace!(
+            "decode: variant of type '{}', length={:#x}",
+            ty,
+            value.len()
+        );
+        self.value(value, &ty, depth + 1)
+    }
+
+    fn value(&self, data: &[u8], ty: &VariantType, depth: usize) -> errors::Result<Variant> {
+        if depth > MAX_DEPTH {
+            bail!(errors::Error::limit("decode: nesting too deep"));
+        }
+        if let Some(size) = ty.fixed_size() {
+            if data.len() != size {
+                return Err(errors::Error::framing(format!(
+                    "decode: wrong length for '{}' - expected={}, got={}",
+                    ty,
+                    size,
+                    data.len()
+                )));
+            }
+        }
+
+        let value = match *ty {
+            VariantType::Bool => Variant::Bool(data[0] != 0),
+            VariantType::U8 => Variant::U8(data[0]),
+            VariantType::U16 => Variant::U16(read_number!(self, data, read_u16)),
+            VariantType::U32 => Variant::U32(read_number!(self, data, read_u32)),
+            VariantType::U64 => Variant::U64(read_number!(self, data, read_u64)),
+            VariantType::I16 => Variant::I16(read_number!(self, data, read_i16)),
+            VariantType::I32 => Variant::I32(read_number!(self, data, read_i32)),
+            VariantType::I64 => Variant::I64(read_number!(self, data, read_i64)),
+            VariantType::F64 => Variant::F64(read_number!(self, data, read_f64)),
+            VariantType::String => Variant::String(string(data)?),
+            VariantType::ObjectPath => Variant::ObjectPath(string(data)?),
+            VariantType::Signature => Variant::Signature(string(data)?),
+            VariantType::Handle => Variant::Handle(Handle(read_number!(self, data, read_i32))),
+            VariantType::Variant => Variant::Variant(Box::new(self.boxed(data, depth)?)),
+            VariantType::Option(ref inner) => {
+                let value = match framing::maybe_content(data, inner.fixed_size().is_some())? {
+                    Some(content) => Some(self.value(content, inner, depth + 1)?),
+                    None => None,
+                };
+                Variant::Option(Maybe {
+                    inner_type: (**inner).clone(),
+                    value: value.map(Box::new),
+                })
+            }
+            VariantType::Vec(ref elem) => match **elem {
+                VariantType::DictEntry(ref k, ref v) => {
+                    let ranges = framing::split_array(data, elem.alignment(), elem.fixed_size())?;
+                    let layout = [
+                        (k.alignment(), k.fixed_size()),
+                        (v.alignment(), v.fixed_size()),
+                    ];
+                    let mut inner = alloc::collections::BTreeMap::new();
+                    for (i, (start, end)) in ranges.into_iter().enumerate() {
+                        let entry = &data[start..end];
+                        let at = |e: errors::Error, pos: usize| e.shift((start + pos) as u64);
+                        let fields =
+                            framing::split_fields(entry, &layout).map_err(|e| at(e, 0).index(i))?;
+                        let key = self
+                            .value(&entry[fields[0].0..fields[0].1], k, depth + 1)
+                            .map_err(|e| at(e, fields[0].0).index(i))?;
+                        let value = self
+                            .value(&entry[fields[1].0..fields[1].1], v, depth + 1)
+                            .map_err(|e| match key.as_str() {
+                                Some(name) => at(e, fields[1].0).field(name),
+                                None => at(e, fields[1].0).index(i),
+                            })?;
+                        inner.insert(key, value);
+                    }
+                    Variant::Dictionary(Dictionary {
+                        key_type: (**k).clone(),
+                        value_type: (**v).clone(),
+                        inner,
+                    })
+                }
+                _ => {
+                    let ranges = framing::split_array(data, elem.alignment(), elem.fixed_size())?;
+                    let inner = ranges
+                        .into_iter()
+                        .enumerate()
+                        .map(|(i, (start, end))| {
+                            self.value(&data[start..end], elem, depth + 1)
+                                .map_err(|e| e.shift(start as u64).index(i))
+                        })
+                        .collect::<errors::Result<_>>()?;
+                    Variant::Vec(Array {
+                        elem_type: (**elem).clone(),
+                        inner,
+                    })
+                }
+            },
+            VariantType::Structure(ref types) => {
+                let layout: Vec<_> = types
+                    .iter()
+                    .map(|t| (t.alignment(), t.fixed_size()))
+                    .collect();
+                let ranges = framing::split_fields(data, &layout)?;
+                let fields = ranges
+                    .into_iter()
+                    .zip(types)
+                    .enumerate()
+                    .map(|(i, ((start, end), t))| {
+                        self.value(&data[start..end], t, depth + 1)
+                            .map_err(|e| e.shift(start as u64).index(i))
+                    })
+                    .collect::<errors::Result<_>>()?;
+                Variant::Structure(Structure { fields })
+            }
+            VariantType::DictEntry(..) => {
+                bail!(errors::Error::unsupported(
+                    "dictionary entry outside of an array"
+                ))
+            }
+        };
+        Ok(value)
+    }
+}
+
+/// Decode a nul-terminated string.
+fn string(data: &[u8]) -> errors::Result<String> {
+    nul_str(data).map(str::to_owned)
+}
+
+/// Borrow the content of a nul-terminated string.
+pub(crate) fn nul_str(data: &[u8]) -> errors::Result<&str> {
+    let content = match data.split_last() {
+        Some((0x00, content)) => content,
+        _ => bail!(errors::Error::framing("non-terminated string")),
+    };
+    if let Some(pos) = content.iter().position(|b| *b == 0x00) {
+        bail!(errors::Error::framing("string with embedded nul byte").at(pos as u64));
+    }
+    core::str::from_utf8(content).map_err(|e| {
+        errors::Error::from_kind(errors::ErrorKind::InvalidUtf8).at(e.valid_up_to() as u64)
+    })
+}
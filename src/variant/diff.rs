@@ -0,0 +1,195 @@
+//! Structural differences between `Variant` trees.
+
+use super::{query, Variant};
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::cmp::Ordering;
+use core::fmt;
+
+/// A difference between two `Variant` trees, as found by `Variant::diff`.
+///
+/// Paths are written as `Variant::query` paths: `[i]` for structure fields
+/// and array elements, `['key']` for string-like dictionary keys and `[key]`
+/// for other keys. The root value has an empty path.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Change {
+    /// A value only present in the new tree.
+    Added {
+        /// Path of the value.
+        path: String,
+        /// New value.
+        value: Variant,
+    },
+    /// A value only present in the old tree.
+    Removed {
+        /// Path of the value.
+        path: String,
+        /// Old value.
+        value: Variant,
+    },
+    /// A value replaced by another one.
+    Changed {
+        /// Path of the value.
+        path: String,
+        /// Old value.
+        old: Variant,
+        /// New value.
+        new: Variant,
+    },
+}
+
+impl Change {
+    /// Return the path of the changed value.
+    pub fn path(&self) -> &str {
+        match *self {
+            Change::Added { ref path, .. }
+            | Change::Removed { ref path, .. }
+            | Change::Changed { ref path, .. } => path,
+        }
+    }
+}
+
+/// Renders one line per change, with the value in GVariant text format.
+///
+/// Values replaced by another type are annotated with their types.
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let path = match self.path() {
+            "" => "/",
+            path => path,
+        };
+        match *self {
+            Change::Added { ref value, .. } => write!(f, "+ {}: {}", path, value),
+            Change::Removed { ref value, .. } => write!(f, "- {}: {}", path, value),
+            Change::Changed {
+                ref old, ref new, ..
+            } if old.variant_type() != new.variant_type() => {
+                write!(f, "~ {}: {} -> {}", path, Annotated(old), Annotated(new))
+            }
+            Change::Changed {
+                ref old, ref new, ..
+            } => {
+                write!(f, "~ {}: {} -> {}", path, old, new)
+            }
+        }
+    }
+}
+
+/// A value in GVariant text format, with its type annotation.
+struct Annotated<'a>(&'a Variant);
+
+impl fmt::Display for Annotated<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let keyword = match *self.0 {
+            Variant::Bool(_) => "boolean",
+            Variant::U8(_) => "byte",
+            Variant::U16(_) => "uint16",
+            Variant::U32(_) => "uint32",
+            Variant::U64(_) => "uint64",
+            Variant::I16(_) => "int16",
+            Variant::I32(_) => "int32",
+            Variant::I64(_) => "int64",
+            Variant::F64(_) => "double",
+            // Already annotated.
+            Variant::ObjectPath(_) | Variant::Signature(_) | Variant::Handle(_) => {
+                return write!(f, "{}", self.0)
+            }
+            ref value => return write!(f, "@{} {}", value.variant_type(), value),
+        };
+        write!(f, "{} {}", keyword, self.0)
+    }
+}
+
+/// Append the changes from `old` to `new`, at `path`, to `changes`.
+///
+/// Array elements are compared by index, dictionary entries by key.
+pub(crate) fn diff(path: &str, old: &Variant, new: &Variant, changes: &mut Vec<Change>) {
+    match (old, new) {
+        (Variant::Variant(v0), Variant::Variant(v1)) => diff(path, v0, v1, changes),
+        (Variant::Option(v0), Variant::Option(v1)) if v0.inner_type == v1.inner_type => {
+            match (&v0.value, &v1.value) {
+                (Some(v0), Some(v1)) => diff(path, v0, v1, changes),
+                (Some(v0), None) => changes.push(Change::Removed {
+                    path: path.into(),
+                    value: (**v0).clone(),
+                }),
+                (None, Some(v1)) => changes.push(Change::Added {
+                    path: path.into(),
+                    value: (**v1).clone(),
+                }),
+                (None, None) => {}
+            }
+        }
+        (Variant::Vec(v0), Variant::Vec(v1)) if v0.elem_type == v1.elem_type => {
+            let (old, new) = (&v0.inner, &v1.inner);
+            for i in 0..old.len().max(new.len()) {
+                let path = format!("{}[{}]", path, i);
+                match (old.get(i), new.get(i)) {
+                    (Some(v0), Some(v1)) => diff(&path, v0, v1, changes),
+                    (Some(v0), None) => changes.push(Change::Removed {
+                        path,
+                        value: v0.clone(),
+                    }),
+                    (None, Some(v1)) => changes.push(Change::Added {
+                        path,
+                        value: v1.clone(),
+                    }),
+                    (None, None) => {}
+                }
+            }
+        }
+        (Variant::Structure(v0), Variant::Structure(v1)) if v0.len() == v1.len() => {
+            for (i, (v0, v1)) in v0.fields.iter().zip(&v1.fields).enumerate() {
+                diff(&format!("{}[{}]", path, i), v0, v1, changes);
+            }
+        }
+        (Variant::Dictionary(v0), Variant::Dictionary(v1))
+            if (&v0.key_type, &v0.value_type) == (&v1.key_type, &v1.value_type) =>
+        {
+            let mut old = v0.inner.iter().peekable();
+            let mut new = v1.inner.iter().peekable();
+            loop {
+                let order = match (old.peek(), new.peek()) {
+                    (Some((k0, _)), Some((k1, _))) => k0.cmp(k1),
+                    (Some(_), None) => Ordering::Less,
+                    (None, Some(_)) => Ordering::Greater,
+                    (None, None) => break,
+                };
+                match order {
+                    Ordering::Less => {
+                        let (key, value) = old.next().expect("peeked entry");
+                        changes.push(Change::Removed {
+                            path: key_path(path, key),
+                            value: value.clone(),
+                        });
+                    }
+                    Ordering::Greater => {
+                        let (key, value) = new.next().expect("peeked entry");
+                        changes.push(Change::Added {
+                            path: key_path(path, key),
+                            value: value.clone(),
+                        });
+                    }
+                    Ordering::Equal => {
+                        let (key, v0) = old.next().expect("peeked entry");
+                        let (_, v1) = new.next().expect("peeked entry");
+                        diff(&key_path(path, key), v0, v1, changes);
+                    }
+                }
+            }
+        }
+        // Leaves compare as ordered, so that NaNs are equal.
+        _ if old.cmp(new) == Ordering::Equal => {}
+        _ => changes.push(Change::Changed {
+            path: path.into(),
+            old: old.clone(),
+            new: new.clone(),
+        }),
+    }
+}
+
+/// Append a dictionary key to `path`.
+fn key_path(path: &str, key: &Variant) -> String {
+    format!("{}{}", path, query::key_segment(key))
+}
@@ -0,0 +1,148 @@
+//! Encoding of `Variant` trees into serialized data.
+
+use super::Variant;
+use crate::framing;
+use crate::variant_type::VariantType;
+use alloc::vec;
+use alloc::vec::Vec;
+use byteorder::{BigEndian, ByteOrder, LittleEndian};
+
+/// Encoder of `Variant` trees, always in normal form.
+///
+/// Padding is zeroed, framing offsets are as narrow as possible, and
+/// dictionary entries are sorted by key.
+pub(crate) struct Encoder {
+    pub(crate) network_endian: bool,
+}
+
+macro_rules! write_number {
+    ($self:ident, $buf:ident, $method:ident, $size:expr, $v:expr) => {{
+        let mut bytes = [0; $size];
+        if $self.network_endian {
+            BigEndian::$method(&mut bytes, $v)
+        } else {
+            LittleEndian::$method(&mut bytes, $v)
+        }
+        $buf.extend_from_slice(&bytes);
+    }};
+}
+
+impl Encoder {
+    /// Encode a value, as its own type.
+    pub(crate) fn encode(&self, value: &Variant) -> Vec<u8> {
+        let mut buf = vec![];
+        self.value(&mut buf, value);
+        buf
+    }
+
+    // Positions are absolute: each container starts aligned to its own
+    // alignment, which is the largest of its children.
+    fn value(&self, buf: &mut Vec<u8>, value: &Variant) {
+        match *value {
+            Variant::Bool(v) => buf.push(u8::from(v)),
+            Variant::U8(v) => buf.push(v),
+            Variant::U16(v) => write_number!(self, buf, write_u16, 2, v),
+            Variant::U32(v) => write_number!(self, buf, write_u32, 4, v),
+            Variant::U64(v) => write_number!(self, buf, write_u64, 8, v),
+            Variant::I16(v) => write_number!(self, buf, write_i16, 2, v),
+            Variant::I32(v) => write_number!(self, buf, write_i32, 4, v),
+            Variant::I64(v) => write_number!(self, buf, write_i64, 8, v),
+            Variant::F64(v) => write_number!(self, buf, write_f64, 8, v),
+            Variant::Handle(v) => write_number!(self, buf, write_i32, 4, v.0),
+            Variant::String(ref v) | Variant::ObjectPath(ref v) | Variant::Signature(ref v) => {
+                buf.extend_from_slice(v.as_bytes());
+                buf.push(0x00);
+            }
+            Variant::Variant(ref inner) => {
+                self.value(buf, inner);
+                buf.push(0x00);
+                buf.extend_from_slice(inner.signature().as_bytes());
+            }
+            Variant::Option(ref maybe) => {
+                if let Some(inner) = maybe.value() {
+                    self.value(buf, inner);
+                    if maybe.inner_type().fixed_size().is_none() {
+                        buf.push(0x00);
+                    }
+                }
+            }
+            Variant::Vec(ref array) => {
+                self.array(buf, array.element_type(), array.iter(), |buf, elem| {
+                    self.value(buf, elem)
+                })
+            }
+            Variant::Dictionary(ref dict) => {
+                let entry = VariantType::DictEntry(
+                    dict.key_type().clone().into(),
+                    dict.value_type().clone().into(),
+                );
+                self.array(buf, &entry, dict.iter(), |buf, (k, v)| {
+                    self.fields(buf, &[(k, dict.key_type()), (v, dict.value_type())])
+                })
+            }
+            Variant::Structure(ref s) => {
+                let types: Vec<_> = s.iter().map(Variant::variant_type).collect();
+                let fields: Vec<_> = s.iter().zip(&types).collect();
+                self.fields(buf, &fields)
+            }
+        }
+    }
+
+    /// Encode the elements of an array, each written by `write`.
+    fn array<T, I, F>(&self, buf: &mut Vec<u8>, elem: &VariantType, elems: I, mut write: F)
+    where
+        I: Iterator<Item = T>,
+        F: FnMut(&mut Vec<u8>, T),
+    {
+        let start = buf.len();
+        let fixed = elem.fixed_size().is_some();
+        let mut ends = vec![];
+        for e in elems {
+            pad(buf, elem.alignment());
+            write(buf, e);
+            if !fixed {
+                ends.push(buf.len() - start);
+            }
+        }
+        framing::write_offsets(buf, start, &ends);
+    }
+
+    /// Encode the fields of a structure or dictionary entry.
+    fn fields(&self, buf: &mut Vec<u8>, fields: &[(&Variant, &VariantType)]) {
+        // Like `()`, a structure without fields is a single 0x00 byte.
+        if fields.is_empty() {
+            buf.push(0x00);
+            return;
+        }
+
+        let start = buf.len();
+        let mut alignment = 1;
+        let mut fixed = true;
+        let mut ends = vec![];
+        for (i, &(field, ty)) in fields.iter().enumerate() {
+            alignment = alignment.max(ty.alignment());
+            pad(buf, ty.alignment());
+            self.value(buf, field);
+            if ty.fixed_size().is_none() {
+                fixed = false;
+                if i + 1 < fields.len() {
+                    ends.push(buf.len() - start);
+                }
+            }
+        }
+
+        // Fixed-size structures are padded to their alignment, others are
+        // followed by their framing offsets, in reverse order.
+        if fixed {
+            pad(buf, alignment);
+        }
+        ends.reverse();
+        framing::write_offsets(buf, start, &ends);
+    }
+}
+
+/// Pad with zero bytes up to the next multiple of `alignment`.
+fn pad(buf: &mut Vec<u8>, alignment: usize) {
+    let len = framing::align(buf.len(), alignment);
+    buf.resize(len, 0x00);
+}
@@ -0,0 +1,573 @@
+mod collections;
+mod convert;
+pub(crate) mod de;
+pub(crate) mod decode;
+mod diff;
+pub(crate) mod encode;
+mod query;
+pub(crate) mod ser;
+mod vardict;
+
+pub use self::diff::Change;
+pub use self::vardict::VarDict;
+
+use crate::errors;
+use crate::handle::Handle;
+use crate::variant_type::VariantType;
+use alloc::boxed::Box;
+use alloc::collections::BTreeMap;
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::{cmp, fmt, hash};
+use ordered_float::OrderedFloat;
+
+/// Check that `value` is of type `expected`.
+fn check_type(expected: &VariantType, value: &Variant) -> errors::Result<()> {
+    let actual = value.variant_type();
+    if actual != *expected {
+        return Err(errors::Error::mismatch(expected, actual));
+    }
+    Ok(())
+}
+
+/// GVariant array, homogeneous inner type.
+#[derive(Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct Array {
+    /// Element type.
+    elem_type: VariantType,
+    /// Inner elements.
+    inner: Vec<Variant>,
+}
+
+impl Array {
+    /// Build an array of `elem_type` elements.
+    ///
+    /// All elements are checked to be of the declared type. Arrays of
+    /// dictionary entries are represented by `Dictionary` instead.
+    pub fn with_type(elem_type: VariantType, elements: Vec<Variant>) -> errors::Result<Self> {
+        if let VariantType::DictEntry(..) = elem_type {
+            return Err(errors::Error::unsupported(
+                "array: dictionary entries must be stored in a Dictionary",
+            ));
+        }
+        for elem in &elements {
+            check_type(&elem_type, elem)?;
+        }
+        let array = Self {
+            elem_type,
+            inner: elements,
+        };
+        Ok(array)
+    }
+
+    /// Build an array, taking the element type from the first element.
+    ///
+    /// An empty array has no element to infer its type from, and is an `av`:
+    /// use `with_type` to build another type of empty array.
+    pub fn from_elements(elements: Vec<Variant>) -> errors::Result<Self> {
+        let elem_type = elements
+            .first()
+            .map_or(VariantType::Variant, Variant::variant_type);
+        Self::with_type(elem_type, elements)
+    }
+
+    /// Return the type of the elements.
+    pub fn element_type(&self) -> &VariantType {
+        &self.elem_type
+    }
+
+    /// Transform self into a `Variant`.
+    pub fn into_variant(self) -> Variant {
+        Variant::Vec(self)
+    }
+
+    /// Return type signature.
+    pub fn signature(&self) -> String {
+        format!("a{}", self.elem_type)
+    }
+}
+
+/// GVariant dictionary, homogeneous inner key-value types.
+#[derive(Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct Dictionary {
+    /// Key type.
+    key_type: VariantType,
+    /// Value type.
+    value_type: VariantType,
+    /// Inner map.
+    inner: BTreeMap<Variant, Variant>,
+}
+
+impl Dictionary {
+    /// Build a dictionary of `key_type` keys and `value_type` values.
+    ///
+    /// The key type must be a basic type, and all entries are checked to
+    /// be of the declared types.
+    pub fn with_types(
+        key_type: VariantType,
+        value_type: VariantType,
+        map: BTreeMap<Variant, Variant>,
+    ) -> errors::Result<Self> {
+        if !key_type.is_basic() {
+            return Err(errors::Error::signature(format!(
+                "dictionary: non-basic key type '{}'",
+                key_type
+            )));
+        }
+        for (k, v) in &map {
+            check_type(&key_type, k)?;
+            check_type(&value_type, v)?;
+        }
+        let dict = Self {
+            key_type,
+            value_type,
+            inner: map,
+        };
+        Ok(dict)
+    }
+
+    /// Build a dictionary, taking key and value types from the first entry.
+    ///
+    /// An empty map has no entry to infer its types from, and is an `a{sv}`:
+    /// use `with_types` to build another type of empty dictionary.
+    pub fn from_map(map: BTreeMap<Variant, Variant>) -> errors::Result<Self> {
+        let (key_type, value_type) = match map.iter().next() {
+            Some((k, v)) => (k.variant_type(), v.variant_type()),
+            None => (VariantType::String, VariantType::Variant),
+        };
+        Self::with_types(key_type, value_type, map)
+    }
+
+    /// Return the type of the keys.
+    pub fn key_type(&self) -> &VariantType {
+        &self.key_type
+    }
+
+    /// Return the type of the values.
+    pub fn value_type(&self) -> &VariantType {
+        &self.value_type
+    }
+
+    /// Transform self into a `Variant`.
+    pub fn into_variant(self) -> Variant {
+        Variant::Dictionary(self)
+    }
+
+    /// Return type signature.
+    pub fn signature(&self) -> String {
+        format!("a{{{}{}}}", self.key_type, self.value_type)
+    }
+}
+
+/// GVariant maybe, an optional value of a given type.
+#[derive(Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct Maybe {
+    /// Type of the (optional) inner value.
+    inner_type: VariantType,
+    /// Inner value, if any.
+    value: Option<Box<Variant>>,
+}
+
+impl Maybe {
+    /// Build an empty ("nothing") value of `inner_type`.
+    pub fn nothing(inner_type: VariantType) -> Self {
+        Self {
+            inner_type,
+            value: None,
+        }
+    }
+
+    /// Build a non-empty ("just") value, typed after its content.
+    pub fn just(value: Variant) -> Self {
+        Self {
+            inner_type: value.variant_type(),
+            value: Some(Box::new(value)),
+        }
+    }
+
+    /// Build an optional value of `inner_type`, checking its content type.
+    pub fn with_type(inner_type: VariantType, value: Option<Variant>) -> errors::Result<Self> {
+        if let Some(ref v) = value {
+            check_type(&inner_type, v)?;
+        }
+        let maybe = Self {
+            inner_type,
+            value: value.map(Box::new),
+        };
+        Ok(maybe)
+    }
+
+    /// Return the type of the inner value.
+    pub fn inner_type(&self) -> &VariantType {
+        &self.inner_type
+    }
+
+    /// Return the inner value, if any.
+    pub fn value(&self) -> Option<&Variant> {
+        self.value.as_deref()
+    }
+
+    /// Transform self into the inner value, if any.
+    pub fn into_value(self) -> Option<Variant> {
+        self.value.map(|v| *v)
+    }
+
+    /// Transform self into a `Variant`.
+    pub fn into_variant(self) -> Variant {
+        Variant::Option(self)
+    }
+
+    /// Return type signature.
+    pub fn signature(&self) -> String {
+        format!("m{}", self.inner_type)
+    }
+}
+
+/// GVariant structure, variadic tuple.
+#[derive(Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct Structure {
+    /// Structure fields.
+    fields: Vec<Variant>,
+}
+
+impl Structure {
+    /// Return type signature.
+    pub fn signature(&self) -> String {
+        let mut inner = String::new();
+        for field in &self.fields {
+            inner += &field.signature();
+        }
+        format!("({})", inner)
+    }
+}
+
+/// All the types supported by GVariant (basic or containers).
+#[derive(Clone, Debug)]
+pub enum Variant {
+    /// Boolean (signature: `b`).
+    Bool(bool),
+    /// Byte (signature: `y`).
+    U8(u8),
+    /// Unsigned 16-bits integer (signature: `q`).
+    U16(u16),
+    /// Unsigned 32-bits integer (signature: `u`).
+    U32(u32),
+    /// Unsigned 64-bits integer (signature: `t`).
+    U64(u64),
+    /// Signed 16-bits integer (signature: `n`).
+    I16(i16),
+    /// Signed 32-bits integer (signature: `i`).
+    I32(i32),
+    /// Signed 64-bits integer (signature: `x`).
+    I64(i64),
+    /// Double-precision floating point number (signature: `d`).
+    F64(f64),
+    /// String (signature: `s`).
+    String(String),
+    /// DBus object path (signature: `o`).
+    ObjectPath(String),
+    /// DBus signature string (signature: `g`).
+    Signature(String),
+    /// Index of a file descriptor passed alongside (signature: `h`).
+    Handle(Handle),
+    /// Generic variant type (signature: `v`).
+    Variant(Box<Variant>),
+    /// Optional ("Maybe") container (signature: `mX`).
+    Option(Maybe),
+    /// Homogeneous array (signature: `aX`).
+    Vec(Array),
+    /// Structure, variadic tuple (signature: `(XYZ)`).
+    Structure(Structure),
+    /// Dictionary map (signature: `a{XY}`).
+    Dictionary(Dictionary),
+}
+
+impl Variant {
+    /// Return inner type signature.
+    pub fn signature(&self) -> String {
+        self.variant_type().to_string()
+    }
+
+    /// Return inner type.
+    pub fn variant_type(&self) -> VariantType {
+        match *self {
+            Variant::Bool(..) => VariantType::Bool,
+            Variant::U8(..) => VariantType::U8,
+            Variant::U16(..) => VariantType::U16,
+            Variant::U32(..) => VariantType::U32,
+            Variant::U64(..) => VariantType::U64,
+            Variant::I16(..) => VariantType::I16,
+            Variant::I32(..) => VariantType::I32,
+            Variant::I64(..) => VariantType::I64,
+            Variant::F64(..) => VariantType::F64,
+            Variant::String(..) => VariantType::String,
+            Variant::ObjectPath(..) => VariantType::ObjectPath,
+            Variant::Signature(..) => VariantType::Signature,
+            Variant::Handle(..) => VariantType::Handle,
+            Variant::Variant(..) => VariantType::Variant,
+            Variant::Option(ref v) => VariantType::Option(Box::new(v.inner_type.clone())),
+            Variant::Vec(ref v) => VariantType::Vec(Box::new(v.elem_type.clone())),
+            Variant::Structure(ref v) => {
+                VariantType::Structure(v.fields.iter().map(Variant::variant_type).collect())
+            }
+            Variant::Dictionary(ref v) => {
+                let entry = VariantType::DictEntry(
+                    Box::new(v.key_type.clone()),
+                    Box::new(v.value_type.clone()),
+                );
+                VariantType::Vec(Box::new(entry))
+            }
+        }
+    }
+
+    /// Return the changes turning this value into `other`, in document order.
+    ///
+    /// Containers of the same type are compared child by child, down to the
+    /// values which differ: see `Change` for how they are located.
+    pub fn diff(&self, other: &Variant) -> Vec<Change> {
+        let mut changes = Vec::new();
+        diff::diff("", self, other, &mut changes);
+        changes
+    }
+
+    /// Return the values within this one matching `path`, in document order.
+    ///
+    /// Each segment of the path, either after a `/` or within `[...]`, selects
+    /// structure fields and array elements by index, dictionary values by key
+    /// (possibly quoted, with `\` escapes), or all children with `*`. A `v` or `m` is unwrapped
+    /// on the way, and at the end: e.g. `/0/ostree.ref-binding/*` or
+    /// `metadata['version']`.
+    pub fn query(&self, path: &str) -> errors::Result<Vec<&Variant>> {
+        query::query(self, path)
+    }
+
+    /// Edit the values within this one matching `path` with `edit`, in place.
+    ///
+    /// See `query` for the path syntax. Return the number of edited values.
+    /// Edited values must keep their type where their container fixes it, so
+    /// that containers stay valid: array elements, dictionary values and `m`
+    /// values must, as must the fields of structures within them. Values in
+    /// a `v` (e.g. `a{sv}` dictionary values) may take any type. Either all
+    /// edits are applied, or none and an error is returned.
+    pub fn query_mut<F>(&mut self, path: &str, edit: F) -> errors::Result<usize>
+    where
+        F: FnMut(&mut Variant),
+    {
+        query::query_mut(self, path, edit)
+    }
+
+    /// Canonicalize this value in place, so that equal values encode the same.
+    ///
+    /// Floating point zeros and NaNs compare, order and hash alike, whatever
+    /// their sign or payload: they are given a single representation.
+    /// Dictionaries are always kept sorted by key.
+    pub fn normalize(&mut self) {
+        match *self {
+            Variant::F64(ref mut v) if v.is_nan() => *v = f64::NAN,
+            Variant::F64(ref mut v) if *v == 0.0 => *v = 0.0,
+            Variant::Variant(ref mut inner) => inner.normalize(),
+            Variant::Option(ref mut maybe) => {
+                if let Some(ref mut inner) = maybe.value {
+                    inner.normalize();
+                }
+            }
+            Variant::Vec(ref mut array) => array.inner.iter_mut().for_each(Variant::normalize),
+            Variant::Structure(ref mut s) => s.fields.iter_mut().for_each(Variant::normalize),
+            Variant::Dictionary(ref mut dict) => {
+                let inner = core::mem::take(&mut dict.inner);
+                dict.inner = inner
+                    .into_iter()
+                    .map(|(mut k, mut v)| {
+                        k.normalize();
+                        v.normalize();
+                        (k, v)
+                    })
+                    .collect();
+            }
+            _ => {}
+        }
+    }
+
+    pub(crate) fn discriminant(&self) -> u64 {
+        match *self {
+            Variant::Bool(..) => 0,
+            Variant::U8(..) => 1,
+            Variant::U16(..) => 2,
+            Variant::U32(..) => 3,
+            Variant::U64(..) => 4,
+            Variant::I16(..) => 5,
+            Variant::I32(..) => 6,
+            Variant::I64(..) => 7,
+            Variant::F64(..) => 8,
+            Variant::String(..) => 9,
+            Variant::ObjectPath(..) => 10,
+            Variant::Signature(..) => 11,
+            Variant::Variant(..) => 12,
+            Variant::Option(..) => 13,
+            Variant::Vec(..) => 14,
+            Variant::Structure(..) => 15,
+            Variant::Dictionary(..) => 16,
+            Variant::Handle(..) => 17,
+        }
+    }
+}
+
+impl hash::Hash for Variant {
+    fn hash<H>(&self, hasher: &mut H)
+    where
+        H: hash::Hasher,
+    {
+        self.discriminant().hash(hasher);
+        match *self {
+            Variant::Bool(v) => v.hash(hasher),
+            Variant::U8(v) => v.hash(hasher),
+            Variant::U16(v) => v.hash(hasher),
+            Variant::U32(v) => v.hash(hasher),
+            Variant::U64(v) => v.hash(hasher),
+            Variant::I16(v) => v.hash(hasher),
+            Variant::I32(v) => v.hash(hasher),
+            Variant::I64(v) => v.hash(hasher),
+            Variant::F64(v) => OrderedFloat(v).hash(hasher),
+            Variant::String(ref v) => v.hash(hasher),
+            Variant::ObjectPath(ref v) => v.hash(hasher),
+            Variant::Signature(ref v) => v.hash(hasher),
+            Variant::Handle(v) => v.hash(hasher),
+            Variant::Variant(ref v) => v.hash(hasher),
+            Variant::Option(ref v) => v.hash(hasher),
+            Variant::Vec(ref v) => v.hash(hasher),
+            Variant::Structure(ref v) => v.hash(hasher),
+            Variant::Dictionary(ref v) => v.hash(hasher),
+        }
+    }
+}
+
+impl PartialEq for Variant {
+    fn eq(&self, rhs: &Self) -> bool {
+        match (self, rhs) {
+            (&Variant::Bool(v0), &Variant::Bool(v1)) if v0 == v1 => true,
+            (&Variant::U8(v0), &Variant::U8(v1)) if v0 == v1 => true,
+            (&Variant::U16(v0), &Variant::U16(v1)) if v0 == v1 => true,
+            (&Variant::U32(v0), &Variant::U32(v1)) if v0 == v1 => true,
+            (&Variant::U64(v0), &Variant::U64(v1)) if v0 == v1 => true,
+            (&Variant::I16(v0), &Variant::I16(v1)) if v0 == v1 => true,
+            (&Variant::I32(v0), &Variant::I32(v1)) if v0 == v1 => true,
+            (&Variant::I64(v0), &Variant::I64(v1)) if v0 == v1 => true,
+            (&Variant::F64(v0), &Variant::F64(v1)) => OrderedFloat(v0) == OrderedFloat(v1),
+            (Variant::String(v0), Variant::String(v1)) if v0 == v1 => true,
+            (Variant::ObjectPath(v0), Variant::ObjectPath(v1)) if v0 == v1 => true,
+            (Variant::Signature(v0), Variant::Signature(v1)) if v0 == v1 => true,
+            (&Variant::Handle(v0), &Variant::Handle(v1)) if v0 == v1 => true,
+            (Variant::Variant(v0), Variant::Variant(v1)) if v0 == v1 => true,
+            (Variant::Option(v0), Variant::Option(v1)) if v0 == v1 => true,
+            (Variant::Vec(v0), Variant::Vec(v1)) if v0 == v1 => true,
+            (Variant::Structure(v0), Variant::Structure(v1)) if v0 == v1 => true,
+            (Variant::Dictionary(v0), Variant::Dictionary(v1)) if v0 == v1 => true,
+            _ => false,
+        }
+    }
+}
+
+impl Ord for Variant {
+    fn cmp(&self, rhs: &Self) -> cmp::Ordering {
+        match (self, rhs) {
+            (&Variant::Bool(v0), Variant::Bool(v1)) => v0.cmp(v1),
+            (&Variant::U8(v0), Variant::U8(v1)) => v0.cmp(v1),
+            (&Variant::U16(v0), Variant::U16(v1)) => v0.cmp(v1),
+            (&Variant::U32(v0), Variant::U32(v1)) => v0.cmp(v1),
+            (&Variant::U64(v0), Variant::U64(v1)) => v0.cmp(v1),
+            (&Variant::I16(v0), Variant::I16(v1)) => v0.cmp(v1),
+            (&Variant::I32(v0), Variant::I32(v1)) => v0.cmp(v1),
+            (&Variant::I64(v0), Variant::I64(v1)) => v0.cmp(v1),
+            (&Variant::F64(v0), &Variant::F64(v1)) => OrderedFloat(v0).cmp(&OrderedFloat(v1)),
+            (Variant::String(v0), Variant::String(v1)) => v0.cmp(v1),
+            (Variant::ObjectPath(v0), Variant::ObjectPath(v1)) => v0.cmp(v1),
+            (Variant::Signature(v0), Variant::Signature(v1)) => v0.cmp(v1),
+            (&Variant::Handle(v0), Variant::Handle(v1)) => v0.cmp(v1),
+            (Variant::Variant(v0), Variant::Variant(v1)) => v0.cmp(v1),
+            (Variant::Option(v0), Variant::Option(v1)) => v0.cmp(v1),
+            (Variant::Vec(v0), Variant::Vec(v1)) => v0.cmp(v1),
+            (Variant::Structure(v0), Variant::Structure(v1)) => v0.cmp(v1),
+            (Variant::Dictionary(v0), Variant::Dictionary(v1)) => v0.cmp(v1),
+            (v0, v1) => v0.discriminant().cmp(&v1.discriminant()),
+        }
+    }
+}
+
+impl Eq for Variant {}
+impl PartialOrd for Variant {
+    fn partial_cmp(&self, rhs: &Self) -> Option<cmp::Ordering> {
+        Some(self.cmp(rhs))
+    }
+}
+
+/// Renders values in GVariant text format, without type annotations.
+impl fmt::Display for Variant {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fn list<'a, I>(f: &mut fmt::Formatter, open: &str, items: I, close: &str) -> fmt::Result
+        where
+            I: Iterator<Item = &'a Variant>,
+        {
+            f.write_str(open)?;
+            for (i, item) in items.enumerate() {
+                if i > 0 {
+                    f.write_str(", ")?;
+                }
+                write!(f, "{}", item)?;
+            }
+            f.write_str(close)
+        }
+
+        fn quoted(f: &mut fmt::Formatter, s: &str) -> fmt::Result {
+            f.write_str("'")?;
+            for c in s.chars() {
+                match c {
+                    '\'' | '\\' => write!(f, "\\{}", c)?,
+                    c if c.is_control() => write!(f, "{}", c.escape_default())?,
+                    c => write!(f, "{}", c)?,
+                }
+            }
+            f.write_str("'")
+        }
+
+        match *self {
+            Variant::Bool(v) => write!(f, "{}", v),
+            Variant::U8(v) => write!(f, "{:#04x}", v),
+            Variant::U16(v) => write!(f, "{}", v),
+            Variant::U32(v) => write!(f, "{}", v),
+            Variant::U64(v) => write!(f, "{}", v),
+            Variant::I16(v) => write!(f, "{}", v),
+            Variant::I32(v) => write!(f, "{}", v),
+            Variant::I64(v) => write!(f, "{}", v),
+            Variant::F64(v) => write!(f, "{:?}", v),
+            Variant::String(ref v) => quoted(f, v),
+            Variant::ObjectPath(ref v) => {
+                f.write_str("objectpath ")?;
+                quoted(f, v)
+            }
+            Variant::Signature(ref v) => {
+                f.write_str("signature ")?;
+                quoted(f, v)
+            }
+            Variant::Handle(v) => write!(f, "handle {}", v.0),
+            Variant::Variant(ref v) => write!(f, "<{}>", v),
+            Variant::Option(ref v) => match v.value {
+                Some(ref inner) => write!(f, "just {}", inner),
+                None => f.write_str("nothing"),
+            },
+            Variant::Vec(ref v) => list(f, "[", v.inner.iter(), "]"),
+            Variant::Structure(ref v) if v.fields.len() == 1 => write!(f, "({},)", v.fields[0]),
+            Variant::Structure(ref v) => list(f, "(", v.fields.iter(), ")"),
+            Variant::Dictionary(ref v) => {
+                f.write_str("{")?;
+                for (i, (key, value)) in v.inner.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(", ")?;
+                    }
+                    write!(f, "{}: {}", key, value)?;
+                }
+                f.write_str("}")
+            }
+        }
+    }
+}
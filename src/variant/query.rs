@@ -0,0 +1,269 @@
+//! Path-based queries over `Variant` trees.
+
+use super::{check_type, Variant};
+use crate::errors;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::cmp::Ordering;
+
+/// A step of a query path.
+#[derive(Debug, PartialEq)]
+enum Segment {
+    /// A structure field or array element by index, or a dictionary value by key.
+    Key(String),
+    /// All children.
+    Any,
+}
+
+/// Parse a query path.
+///
+/// Segments are either separated by `/`, or written as `[...]`, with keys
+/// possibly quoted: `/0/ostree.ref-binding/*` and `[0]['ostree.ref-binding'][*]`
+/// are the same query. Within quotes, `\` escapes the next character.
+fn parse(path: &str) -> errors::Result<Vec<Segment>> {
+    let segment = |token: &str| match token {
+        "*" => Segment::Any,
+        key => Segment::Key(key.into()),
+    };
+    let mut segments = Vec::new();
+    let mut rest = path;
+    while let Some(c) = rest.chars().next() {
+        match c {
+            '/' => rest = &rest[1..],
+            '[' => {
+                let inner = &rest[1..];
+                match inner.chars().next() {
+                    Some(quote @ '\'') | Some(quote @ '"') => {
+                        let mut key = String::new();
+                        let mut chars = inner.char_indices().skip(1);
+                        let end = loop {
+                            match chars.next() {
+                                Some((end, c)) if c == quote => break end,
+                                Some((_, '\\')) => match chars.next() {
+                                    Some((_, c)) => key.push(c),
+                                    None => bail!("query: unterminated quote in '{}'", path),
+                                },
+                                Some((_, c)) => key.push(c),
+                                None => bail!("query: unterminated quote in '{}'", path),
+                            }
+                        };
+                        segments.push(Segment::Key(key));
+                        rest = &inner[end + 1..];
+                        if !rest.starts_with(']') {
+                            bail!("query: expected ']' after key in '{}'", path);
+                        }
+                        rest = &rest[1..];
+                    }
+                    _ => {
+                        let end = match inner.find(']') {
+                            Some(end) => end,
+                            None => bail!("query: unterminated '[' in '{}'", path),
+                        };
+                        segments.push(segment(&inner[..end]));
+                        rest = &inner[end + 1..];
+                    }
+                }
+            }
+            _ => {
+                let end = rest.find(['/', '[']).unwrap_or(rest.len());
+                segments.push(segment(&rest[..end]));
+                rest = &rest[end..];
+            }
+        }
+    }
+    Ok(segments)
+}
+
+/// Return the value of an integer (or handle) dictionary key.
+fn number(key: &Variant) -> Option<i128> {
+    match *key {
+        Variant::U8(v) => Some(i128::from(v)),
+        Variant::U16(v) => Some(i128::from(v)),
+        Variant::U32(v) => Some(i128::from(v)),
+        Variant::U64(v) => Some(i128::from(v)),
+        Variant::I16(v) => Some(i128::from(v)),
+        Variant::I32(v) => Some(i128::from(v)),
+        Variant::I64(v) => Some(i128::from(v)),
+        Variant::Handle(v) => Some(i128::from(v.0)),
+        _ => None,
+    }
+}
+
+/// Return whether dictionary `key` is the one written as `text`.
+fn key_matches(key: &Variant, text: &str) -> bool {
+    match (number(key), key) {
+        (Some(number), _) => text.parse() == Ok(number),
+        (None, Variant::Bool(v)) => text == if *v { "true" } else { "false" },
+        // Compared as ordered, so that NaNs match.
+        (None, Variant::F64(_)) => text
+            .parse()
+            .is_ok_and(|v| Variant::F64(v).cmp(key) == Ordering::Equal),
+        (None, key) => key.as_str() == Some(text),
+    }
+}
+
+/// Write dictionary `key` as a path segment, matching it in queries.
+///
+/// String-like keys are quoted, e.g. `['ostree.ref-binding']`.
+pub(super) fn key_segment(key: &Variant) -> String {
+    match (key.as_str(), number(key)) {
+        (Some(name), _) => {
+            let mut segment = String::from("['");
+            for c in name.chars() {
+                if c == '\\' || c == '\'' {
+                    segment.push('\\');
+                }
+                segment.push(c);
+            }
+            segment + "']"
+        }
+        (None, Some(number)) => format!("[{}]", number),
+        (None, None) => format!("[{}]", key),
+    }
+}
+
+/// Unwrap `v` and `m` containers, down to a value if any.
+fn unwrap(value: &Variant) -> Option<&Variant> {
+    match *value {
+        Variant::Variant(ref inner) => unwrap(inner),
+        Variant::Option(ref maybe) => maybe.value.as_deref().and_then(unwrap),
+        ref value => Some(value),
+    }
+}
+
+/// Unwrap `v` and `m` containers, down to a value if any, along with
+/// whether its type is fixed by its container.
+///
+/// A `v` lets its value take any type, while a `m` fixes it.
+fn unwrap_mut(value: &mut Variant, fixed: bool) -> Option<(&mut Variant, bool)> {
+    match *value {
+        Variant::Variant(ref mut inner) => unwrap_mut(inner, false),
+        Variant::Option(ref mut maybe) => maybe
+            .value
+            .as_deref_mut()
+            .and_then(|value| unwrap_mut(value, true)),
+        ref mut value => Some((value, fixed)),
+    }
+}
+
+fn select<'a>(value: &'a Variant, segments: &[Segment], matches: &mut Vec<&'a Variant>) {
+    let value = match unwrap(value) {
+        Some(value) => value,
+        None => return,
+    };
+    let (segment, rest) = match segments.split_first() {
+        Some(split) => split,
+        None => return matches.push(value),
+    };
+    match (value, segment) {
+        (Variant::Structure(s), Segment::Any) => {
+            s.fields.iter().for_each(|v| select(v, rest, matches))
+        }
+        (Variant::Vec(a), Segment::Any) => a.inner.iter().for_each(|v| select(v, rest, matches)),
+        (Variant::Dictionary(d), Segment::Any) => {
+            d.inner.values().for_each(|v| select(v, rest, matches))
+        }
+        (Variant::Structure(s), Segment::Key(k)) => {
+            if let Some(v) = k.parse().ok().and_then(|i: usize| s.fields.get(i)) {
+                select(v, rest, matches)
+            }
+        }
+        (Variant::Vec(a), Segment::Key(k)) => {
+            if let Some(v) = k.parse().ok().and_then(|i: usize| a.inner.get(i)) {
+                select(v, rest, matches)
+            }
+        }
+        (Variant::Dictionary(d), Segment::Key(k)) => {
+            if let Some((_, v)) = d.inner.iter().find(|(key, _)| key_matches(key, k)) {
+                select(v, rest, matches)
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Like `select`, also telling whether the type of each match is fixed.
+///
+/// Array elements and dictionary values have their type fixed, as do the
+/// fields of a structure whose own type is.
+fn select_mut<'a>(
+    value: &'a mut Variant,
+    fixed: bool,
+    segments: &[Segment],
+    matches: &mut Vec<(&'a mut Variant, bool)>,
+) {
+    let (value, fixed) = match unwrap_mut(value, fixed) {
+        Some(unwrapped) => unwrapped,
+        None => return,
+    };
+    let (segment, rest) = match segments.split_first() {
+        Some(split) => split,
+        None => return matches.push((value, fixed)),
+    };
+    match (value, segment) {
+        (Variant::Structure(s), Segment::Any) => s
+            .fields
+            .iter_mut()
+            .for_each(|v| select_mut(v, fixed, rest, matches)),
+        (Variant::Vec(a), Segment::Any) => a
+            .inner
+            .iter_mut()
+            .for_each(|v| select_mut(v, true, rest, matches)),
+        (Variant::Dictionary(d), Segment::Any) => d
+            .inner
+            .values_mut()
+            .for_each(|v| select_mut(v, true, rest, matches)),
+        (Variant::Structure(s), Segment::Key(k)) => {
+            if let Some(v) = k.parse().ok().and_then(move |i: usize| s.fields.get_mut(i)) {
+                select_mut(v, fixed, rest, matches)
+            }
+        }
+        (Variant::Vec(a), Segment::Key(k)) => {
+            if let Some(v) = k.parse().ok().and_then(move |i: usize| a.inner.get_mut(i)) {
+                select_mut(v, true, rest, matches)
+            }
+        }
+        (Variant::Dictionary(d), Segment::Key(k)) => {
+            if let Some((_, v)) = d.inner.iter_mut().find(|(key, _)| key_matches(key, k)) {
+                select_mut(v, true, rest, matches)
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Return the values of `value` matching `path`.
+pub(crate) fn query<'a>(value: &'a Variant, path: &str) -> errors::Result<Vec<&'a Variant>> {
+    let segments = parse(path)?;
+    let mut matches = Vec::new();
+    select(value, &segments, &mut matches);
+    Ok(matches)
+}
+
+/// Edit the values of `value` matching `path` with `edit`, in place.
+///
+/// Edits are made on copies, and only applied once all of them are checked
+/// against the types fixed by their containers.
+pub(crate) fn query_mut<F>(value: &mut Variant, path: &str, mut edit: F) -> errors::Result<usize>
+where
+    F: FnMut(&mut Variant),
+{
+    let segments = parse(path)?;
+    let mut matches = Vec::new();
+    select_mut(value, false, &segments, &mut matches);
+    let mut edited = Vec::with_capacity(matches.len());
+    for (value, fixed) in &matches {
+        let mut new = (**value).clone();
+        edit(&mut new);
+        if *fixed {
+            check_type(&value.variant_type(), &new)?;
+        }
+        edited.push(new);
+    }
+    let count = matches.len();
+    for ((value, _), new) in matches.into_iter().zip(edited) {
+        *value = new;
+    }
+    Ok(count)
+}
@@ -0,0 +1,718 @@
+//! Serialization into `Variant` trees.
+//!
+//! `Variant` values serialize as an enum variant named after their arm, with
+//! a private enum name so that GVariant-aware serializers can box them as a
+//! `v` container. Other serializers see the same externally tagged form as
+//! `Deserialize` accepts, e.g. `{"U32":7}` in JSON. Arrays, dictionaries and
+//! maybes come along with their signature, e.g. `{"Vec":["as",[]]}`, so that
+//! empty ones keep their type.
+//!
+//! Serde does not convey the element type of empty sequences and maps, nor the
+//! inner type of `None`. When building a tree, those default to `v` (and to
+//! `s` for dictionary keys), unless a sibling value tells the actual type.
+
+use super::{Array, Dictionary, Maybe, Structure, Variant};
+use crate::config::{CharType, Config, I8Type};
+use crate::errors;
+use crate::handle::Handle;
+use crate::variant_type::VariantType;
+use alloc::boxed::Box;
+use alloc::collections::BTreeMap;
+use alloc::format;
+use alloc::string::ToString;
+use alloc::vec;
+use alloc::vec::Vec;
+use serde::ser::{self, Serialize};
+
+/// Enum name marking a `v` container.
+pub(crate) const VARIANT_TOKEN: &str = "$serde_gvariant::private::Variant";
+/// Enum name marking the content of a `v` container, without the boxing.
+pub(crate) const VALUE_TOKEN: &str = "$serde_gvariant::private::Value";
+/// Newtype name marking an object path (`o`).
+pub(crate) const OBJECT_PATH_TOKEN: &str = "$serde_gvariant::private::ObjectPath";
+/// Newtype name marking a signature string (`g`).
+pub(crate) const SIGNATURE_TOKEN: &str = "$serde_gvariant::private::Signature";
+/// Newtype name marking a file descriptor handle (`h`).
+pub(crate) const HANDLE_TOKEN: &str = "$serde_gvariant::private::Handle";
+/// Tuple struct name pairing a container with its signature.
+pub(crate) const TYPED_TOKEN: &str = "$serde_gvariant::private::Typed";
+
+/// Names of `Variant` arms, indexed by discriminant.
+pub(crate) const VARIANTS: &[&str] = &[
+    "Bool",
+    "U8",
+    "U16",
+    "U32",
+    "U64",
+    "I16",
+    "I32",
+    "I64",
+    "F64",
+    "String",
+    "ObjectPath",
+    "Signature",
+    "Variant",
+    "Option",
+    "Vec",
+    "Structure",
+    "Dictionary",
+    "Handle",
+];
+
+impl Serialize for Variant {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: ser::Serializer,
+    {
+        tagged(VARIANT_TOKEN, self, serializer)
+    }
+}
+
+/// Serialize `value` as the enum variant named after its arm.
+fn tagged<S>(name: &'static str, value: &Variant, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: ser::Serializer,
+{
+    let discriminant = value.discriminant() as usize;
+    serializer.serialize_newtype_variant(
+        name,
+        discriminant as u32,
+        VARIANTS[discriminant],
+        &Payload(value),
+    )
+}
+
+impl Serialize for Array {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: ser::Serializer,
+    {
+        typed(&self.signature(), &Children(self), serializer)
+    }
+}
+
+impl Serialize for Dictionary {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: ser::Serializer,
+    {
+        typed(&self.signature(), &Children(self), serializer)
+    }
+}
+
+impl Serialize for Maybe {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: ser::Serializer,
+    {
+        typed(&self.signature(), &Children(self), serializer)
+    }
+}
+
+impl Serialize for Structure {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: ser::Serializer,
+    {
+        use serde::ser::SerializeTuple;
+        if self.fields.is_empty() {
+            return serializer.serialize_unit();
+        }
+        let mut tuple = serializer.serialize_tuple(self.fields.len())?;
+        for field in &self.fields {
+            tuple.serialize_element(&Content(field))?;
+        }
+        tuple.end()
+    }
+}
+
+/// Serialize `container` along with its signature.
+///
+/// GVariant-aware serializers take the signature as the type of the
+/// container, which serde does not tell when it is empty. Other serializers
+/// see a pair, e.g. `["as",[]]` in JSON.
+fn typed<S, T>(signature: &str, container: &T, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: ser::Serializer,
+    T: Serialize,
+{
+    use serde::ser::SerializeTupleStruct;
+    let mut pair = serializer.serialize_tuple_struct(TYPED_TOKEN, 2)?;
+    pair.serialize_field(signature)?;
+    pair.serialize_field(container)?;
+    pair.end()
+}
+
+/// Children of a container, serialized without its signature.
+struct Children<'a, T>(&'a T);
+
+impl<'a> Serialize for Children<'a, Array> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: ser::Serializer,
+    {
+        use serde::ser::SerializeSeq;
+        let mut seq = serializer.serialize_seq(Some(self.0.inner.len()))?;
+        for elem in &self.0.inner {
+            seq.serialize_element(&Content(elem))?;
+        }
+        seq.end()
+    }
+}
+
+impl<'a> Serialize for Children<'a, Dictionary> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: ser::Serializer,
+    {
+        use serde::ser::SerializeMap;
+        let mut map = serializer.serialize_map(Some(self.0.inner.len()))?;
+        for (k, v) in &self.0.inner {
+            map.serialize_entry(&Content(k), &Content(v))?;
+        }
+        map.end()
+    }
+}
+
+impl<'a> Serialize for Children<'a, Maybe> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: ser::Serializer,
+    {
+        match self.0.value {
+            None => serializer.serialize_none(),
+            Some(ref v) => serializer.serialize_some(&Content(v)),
+        }
+    }
+}
+
+/// Content of a `Variant`, serialized without the `v` boxing.
+struct Content<'a>(&'a Variant);
+
+impl<'a> Serialize for Content<'a> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: ser::Serializer,
+    {
+        tagged(VALUE_TOKEN, self.0, serializer)
+    }
+}
+
+/// Value held by an arm of a `Variant`.
+struct Payload<'a>(&'a Variant);
+
+impl<'a> Serialize for Payload<'a> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: ser::Serializer,
+    {
+        match *self.0 {
+            Variant::Bool(v) => serializer.serialize_bool(v),
+            Variant::U8(v) => serializer.serialize_u8(v),
+            Variant::U16(v) => serializer.serialize_u16(v),
+            Variant::U32(v) => serializer.serialize_u32(v),
+            Variant::U64(v) => serializer.serialize_u64(v),
+            Variant::I16(v) => serializer.serialize_i16(v),
+            Variant::I32(v) => serializer.serialize_i32(v),
+            Variant::I64(v) => serializer.serialize_i64(v),
+            Variant::F64(v) => serializer.serialize_f64(v),
+            Variant::String(ref v) => serializer.serialize_str(v),
+            Variant::ObjectPath(ref v) => serializer.serialize_newtype_struct(OBJECT_PATH_TOKEN, v),
+            Variant::Signature(ref v) => serializer.serialize_newtype_struct(SIGNATURE_TOKEN, v),
+            Variant::Handle(ref v) => v.serialize(serializer),
+            Variant::Variant(ref v) => v.serialize(serializer),
+            Variant::Option(ref v) => v.serialize(serializer),
+            Variant::Vec(ref v) => v.serialize(serializer),
+            Variant::Structure(ref v) => v.serialize(serializer),
+            Variant::Dictionary(ref v) => v.serialize(serializer),
+        }
+    }
+}
+
+/// A serializer building `Variant` trees.
+#[derive(Clone, Copy)]
+pub(crate) struct VariantSerializer {
+    char_type: CharType,
+    i8_type: I8Type,
+}
+
+impl VariantSerializer {
+    pub(crate) fn new(options: &Config) -> Self {
+        Self {
+            char_type: options.char_type,
+            i8_type: options.i8_type,
+        }
+    }
+}
+
+impl ser::Serializer for VariantSerializer {
+    type Ok = Variant;
+    type Error = errors::Error;
+
+    type SerializeSeq = SerializeArray;
+    type SerializeTuple = SerializeStructure;
+    type SerializeTupleStruct = SerializeStructure;
+    type SerializeTupleVariant = ser::Impossible<Self::Ok, Self::Error>;
+    type SerializeMap = SerializeDictionary;
+    type SerializeStruct = SerializeStructure;
+    type SerializeStructVariant = ser::Impossible<Self::Ok, Self::Error>;
+
+    fn serialize_bool(self, v: bool) -> errors::Result<Variant> {
+        Ok(Variant::Bool(v))
+    }
+
+    fn serialize_i8(self, v: i8) -> errors::Result<Variant> {
+        match self.i8_type {
+            I8Type::I16 => self.serialize_i16(i16::from(v)),
+            I8Type::Reject => Err(errors::Error::unsupported("i8")),
+        }
+    }
+
+    fn serialize_i16(self, v: i16) -> errors::Result<Variant> {
+        Ok(Variant::I16(v))
+    }
+
+    fn serialize_i32(self, v: i32) -> errors::Result<Variant> {
+        Ok(Variant::I32(v))
+    }
+
+    fn serialize_i64(self, v: i64) -> errors::Result<Variant> {
+        Ok(Variant::I64(v))
+    }
+
+    fn serialize_u8(self, v: u8) -> errors::Result<Variant> {
+        Ok(Variant::U8(v))
+    }
+
+    fn serialize_u16(self, v: u16) -> errors::Result<Variant> {
+        Ok(Variant::U16(v))
+    }
+
+    fn serialize_u32(self, v: u32) -> errors::Result<Variant> {
+        Ok(Variant::U32(v))
+    }
+
+    fn serialize_u64(self, v: u64) -> errors::Result<Variant> {
+        Ok(Variant::U64(v))
+    }
+
+    fn serialize_f32(self, v: f32) -> errors::Result<Variant> {
+        // Internally promote to f64.
+        Ok(Variant::F64(f64::from(v)))
+    }
+
+    fn serialize_f64(self, v: f64) -> errors::Result<Variant> {
+        Ok(Variant::F64(v))
+    }
+
+    fn serialize_char(self, v: char) -> errors::Result<Variant> {
+        match self.char_type {
+            CharType::U32 => self.serialize_u32(u32::from(v)),
+            CharType::String => self.serialize_str(v.encode_utf8(&mut [0; 4])),
+        }
+    }
+
+    fn serialize_str(self, v: &str) -> errors::Result<Variant> {
+        Ok(Variant::String(v.to_string()))
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> errors::Result<Variant> {
+        let inner = v.iter().map(|b| Variant::U8(*b)).collect();
+        Ok(Variant::Vec(Array {
+            elem_type: VariantType::U8,
+            inner,
+        }))
+    }
+
+    fn serialize_none(self) -> errors::Result<Variant> {
+        Ok(Variant::Option(Maybe::nothing(VariantType::Variant)))
+    }
+
+    fn serialize_some<T>(self, value: &T) -> errors::Result<Variant>
+    where
+        T: ?Sized + Serialize,
+    {
+        let inner = value.serialize(self)?;
+        Ok(Variant::Option(Maybe::just(inner)))
+    }
+
+    fn serialize_unit(self) -> errors::Result<Variant> {
+        Ok(Variant::Structure(Structure { fields: vec![] }))
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> errors::Result<Variant> {
+        self.serialize_unit()
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+    ) -> errors::Result<Variant> {
+        Err(errors::Error::unsupported("unit variant"))
+    }
+
+    fn serialize_newtype_struct<T>(self, name: &'static str, value: &T) -> errors::Result<Variant>
+    where
+        T: ?Sized + Serialize,
+    {
+        let inner = value.serialize(self)?;
+        match name {
+            OBJECT_PATH_TOKEN => match inner {
+                Variant::String(s) => Ok(Variant::ObjectPath(s)),
+                _ => Err(errors::Error::mismatch("s", inner.signature())),
+            },
+            SIGNATURE_TOKEN => match inner {
+                Variant::String(s) => Ok(Variant::Signature(s)),
+                _ => Err(errors::Error::mismatch("s", inner.signature())),
+            },
+            HANDLE_TOKEN => match inner {
+                Variant::I32(i) => Ok(Variant::Handle(Handle(i))),
+                _ => Err(errors::Error::mismatch("i", inner.signature())),
+            },
+            _ => Ok(inner),
+        }
+    }
+
+    fn serialize_newtype_variant<T>(
+        self,
+        name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        value: &T,
+    ) -> errors::Result<Variant>
+    where
+        T: ?Sized + Serialize,
+    {
+        match name {
+            VARIANT_TOKEN => Ok(Variant::Variant(Box::new(value.serialize(self)?))),
+            VALUE_TOKEN => value.serialize(self),
+            _ => Err(errors::Error::unsupported("newtype variant")),
+        }
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> errors::Result<Self::SerializeSeq> {
+        let elements = Vec::with_capacity(len.unwrap_or(0));
+        Ok(SerializeArray {
+            ser: self,
+            elements,
+        })
+    }
+
+    fn serialize_tuple(self, len: usize) -> errors::Result<Self::SerializeTuple> {
+        let fields = Vec::with_capacity(len);
+        Ok(SerializeStructure {
+            ser: self,
+            fields,
+            typed: false,
+            skipped: None,
+        })
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        name: &'static str,
+        len: usize,
+    ) -> errors::Result<Self::SerializeTupleStruct> {
+        let mut tuple = self.serialize_tuple(len)?;
+        tuple.typed = name == TYPED_TOKEN;
+        Ok(tuple)
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> errors::Result<Self::SerializeTupleVariant> {
+        Err(errors::Error::unsupported("tuple variant"))
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> errors::Result<Self::SerializeMap> {
+        Ok(SerializeDictionary {
+            ser: self,
+            entries: BTreeMap::new(),
+            next_key: None,
+        })
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> errors::Result<Self::SerializeStruct> {
+        self.serialize_tuple(len)
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> errors::Result<Self::SerializeStructVariant> {
+        Err(errors::Error::unsupported("struct variant"))
+    }
+}
+
+pub(crate) struct SerializeArray {
+    ser: VariantSerializer,
+    elements: Vec<Variant>,
+}
+
+impl ser::SerializeSeq for SerializeArray {
+    type Ok = Variant;
+    type Error = errors::Error;
+
+    fn serialize_element<T>(&mut self, value: &T) -> errors::Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        let index = self.elements.len();
+        let elem = value.serialize(self.ser).map_err(|e| e.index(index))?;
+        self.elements.push(elem);
+        Ok(())
+    }
+
+    fn end(self) -> errors::Result<Variant> {
+        let elem_type = self
+            .elements
+            .iter()
+            .map(Variant::variant_type)
+            .reduce(merge)
+            .unwrap_or(VariantType::Variant);
+        let inner = self
+            .elements
+            .into_iter()
+            .map(|elem| retype(elem, &elem_type))
+            .collect::<errors::Result<_>>()?;
+        Ok(Variant::Vec(Array { elem_type, inner }))
+    }
+}
+
+pub(crate) struct SerializeStructure {
+    ser: VariantSerializer,
+    fields: Vec<Variant>,
+    /// Whether this is a container paired with its signature.
+    typed: bool,
+    /// First field left out, which only trailing fields may be.
+    skipped: Option<&'static str>,
+}
+
+impl ser::SerializeTuple for SerializeStructure {
+    type Ok = Variant;
+    type Error = errors::Error;
+
+    fn serialize_element<T>(&mut self, value: &T) -> errors::Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        let index = self.fields.len();
+        let field = value.serialize(self.ser).map_err(|e| e.index(index))?;
+        self.fields.push(field);
+        Ok(())
+    }
+
+    fn end(self) -> errors::Result<Variant> {
+        Ok(Variant::Structure(Structure {
+            fields: self.fields,
+        }))
+    }
+}
+
+impl ser::SerializeTupleStruct for SerializeStructure {
+    type Ok = Variant;
+    type Error = errors::Error;
+
+    fn serialize_field<T>(&mut self, value: &T) -> errors::Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        ser::SerializeTuple::serialize_element(self, value)
+    }
+
+    fn end(mut self) -> errors::Result<Variant> {
+        if !self.typed {
+            return ser::SerializeTuple::end(self);
+        }
+        match (self.fields.pop(), self.fields.pop()) {
+            (Some(container), Some(Variant::String(signature))) => {
+                retype(container, &VariantType::parse(&signature)?)
+            }
+            _ => Err(errors::Error::from("variant: container without signature")),
+        }
+    }
+}
+
+impl ser::SerializeStruct for SerializeStructure {
+    type Ok = Variant;
+    type Error = errors::Error;
+
+    fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> errors::Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        if let Some(skipped) = self.skipped {
+            let msg = format!("field '{}' skipped before '{}'", skipped, key);
+            return Err(errors::Error::unsupported(msg));
+        }
+        let field = value.serialize(self.ser).map_err(|e| e.field(key))?;
+        self.fields.push(field);
+        Ok(())
+    }
+
+    fn skip_field(&mut self, key: &'static str) -> errors::Result<()> {
+        self.skipped = self.skipped.or(Some(key));
+        Ok(())
+    }
+
+    fn end(self) -> errors::Result<Variant> {
+        ser::SerializeTuple::end(self)
+    }
+}
+
+pub(crate) struct SerializeDictionary {
+    ser: VariantSerializer,
+    entries: BTreeMap<Variant, Variant>,
+    next_key: Option<Variant>,
+}
+
+impl ser::SerializeMap for SerializeDictionary {
+    type Ok = Variant;
+    type Error = errors::Error;
+
+    fn serialize_key<T>(&mut self, key: &T) -> errors::Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.next_key = Some(key.serialize(self.ser)?);
+        Ok(())
+    }
+
+    fn serialize_value<T>(&mut self, value: &T) -> errors::Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        let key = self
+            .next_key
+            .take()
+            .ok_or_else(|| errors::Error::from("variant: dictionary value without key"))?;
+        let value = value.serialize(self.ser).map_err(|e| match key.as_str() {
+            Some(name) => e.field(name),
+            None => e.index(self.entries.len()),
+        })?;
+        self.entries.insert(key, value);
+        Ok(())
+    }
+
+    fn end(self) -> errors::Result<Variant> {
+        let key_type = match self.entries.keys().next() {
+            Some(k) => k.variant_type(),
+            None => VariantType::String,
+        };
+        let value_type = self
+            .entries
+            .values()
+            .map(Variant::variant_type)
+            .reduce(merge)
+            .unwrap_or(VariantType::Variant);
+
+        // Heterogeneous values (e.g. from `#[serde(flatten)]`) are boxed, as in `a{sv}`.
+        let uniform = self.entries.values().all(|v| {
+            let ty = v.variant_type();
+            merge(value_type.clone(), ty.clone()) == merge(ty, value_type.clone())
+        });
+        if !uniform {
+            let entries = self
+                .entries
+                .into_iter()
+                .map(|(k, v)| (k, Variant::Variant(Box::new(v))))
+                .collect();
+            let dict = Dictionary::with_types(key_type, VariantType::Variant, entries)?;
+            return Ok(Variant::Dictionary(dict));
+        }
+
+        let entries = self
+            .entries
+            .into_iter()
+            .map(|(k, v)| Ok((k, retype(v, &value_type)?)))
+            .collect::<errors::Result<_>>()?;
+        let dict = Dictionary::with_types(key_type, value_type, entries)?;
+        Ok(Variant::Dictionary(dict))
+    }
+}
+
+/// Merge the types of two sibling values.
+///
+/// Where they differ, the fallback types of empty containers give way to the
+/// more specific one; any other conflict is left to `retype` to report.
+pub(crate) fn merge(a: VariantType, b: VariantType) -> VariantType {
+    match (a, b) {
+        (a, b) if a == b => a,
+        (VariantType::Variant, b) => b,
+        (VariantType::Vec(a), VariantType::Vec(b)) => VariantType::Vec(Box::new(merge(*a, *b))),
+        (VariantType::Option(a), VariantType::Option(b)) => {
+            VariantType::Option(Box::new(merge(*a, *b)))
+        }
+        (VariantType::Structure(a), VariantType::Structure(b)) if a.len() == b.len() => {
+            VariantType::Structure(a.into_iter().zip(b).map(|(a, b)| merge(a, b)).collect())
+        }
+        (VariantType::DictEntry(ka, va), VariantType::DictEntry(kb, vb)) => {
+            let key = if *ka == VariantType::String { kb } else { ka };
+            VariantType::DictEntry(key, Box::new(merge(*va, *vb)))
+        }
+        (a, _) => a,
+    }
+}
+
+/// Convert `value` to type `ty`, by fixing the type of empty containers.
+pub(crate) fn retype(value: Variant, ty: &VariantType) -> errors::Result<Variant> {
+    let actual = value.variant_type();
+    if actual == *ty {
+        return Ok(value);
+    }
+    let mismatch = || errors::Error::mismatch(ty, &actual);
+
+    match (value, ty) {
+        (Variant::Vec(array), VariantType::Vec(elem)) => {
+            let inner = array
+                .inner
+                .into_iter()
+                .map(|v| retype(v, elem))
+                .collect::<errors::Result<_>>()?;
+            Ok(Variant::Vec(Array::with_type((**elem).clone(), inner)?))
+        }
+        (Variant::Dictionary(dict), VariantType::Vec(entry)) => match **entry {
+            VariantType::DictEntry(ref k, ref v) if dict.inner.is_empty() => {
+                let dict = Dictionary::with_types((**k).clone(), (**v).clone(), BTreeMap::new())?;
+                Ok(Variant::Dictionary(dict))
+            }
+            VariantType::DictEntry(ref k, ref v) if **k == dict.key_type => {
+                let inner = dict
+                    .inner
+                    .into_iter()
+                    .map(|(key, value)| Ok((key, retype(value, v)?)))
+                    .collect::<errors::Result<_>>()?;
+                let dict = Dictionary::with_types((**k).clone(), (**v).clone(), inner)?;
+                Ok(Variant::Dictionary(dict))
+            }
+            _ => Err(mismatch()),
+        },
+        (Variant::Option(maybe), VariantType::Option(inner)) => {
+            let value = maybe.into_value().map(|v| retype(v, inner)).transpose()?;
+            Ok(Variant::Option(Maybe::with_type((**inner).clone(), value)?))
+        }
+        (Variant::Structure(s), VariantType::Structure(types)) if s.fields.len() == types.len() => {
+            let fields = s
+                .fields
+                .into_iter()
+                .zip(types)
+                .map(|(v, t)| retype(v, t))
+                .collect::<errors::Result<_>>()?;
+            Ok(Variant::Structure(Structure { fields }))
+        }
+        _ => Err(mismatch()),
+    }
+}
@@ -0,0 +1,262 @@
+//! Vardict, the ubiquitous `a{sv}` dictionary.
+
+use super::{Dictionary, Variant};
+use crate::errors;
+use crate::variant_type::{StaticVariantType, VariantType};
+use alloc::boxed::Box;
+use alloc::collections::{btree_map, BTreeMap};
+use alloc::string::String;
+use core::convert::TryFrom;
+use core::fmt;
+use core::iter::FromIterator;
+use serde::de::{self, Deserialize};
+use serde::ser::{self, Serialize, SerializeMap};
+
+/// GVariant vardict, string-keyed dictionary of variants (signature: `a{sv}`).
+///
+/// Values are stored unboxed, and boxed as `v` on serialization and on
+/// conversion to a `Variant`: exactly once, even if they are `v` themselves.
+/// Entries are kept sorted by key, so that output is deterministic.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct VarDict {
+    /// Inner map.
+    inner: BTreeMap<String, Variant>,
+}
+
+impl VarDict {
+    /// Build an empty vardict.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Return the number of entries.
+    pub fn len(&self) -> usize {
+        self.inner.len()
+    }
+
+    /// Return whether the vardict has no entries.
+    pub fn is_empty(&self) -> bool {
+        self.inner.is_empty()
+    }
+
+    /// Return whether there is an entry for `key`.
+    pub fn contains_key(&self, key: &str) -> bool {
+        self.inner.contains_key(key)
+    }
+
+    /// Extract the typed value for `key`, if any.
+    ///
+    /// On a type mismatch, the error reports both the expected and the
+    /// actual signature.
+    pub fn get<'a, T>(&'a self, key: &str) -> errors::Result<Option<T>>
+    where
+        T: TryFrom<&'a Variant, Error = errors::Error>,
+    {
+        self.inner.get(key).map(Variant::get).transpose()
+    }
+
+    /// Return the value for `key`, if any.
+    pub fn get_variant(&self, key: &str) -> Option<&Variant> {
+        self.inner.get(key)
+    }
+
+    /// Insert an entry, returning the previous value for `key` if any.
+    pub fn insert<K, V>(&mut self, key: K, value: V) -> Option<Variant>
+    where
+        K: Into<String>,
+        V: Into<Variant>,
+    {
+        self.inner.insert(key.into(), value.into())
+    }
+
+    /// Remove the entry for `key`, returning its value if any.
+    pub fn remove(&mut self, key: &str) -> Option<Variant> {
+        self.inner.remove(key)
+    }
+
+    /// Iterate over the entries, sorted by key.
+    pub fn iter(&self) -> btree_map::Iter<'_, String, Variant> {
+        self.inner.iter()
+    }
+
+    /// Iterate over the keys, in sorted order.
+    pub fn keys(&self) -> btree_map::Keys<'_, String, Variant> {
+        self.inner.keys()
+    }
+
+    /// Transform self into a `Variant`.
+    pub fn into_variant(self) -> Variant {
+        Variant::from(self)
+    }
+}
+
+impl StaticVariantType for VarDict {
+    fn static_variant_type() -> VariantType {
+        BTreeMap::<String, Variant>::static_variant_type()
+    }
+}
+
+impl From<VarDict> for Variant {
+    fn from(v: VarDict) -> Self {
+        let inner = v
+            .inner
+            .into_iter()
+            .map(|(k, v)| (Variant::String(k), Variant::Variant(Box::new(v))))
+            .collect();
+        Variant::Dictionary(Dictionary {
+            key_type: VariantType::String,
+            value_type: VariantType::Variant,
+            inner,
+        })
+    }
+}
+
+impl TryFrom<Variant> for VarDict {
+    type Error = errors::Error;
+
+    fn try_from(v: Variant) -> errors::Result<Self> {
+        let dict = Dictionary::try_from(v)?;
+        if dict.signature() != "a{sv}" {
+            return Err(errors::Error::mismatch("a{sv}", dict.signature()));
+        }
+        let inner = dict
+            .inner
+            .into_iter()
+            .map(|(k, v)| {
+                let key = String::try_from(k)?;
+                let value = match v {
+                    Variant::Variant(inner) => *inner,
+                    other => other,
+                };
+                Ok((key, value))
+            })
+            .collect::<errors::Result<_>>()?;
+        Ok(Self { inner })
+    }
+}
+
+impl<'a> TryFrom<&'a Variant> for VarDict {
+    type Error = errors::Error;
+
+    fn try_from(v: &'a Variant) -> errors::Result<Self> {
+        Self::try_from(v.clone())
+    }
+}
+
+impl From<BTreeMap<String, Variant>> for VarDict {
+    fn from(v: BTreeMap<String, Variant>) -> Self {
+        Self { inner: v }
+    }
+}
+
+impl From<VarDict> for BTreeMap<String, Variant> {
+    fn from(v: VarDict) -> Self {
+        v.inner
+    }
+}
+
+impl<K, V> FromIterator<(K, V)> for VarDict
+where
+    K: Into<String>,
+    V: Into<Variant>,
+{
+    fn from_iter<I>(iter: I) -> Self
+    where
+        I: IntoIterator<Item = (K, V)>,
+    {
+        let inner = iter
+            .into_iter()
+            .map(|(k, v)| (k.into(), v.into()))
+            .collect();
+        Self { inner }
+    }
+}
+
+impl<K, V> Extend<(K, V)> for VarDict
+where
+    K: Into<String>,
+    V: Into<Variant>,
+{
+    fn extend<I>(&mut self, iter: I)
+    where
+        I: IntoIterator<Item = (K, V)>,
+    {
+        self.inner
+            .extend(iter.into_iter().map(|(k, v)| (k.into(), v.into())));
+    }
+}
+
+impl IntoIterator for VarDict {
+    type Item = (String, Variant);
+    type IntoIter = btree_map::IntoIter<String, Variant>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.inner.into_iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a VarDict {
+    type Item = (&'a String, &'a Variant);
+    type IntoIter = btree_map::Iter<'a, String, Variant>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.inner.iter()
+    }
+}
+
+impl Serialize for VarDict {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: ser::Serializer,
+    {
+        let mut map = serializer.serialize_map(Some(self.inner.len()))?;
+        for (k, v) in &self.inner {
+            // `Variant` values serialize as boxed `v`.
+            map.serialize_entry(k, v)?;
+        }
+        map.end()
+    }
+}
+
+impl<'de> Deserialize<'de> for VarDict {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        // Binary data is an array of dictionary entries, decoded as a map;
+        // other sources may still provide a sequence of pairs.
+        deserializer.deserialize_map(VarDictVisitor)
+    }
+}
+
+struct VarDictVisitor;
+
+impl<'de> de::Visitor<'de> for VarDictVisitor {
+    type Value = VarDict;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a vardict (a{sv})")
+    }
+
+    fn visit_seq<A>(self, mut seq: A) -> Result<VarDict, A::Error>
+    where
+        A: de::SeqAccess<'de>,
+    {
+        let mut inner = BTreeMap::new();
+        while let Some((k, v)) = seq.next_element::<(String, Variant)>()? {
+            inner.insert(k, v);
+        }
+        Ok(VarDict { inner })
+    }
+
+    fn visit_map<A>(self, mut map: A) -> Result<VarDict, A::Error>
+    where
+        A: de::MapAccess<'de>,
+    {
+        let mut inner = BTreeMap::new();
+        while let Some((k, v)) = map.next_entry::<String, Variant>()? {
+            inner.insert(k, v);
+        }
+        Ok(VarDict { inner })
+    }
+}
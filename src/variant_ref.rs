@@ -0,0 +1,210 @@
+//! Lazy, borrowed views over serialized values.
+
+use crate::errors;
+use crate::framing;
+use crate::variant::decode::{self, Decoder};
+use crate::variant::Variant;
+use crate::variant_type::VariantType;
+use alloc::format;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::cmp::Ordering;
+
+/// A borrowed view over a serialized value of a known type.
+///
+/// Nothing is decoded upfront: children are located on demand through
+/// framing offsets, so that accessing one element of a large container
+/// only touches the bytes on the way to it.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct VariantRef<'a> {
+    data: &'a [u8],
+    ty: VariantType,
+    network_endian: bool,
+}
+
+impl<'a> VariantRef<'a> {
+    /// Build a view over `data`, serialized as a value of type `ty`.
+    ///
+    /// Data is little-endian, unless set otherwise.
+    pub fn new(data: &'a [u8], ty: VariantType) -> Self {
+        Self {
+            data,
+            ty,
+            network_endian: false,
+        }
+    }
+
+    /// Sets whether data uses network (i.e. big) endianness.
+    pub fn network_endian(self, ne: bool) -> Self {
+        let mut view = self;
+        view.network_endian = ne;
+        view
+    }
+
+    /// Return the type of the value.
+    pub fn variant_type(&self) -> &VariantType {
+        &self.ty
+    }
+
+    /// Return the serialized bytes of the value.
+    pub fn as_bytes(&self) -> &'a [u8] {
+        self.data
+    }
+
+    /// Return the number of children of a container value.
+    pub fn n_children(&self) -> errors::Result<usize> {
+        match self.ty {
+            VariantType::Variant => Ok(1),
+            VariantType::Option(ref inner) => {
+                let content = framing::maybe_content(self.data, inner.fixed_size().is_some())?;
+                Ok(content.map_or(0, |_| 1))
+            }
+            VariantType::Vec(ref elem) => {
+                let (count, _) = framing::array_len(self.data, elem.fixed_size())?;
+                Ok(count)
+            }
+            VariantType::Structure(ref fields) => Ok(fields.len()),
+            VariantType::DictEntry(..) => Ok(2),
+            ref ty => bail!(errors::Error::unsupported(format!("children of '{}'", ty))),
+        }
+    }
+
+    /// Return the child at `index` of a container value.
+    ///
+    /// Array elements are located in constant time; structure fields in
+    /// time linear in the number of fields.
+    pub fn child(&self, index: usize) -> errors::Result<VariantRef<'a>> {
+        match self.ty {
+            VariantType::Variant => {
+                self.check_index(index, 1)?;
+                let (value, signature) = framing::split_variant(self.data)?;
+                Ok(self.view(value, VariantType::parse(signature)?))
+            }
+            VariantType::Option(ref inner) => {
+                let content = framing::maybe_content(self.data, inner.fixed_size().is_some())?;
+                self.check_index(index, content.map_or(0, |_| 1))?;
+                Ok(self.view(content.unwrap_or_default(), (**inner).clone()))
+            }
+            VariantType::Vec(ref elem) => {
+                let (count, table) = framing::array_len(self.data, elem.fixed_size())?;
+                self.check_index(index, count)?;
+                self.element(elem, table, index)
+            }
+            VariantType::Structure(ref fields) => {
+                self.check_index(index, fields.len())?;
+                self.field(fields.iter(), index)
+            }
+            VariantType::DictEntry(ref k, ref v) => {
+                self.check_index(index, 2)?;
+                self.field(vec![&**k, &**v].into_iter(), index)
+            }
+            ref ty => bail!(errors::Error::unsupported(format!("children of '{}'", ty))),
+        }
+    }
+
+    /// Look up `key` in a dictionary with string-like keys (`a{s*}`).
+    ///
+    /// This is a binary search over entries, so it takes a logarithmic number
+    /// of steps but requires entries to be sorted by key, as in ostree
+    /// summaries; results on unsorted dictionaries are unspecified.
+    pub fn lookup(&self, key: &str) -> errors::Result<Option<VariantRef<'a>>> {
+        let elem = match self.ty {
+            VariantType::Vec(ref elem) => match **elem {
+                VariantType::DictEntry(ref k, _)
+                    if matches!(
+                        **k,
+                        VariantType::String | VariantType::ObjectPath | VariantType::Signature
+                    ) =>
+                {
+                    elem
+                }
+                _ => bail!(errors::Error::mismatch("a{s*}", &self.ty)),
+            },
+            _ => bail!(errors::Error::mismatch("a{s*}", &self.ty)),
+        };
+
+        let (count, table) = framing::array_len(self.data, elem.fixed_size())?;
+        let (mut low, mut high) = (0, count);
+        while low < high {
+            let mid = low + (high - low) / 2;
+            let entry = self.element(elem, table, mid)?;
+            match entry.child(0)?.as_str()?.cmp(key) {
+                Ordering::Less => low = mid + 1,
+                Ordering::Greater => high = mid,
+                Ordering::Equal => return entry.child(1).map(Some),
+            }
+        }
+        Ok(None)
+    }
+
+    /// Borrow the content of a string-like value (`s`, `o` or `g`).
+    pub fn as_str(&self) -> errors::Result<&'a str> {
+        match self.ty {
+            VariantType::String | VariantType::ObjectPath | VariantType::Signature => {
+                decode::nul_str(self.data)
+            }
+            ref ty => bail!(errors::Error::mismatch("s", ty)),
+        }
+    }
+
+    /// Decode the whole value into a `Variant` tree.
+    pub fn to_variant(&self) -> errors::Result<Variant> {
+        let decoder = Decoder {
+            network_endian: self.network_endian,
+        };
+        decoder.decode(self.data, &self.ty)
+    }
+
+    /// Decode the whole value into an instance of `T`.
+    pub fn get<T>(&self) -> errors::Result<T>
+    where
+        T: serde::de::DeserializeOwned,
+    {
+        crate::from_variant(self.to_variant()?)
+    }
+
+    fn view(&self, data: &'a [u8], ty: VariantType) -> VariantRef<'a> {
+        VariantRef {
+            data,
+            ty,
+            network_endian: self.network_endian,
+        }
+    }
+
+    fn check_index(&self, index: usize, count: usize) -> errors::Result<()> {
+        if index >= count {
+            bail!(
+                "variant_ref: child {} out of bounds for '{}' with {} children",
+                index,
+                self.ty,
+                count
+            );
+        }
+        Ok(())
+    }
+
+    fn element(
+        &self,
+        elem: &VariantType,
+        table: usize,
+        index: usize,
+    ) -> errors::Result<VariantRef<'a>> {
+        let (start, end) =
+            framing::array_element(self.data, elem.alignment(), elem.fixed_size(), table, index)?;
+        Ok(self.view(&self.data[start..end], elem.clone()))
+    }
+
+    fn field<'t, I>(&self, fields: I, index: usize) -> errors::Result<VariantRef<'a>>
+    where
+        I: Iterator<Item = &'t VariantType>,
+    {
+        let fields: Vec<_> = fields.collect();
+        let layout: Vec<_> = fields
+            .iter()
+            .map(|t| (t.alignment(), t.fixed_size()))
+            .collect();
+        let ranges = framing::split_fields(self.data, &layout)?;
+        let (start, end) = ranges[index];
+        Ok(self.view(&self.data[start..end], fields[index].clone()))
+    }
+}
@@ -0,0 +1,382 @@
+//! GVariant type signatures.
+
+use crate::errors;
+use alloc::boxed::Box;
+use alloc::collections::BTreeMap;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::{fmt, str};
+
+/// Maximum nesting depth of containers in a type signature.
+pub(crate) const MAX_DEPTH: usize = 128;
+
+/// A GVariant type, as described by a type signature.
+#[derive(Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub enum VariantType {
+    /// Boolean (signature: `b`).
+    Bool,
+    /// Byte (signature: `y`).
+    U8,
+    /// Unsigned 16-bits integer (signature: `q`).
+    U16,
+    /// Unsigned 32-bits integer (signature: `u`).
+    U32,
+    /// Unsigned 64-bits integer (signature: `t`).
+    U64,
+    /// Signed 16-bits integer (signature: `n`).
+    I16,
+    /// Signed 32-bits integer (signature: `i`).
+    I32,
+    /// Signed 64-bits integer (signature: `x`).
+    I64,
+    /// Double-precision floating point number (signature: `d`).
+    F64,
+    /// String (signature: `s`).
+    String,
+    /// DBus object path (signature: `o`).
+    ObjectPath,
+    /// DBus signature string (signature: `g`).
+    Signature,
+    /// Index of a file descriptor passed alongside (signature: `h`).
+    Handle,
+    /// Generic variant type (signature: `v`).
+    Variant,
+    /// Optional ("Maybe") container (signature: `mX`).
+    Option(Box<VariantType>),
+    /// Homogeneous array (signature: `aX`).
+    Vec(Box<VariantType>),
+    /// Structure, variadic tuple (signature: `(XYZ)`).
+    Structure(Vec<VariantType>),
+    /// Dictionary entry, only valid as array element (signature: `{XY}`).
+    DictEntry(Box<VariantType>, Box<VariantType>),
+}
+
+impl VariantType {
+    /// Parse a type signature, which must describe exactly one complete type.
+    pub fn parse(signature: &str) -> errors::Result<Self> {
+        let bytes = signature.as_bytes();
+        let (ty, consumed) = Self::parse_prefix(bytes, 0)?;
+        if consumed != bytes.len() {
+            return Err(errors::Error::signature(format!(
+                "trailing characters in '{}'",
+                signature
+            )));
+        }
+        Ok(ty)
+    }
+
+    /// Parse the first complete type at the start of `bytes`.
+    ///
+    /// Returns the type and the number of bytes it spans.
+    pub(crate) fn parse_prefix(bytes: &[u8], depth: usize) -> errors::Result<(Self, usize)> {
+        if depth > MAX_DEPTH {
+            return Err(errors::Error::limit("signature: nesting too deep"));
+        }
+        let first = *bytes
+            .first()
+            .ok_or_else(|| errors::Error::signature("unexpected end"))?;
+        let basic = match first {
+            b'b' => Some(VariantType::Bool),
+            b'y' => Some(VariantType::U8),
+            b'q' => Some(VariantType::U16),
+            b'u' => Some(VariantType::U32),
+            b't' => Some(VariantType::U64),
+            b'n' => Some(VariantType::I16),
+            b'i' => Some(VariantType::I32),
+            b'x' => Some(VariantType::I64),
+            b'd' => Some(VariantType::F64),
+            b's' => Some(VariantType::String),
+            b'o' => Some(VariantType::ObjectPath),
+            b'g' => Some(VariantType::Signature),
+            b'h' => Some(VariantType::Handle),
+            b'v' => Some(VariantType::Variant),
+            _ => None,
+        };
+        if let Some(ty) = basic {
+            return Ok((ty, 1));
+        }
+
+        match first {
+            b'm' => {
+                let (inner, len) = Self::parse_prefix(&bytes[1..], depth + 1)?;
+                Ok((VariantType::Option(Box::new(inner)), len + 1))
+            }
+            b'a' => {
+                let (inner, len) = Self::parse_prefix(&bytes[1..], depth + 1)?;
+                Ok((VariantType::Vec(Box::new(inner)), len + 1))
+            }
+            b'(' => {
+                let mut fields = vec![];
+                let mut pos = 1;
+                loop {
+                    match bytes.get(pos) {
+                        Some(b')') => break,
+                        Some(_) => {
+                            let (field, len) = Self::parse_prefix(&bytes[pos..], depth + 1)?;
+                            fields.push(field);
+                            pos += len;
+                        }
+                        None => return Err(errors::Error::signature("unclosed structure")),
+                    }
+                }
+                Ok((VariantType::Structure(fields), pos + 1))
+            }
+            b'{' => {
+                let (key, klen) = Self::parse_prefix(&bytes[1..], depth + 1)?;
+                if !key.is_basic() {
+                    return Err(errors::Error::signature(format!(
+                        "non-basic dictionary key type '{}'",
+                        key
+                    )));
+                }
+                let (value, vlen) = Self::parse_prefix(&bytes[1 + klen..], depth + 1)?;
+                let end = 1 + klen + vlen;
+                if bytes.get(end) != Some(&b'}') {
+                    return Err(errors::Error::signature("unclosed dictionary entry"));
+                }
+                Ok((
+                    VariantType::DictEntry(Box::new(key), Box::new(value)),
+                    end + 1,
+                ))
+            }
+            c => Err(errors::Error::signature(format!(
+                "unrecognized type character '{}'",
+                c as char
+            ))),
+        }
+    }
+
+    /// Whether this is a basic type, usable as dictionary key.
+    pub fn is_basic(&self) -> bool {
+        !matches!(
+            *self,
+            VariantType::Variant
+                | VariantType::Option(..)
+                | VariantType::Vec(..)
+                | VariantType::Structure(..)
+                | VariantType::DictEntry(..)
+        )
+    }
+
+    /// Alignment (in bytes) of serialized values of this type.
+    pub fn alignment(&self) -> usize {
+        match *self {
+            VariantType::Bool | VariantType::U8 => 1,
+            VariantType::U16 | VariantType::I16 => 2,
+            VariantType::U32 | VariantType::I32 | VariantType::Handle => 4,
+            VariantType::U64 | VariantType::I64 | VariantType::F64 => 8,
+            VariantType::String | VariantType::ObjectPath | VariantType::Signature => 1,
+            VariantType::Variant => 8,
+            VariantType::Option(ref inner) | VariantType::Vec(ref inner) => inner.alignment(),
+            VariantType::Structure(ref fields) => {
+                fields.iter().map(Self::alignment).max().unwrap_or(1)
+            }
+            VariantType::DictEntry(ref k, ref v) => k.alignment().max(v.alignment()),
+        }
+    }
+
+    /// Size (in bytes) of serialized values of this type, if fixed.
+    pub fn fixed_size(&self) -> Option<usize> {
+        match *self {
+            VariantType::Bool | VariantType::U8 => Some(1),
+            VariantType::U16 | VariantType::I16 => Some(2),
+            VariantType::U32 | VariantType::I32 | VariantType::Handle => Some(4),
+            VariantType::U64 | VariantType::I64 | VariantType::F64 => Some(8),
+            VariantType::String
+            | VariantType::ObjectPath
+            | VariantType::Signature
+            | VariantType::Variant
+            | VariantType::Option(..)
+            | VariantType::Vec(..) => None,
+            VariantType::Structure(ref fields) => Self::fixed_fields_size(fields.iter()),
+            VariantType::DictEntry(ref k, ref v) => {
+                Self::fixed_fields_size(vec![&**k, &**v].into_iter())
+            }
+        }
+    }
+
+    fn fixed_fields_size<'a, I>(fields: I) -> Option<usize>
+    where
+        I: Iterator<Item = &'a VariantType>,
+    {
+        let mut size = 0;
+        let mut alignment = 1;
+        for field in fields {
+            let field_align = field.alignment();
+            size = crate::framing::align(size, field_align);
+            size += field.fixed_size()?;
+            alignment = alignment.max(field_align);
+        }
+        // The unit structure is a single zero byte.
+        if size == 0 {
+            return Some(1);
+        }
+        Some(crate::framing::align(size, alignment))
+    }
+}
+
+impl fmt::Display for VariantType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            VariantType::Bool => f.write_str("b"),
+            VariantType::U8 => f.write_str("y"),
+            VariantType::U16 => f.write_str("q"),
+            VariantType::U32 => f.write_str("u"),
+            VariantType::U64 => f.write_str("t"),
+            VariantType::I16 => f.write_str("n"),
+            VariantType::I32 => f.write_str("i"),
+            VariantType::I64 => f.write_str("x"),
+            VariantType::F64 => f.write_str("d"),
+            VariantType::String => f.write_str("s"),
+            VariantType::ObjectPath => f.write_str("o"),
+            VariantType::Signature => f.write_str("g"),
+            VariantType::Handle => f.write_str("h"),
+            VariantType::Variant => f.write_str("v"),
+            VariantType::Option(ref inner) => write!(f, "m{}", inner),
+            VariantType::Vec(ref inner) => write!(f, "a{}", inner),
+            VariantType::Structure(ref fields) => {
+                f.write_str("(")?;
+                for field in fields {
+                    write!(f, "{}", field)?;
+                }
+                f.write_str(")")
+            }
+            VariantType::DictEntry(ref k, ref v) => write!(f, "{{{}{}}}", k, v),
+        }
+    }
+}
+
+impl str::FromStr for VariantType {
+    type Err = errors::Error;
+
+    fn from_str(s: &str) -> errors::Result<Self> {
+        Self::parse(s)
+    }
+}
+
+/// Rust types with a GVariant type known at compile time.
+pub trait StaticVariantType {
+    /// Return the GVariant type of this Rust type.
+    fn static_variant_type() -> VariantType;
+}
+
+macro_rules! impl_static_type {
+    ($ty:ty, $arm:ident) => {
+        impl StaticVariantType for $ty {
+            fn static_variant_type() -> VariantType {
+                VariantType::$arm
+            }
+        }
+    };
+}
+
+impl_static_type!(bool, Bool);
+impl_static_type!(u8, U8);
+impl_static_type!(u16, U16);
+impl_static_type!(u32, U32);
+impl_static_type!(u64, U64);
+impl_static_type!(i16, I16);
+impl_static_type!(i32, I32);
+impl_static_type!(i64, I64);
+impl_static_type!(f32, F64);
+impl_static_type!(f64, F64);
+impl_static_type!(char, U32);
+impl_static_type!(String, String);
+impl_static_type!(str, String);
+impl_static_type!(crate::handle::Handle, Handle);
+#[cfg(all(feature = "std", unix))]
+impl_static_type!(crate::handle::Fd, Handle);
+impl_static_type!(crate::variant::Variant, Variant);
+impl_static_type!(Box<crate::variant::Variant>, Variant);
+
+/// Rust types with a basic GVariant type, which can be dictionary keys.
+pub trait BasicVariantType: StaticVariantType {}
+
+impl BasicVariantType for bool {}
+impl BasicVariantType for u8 {}
+impl BasicVariantType for u16 {}
+impl BasicVariantType for u32 {}
+impl BasicVariantType for u64 {}
+impl BasicVariantType for i16 {}
+impl BasicVariantType for i32 {}
+impl BasicVariantType for i64 {}
+impl BasicVariantType for f32 {}
+impl BasicVariantType for f64 {}
+impl BasicVariantType for char {}
+impl BasicVariantType for String {}
+impl BasicVariantType for str {}
+impl BasicVariantType for crate::handle::Handle {}
+#[cfg(all(feature = "std", unix))]
+impl BasicVariantType for crate::handle::Fd {}
+impl<T> BasicVariantType for &T where T: ?Sized + BasicVariantType {}
+
+impl StaticVariantType for () {
+    fn static_variant_type() -> VariantType {
+        VariantType::Structure(vec![])
+    }
+}
+
+impl<T> StaticVariantType for &T
+where
+    T: ?Sized + StaticVariantType,
+{
+    fn static_variant_type() -> VariantType {
+        T::static_variant_type()
+    }
+}
+
+impl<T> StaticVariantType for Option<T>
+where
+    T: StaticVariantType,
+{
+    fn static_variant_type() -> VariantType {
+        VariantType::Option(Box::new(T::static_variant_type()))
+    }
+}
+
+impl<T> StaticVariantType for Vec<T>
+where
+    T: StaticVariantType,
+{
+    fn static_variant_type() -> VariantType {
+        VariantType::Vec(Box::new(T::static_variant_type()))
+    }
+}
+
+impl<K, V> StaticVariantType for BTreeMap<K, V>
+where
+    K: BasicVariantType,
+    V: StaticVariantType,
+{
+    fn static_variant_type() -> VariantType {
+        let entry = VariantType::DictEntry(
+            Box::new(K::static_variant_type()),
+            Box::new(V::static_variant_type()),
+        );
+        VariantType::Vec(Box::new(entry))
+    }
+}
+
+macro_rules! impl_static_tuple {
+    ($($name:ident)+) => {
+        impl<$($name),+> StaticVariantType for ($($name,)+)
+        where
+            $($name: StaticVariantType,)+
+        {
+            fn static_variant_type() -> VariantType {
+                VariantType::Structure(vec![$($name::static_variant_type()),+])
+            }
+        }
+    };
+}
+
+impl_static_tuple!(A);
+impl_static_tuple!(A B);
+impl_static_tuple!(A B C);
+impl_static_tuple!(A B C D);
+impl_static_tuple!(A B C D E);
+impl_static_tuple!(A B C D E F);
+impl_static_tuple!(A B C D E F G);
+impl_static_tuple!(A B C D E F G H);
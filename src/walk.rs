@@ -0,0 +1,373 @@
+//! Event-based traversal of serialized values.
+//!
+//! Values are walked in document order, guided by their type: containers are
+//! located through framing offsets, and basic values are handed out borrowed,
+//! without building a `Variant` tree or Rust types.
+
+use crate::errors;
+use crate::framing;
+use crate::handle::Handle;
+use crate::variant::decode::nul_str;
+use crate::variant_type::{VariantType, MAX_DEPTH};
+use alloc::format;
+use alloc::vec;
+use alloc::vec::Vec;
+use byteorder::{BigEndian, ByteOrder, LittleEndian};
+use core::ops::Range;
+
+/// A basic value, borrowed from serialized data.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BasicValue<'a> {
+    /// Boolean (signature: `b`).
+    Bool(bool),
+    /// Byte (signature: `y`).
+    U8(u8),
+    /// Unsigned 16-bits integer (signature: `q`).
+    U16(u16),
+    /// Unsigned 32-bits integer (signature: `u`).
+    U32(u32),
+    /// Unsigned 64-bits integer (signature: `t`).
+    U64(u64),
+    /// Signed 16-bits integer (signature: `n`).
+    I16(i16),
+    /// Signed 32-bits integer (signature: `i`).
+    I32(i32),
+    /// Signed 64-bits integer (signature: `x`).
+    I64(i64),
+    /// Double-precision floating point number (signature: `d`).
+    F64(f64),
+    /// String (signature: `s`).
+    String(&'a str),
+    /// DBus object path (signature: `o`).
+    ObjectPath(&'a str),
+    /// DBus signature string (signature: `g`).
+    Signature(&'a str),
+    /// Index of a file descriptor passed alongside (signature: `h`).
+    Handle(Handle),
+}
+
+/// Callbacks of `walk`, called in document order.
+///
+/// Positions are byte ranges within the walked data. All callbacks do nothing
+/// by default; returning an error aborts the walk.
+pub trait GVariantVisitor<'a> {
+    /// A basic value.
+    fn visit_basic(&mut self, range: Range<usize>, value: BasicValue<'a>) -> errors::Result<()> {
+        let _ = (range, value);
+        Ok(())
+    }
+
+    /// The start of a container of type `ty`, with `len` children.
+    ///
+    /// A `v` has one child, of the type in its signature. Returns whether to
+    /// walk the children: if not, `end_container` follows directly. If the
+    /// children cannot be located, `len` is 0 and `invalid_container` follows.
+    fn begin_container(
+        &mut self,
+        range: Range<usize>,
+        ty: &VariantType,
+        len: usize,
+    ) -> errors::Result<bool> {
+        let _ = (range, ty, len);
+        Ok(true)
+    }
+
+    /// The framing offsets of a container, each `width` bytes wide.
+    ///
+    /// Called after its children, if any were walked.
+    fn visit_offsets(&mut self, range: Range<usize>, width: usize) -> errors::Result<()> {
+        let _ = (range, width);
+        Ok(())
+    }
+
+    /// The end of a container of type `ty`.
+    fn end_container(&mut self, range: Range<usize>, ty: &VariantType) -> errors::Result<()> {
+        let _ = (range, ty);
+        Ok(())
+    }
+
+    /// The end of a container of type `ty` which could not be walked through.
+    ///
+    /// Called instead of `end_container`, after the children walked so far.
+    /// `offsets` locates its framing offsets and their width, if known.
+    /// Returns `error` to abort the walk (the default), or `Ok` to skip the
+    /// rest of the container and go on.
+    fn invalid_container(
+        &mut self,
+        range: Range<usize>,
+        ty: &VariantType,
+        offsets: Option<(Range<usize>, usize)>,
+        error: errors::Error,
+    ) -> errors::Result<()> {
+        let _ = (range, ty, offsets);
+        Err(error)
+    }
+}
+
+/// Walker of serialized values.
+pub(crate) struct Walker {
+    pub(crate) network_endian: bool,
+}
+
+macro_rules! read_number {
+    ($self:ident, $data:ident, $method:ident) => {
+        if $self.network_endian {
+            BigEndian::$method($data)
+        } else {
+            LittleEndian::$method($data)
+        }
+    };
+}
+
+impl Walker {
+    /// Walk `data`, serialized as a value of type `ty`.
+    pub(crate) fn walk<'a, V>(
+        &self,
+        data: &'a [u8],
+        ty: &VariantType,
+        visitor: &mut V,
+    ) -> errors::Result<()>
+    where
+        V: GVariantVisitor<'a> + ?Sized,
+    {
+        self.value(data, 0..data.len(), ty, visitor, 0)
+    }
+
+    fn value<'a, V>(
+        &self,
+        all: &'a [u8],
+        range: Range<usize>,
+        ty: &VariantType,
+        visitor: &mut V,
+        depth: usize,
+    ) -> errors::Result<()>
+    where
+        V: GVariantVisitor<'a> + ?Sized,
+    {
+        if depth > MAX_DEPTH {
+            bail!(errors::Error::limit("walk: nesting too deep"));
+        }
+        let (base, data) = (range.start, &all[range.clone()]);
+        let framing = |e: errors::Error| e.shift(base as u64);
+        if let Some(size) = ty.fixed_size() {
+            if data.len() != size {
+                return Err(errors::Error::framing(format!(
+                    "walk: wrong length for '{}' - expected={}, got={}",
+                    ty,
+                    size,
+                    data.len()
+                ))
+                .at(base as u64));
+            }
+        }
+
+        let value = match *ty {
+            VariantType::Bool => BasicValue::Bool(data[0] != 0),
+            VariantType::U8 => BasicValue::U8(data[0]),
+            VariantType::U16 => BasicValue::U16(read_number!(self, data, read_u16)),
+            VariantType::U32 => BasicValue::U32(read_number!(self, data, read_u32)),
+            VariantType::U64 => BasicValue::U64(read_number!(self, data, read_u64)),
+            VariantType::I16 => BasicValue::I16(read_number!(self, data, read_i16)),
+            VariantType::I32 => BasicValue::I32(read_number!(self, data, read_i32)),
+            VariantType::I64 => BasicValue::I64(read_number!(self, data, read_i64)),
+            VariantType::F64 => BasicValue::F64(read_number!(self, data, read_f64)),
+            VariantType::String => BasicValue::String(nul_str(data).map_err(framing)?),
+            VariantType::ObjectPath => BasicValue::ObjectPath(nul_str(data).map_err(framing)?),
+            VariantType::Signature => BasicValue::Signature(nul_str(data).map_err(framing)?),
+            VariantType::Handle => BasicValue::Handle(Handle(read_number!(self, data, read_i32))),
+            _ => return self.container(all, range, ty, visitor, depth),
+        };
+        visitor
+            .visit_basic(range, value)
+            .map_err(|e| e.at(base as u64))
+    }
+
+    fn container<'a, V>(
+        &self,
+        all: &'a [u8],
+        range: Range<usize>,
+        ty: &VariantType,
+        visitor: &mut V,
+        depth: usize,
+    ) -> errors::Result<()>
+    where
+        V: GVariantVisitor<'a> + ?Sized,
+    {
+        let (base, data) = (range.start, &all[range.clone()]);
+        let framing = |e: errors::Error| e.shift(base as u64);
+
+        let (children, table) = match children(data, ty) {
+            Ok(located) => located,
+            Err(e) => {
+                visitor
+                    .begin_container(range.clone(), ty, 0)
+                    .map_err(|e| e.at(base as u64))?;
+                let offsets = offsets(data, ty).map(|(r, w)| (base + r.start..base + r.end, w));
+                return visitor.invalid_container(range, ty, offsets, framing(e));
+            }
+        };
+        let walk = visitor
+            .begin_container(range.clone(), ty, children.len())
+            .map_err(|e| e.at(base as u64))?;
+        if walk {
+            for i in 0..children.len() {
+                let walked =
+                    children
+                        .get(data, i)
+                        .map_err(framing)
+                        .and_then(|(child, child_ty)| {
+                            let child = base + child.start..base + child.end;
+                            self.value(all, child, child_ty, visitor, depth + 1)
+                        });
+                if let Err(e) = walked {
+                    let offsets = table.map(|(start, width)| (base + start..range.end, width));
+                    return visitor.invalid_container(range, ty, offsets, e.index(i));
+                }
+            }
+            if let Some((start, width)) = table {
+                visitor
+                    .visit_offsets(base + start..range.end, width)
+                    .map_err(|e| e.at((base + start) as u64))?;
+            }
+        }
+        visitor
+            .end_container(range, ty)
+            .map_err(|e| e.at(base as u64))
+    }
+}
+
+/// Children of a container, with ranges relative to the container.
+enum Children<'t> {
+    /// Elements of an array, located on demand.
+    Elements {
+        elem: &'t VariantType,
+        count: usize,
+        table: usize,
+    },
+    /// Any other children, located upfront.
+    Fields(Vec<(Range<usize>, VariantType)>),
+}
+
+impl Children<'_> {
+    fn len(&self) -> usize {
+        match *self {
+            Children::Elements { count, .. } => count,
+            Children::Fields(ref fields) => fields.len(),
+        }
+    }
+
+    fn get(&self, data: &[u8], index: usize) -> errors::Result<(Range<usize>, &VariantType)> {
+        match *self {
+            Children::Elements { elem, table, .. } => {
+                let (start, end) = framing::array_element(
+                    data,
+                    elem.alignment(),
+                    elem.fixed_size(),
+                    table,
+                    index,
+                )?;
+                Ok((start..end, elem))
+            }
+            Children::Fields(ref fields) => {
+                let (ref range, ref ty) = fields[index];
+                Ok((range.clone(), ty))
+            }
+        }
+    }
+}
+
+/// Locate the children of a container.
+///
+/// Also returns where its framing offsets start and their width, if it has
+/// any. Errors are relative to the container.
+fn children<'t>(
+    data: &[u8],
+    ty: &'t VariantType,
+) -> errors::Result<(Children<'t>, Option<(usize, usize)>)> {
+    let width = framing::offset_size(data.len());
+    let fields = match *ty {
+        VariantType::Variant => {
+            let (value, signature) = framing::split_variant(data)?;
+            let inner =
+                VariantType::parse(signature).map_err(|e| e.shift(value.len() as u64 + 1))?;
+            vec![(0..value.len(), inner)]
+        }
+        VariantType::Option(ref inner) => {
+            let content = framing::maybe_content(data, inner.fixed_size().is_some())?;
+            let children = content.map(|c| (0..c.len(), (**inner).clone()));
+            children.into_iter().collect()
+        }
+        VariantType::Vec(ref elem) => {
+            let (count, table) = framing::array_len(data, elem.fixed_size())?;
+            let framed = elem.fixed_size().is_none() && count > 0;
+            let children = Children::Elements { elem, count, table };
+            return Ok((children, Some((table, width)).filter(|_| framed)));
+        }
+        VariantType::Structure(ref types) => return fields(data, types),
+        VariantType::DictEntry(ref k, ref v) => {
+            return fields(data, &[(**k).clone(), (**v).clone()])
+        }
+        ref ty => bail!(errors::Error::unsupported(format!("walk: '{}'", ty))),
+    };
+    Ok((Children::Fields(fields), None))
+}
+
+/// Locate the fields of a structure (or dictionary entry).
+fn fields<'t>(
+    data: &[u8],
+    types: &[VariantType],
+) -> errors::Result<(Children<'t>, Option<(usize, usize)>)> {
+    let layout: Vec<_> = types
+        .iter()
+        .map(|t| (t.alignment(), t.fixed_size()))
+        .collect();
+    let ranges = framing::split_fields(data, &layout)?;
+    let children = ranges
+        .into_iter()
+        .zip(types)
+        .map(|((start, end), t)| (start..end, t.clone()))
+        .collect();
+
+    // All variable-sized fields but the last one are framed.
+    let width = framing::offset_size(data.len());
+    let framed = types
+        .iter()
+        .rev()
+        .skip(1)
+        .filter(|t| t.fixed_size().is_none())
+        .count();
+    let table = Some((data.len() - framed * width, width)).filter(|_| framed > 0);
+    Ok((Children::Fields(children), table))
+}
+
+/// Locate the framing offsets of a container whose children cannot be.
+///
+/// Structures have as many offsets as they have framed fields, while arrays
+/// locate theirs through the last one: if out of bounds, only it is returned.
+fn offsets(data: &[u8], ty: &VariantType) -> Option<(Range<usize>, usize)> {
+    let width = framing::offset_size(data.len());
+    let framed = |types: &[&VariantType]| {
+        types
+            .iter()
+            .rev()
+            .skip(1)
+            .filter(|t| t.fixed_size().is_none())
+            .count()
+    };
+    let start = match *ty {
+        VariantType::Structure(ref types) => {
+            let types: Vec<_> = types.iter().collect();
+            data.len().checked_sub(framed(&types) * width)?
+        }
+        VariantType::DictEntry(ref k, ref v) => data.len().checked_sub(framed(&[k, v]) * width)?,
+        VariantType::Vec(ref elem) if elem.fixed_size().is_none() => {
+            let last = data.len().checked_sub(width)?;
+            match framing::read_offset(data, last, width) {
+                Ok(start) if start <= last && (data.len() - start).is_multiple_of(width) => start,
+                _ => last,
+            }
+        }
+        _ => return None,
+    };
+    Some((start..data.len(), width)).filter(|(range, _)| !range.is_empty())
+}
@@ -0,0 +1,109 @@
+#[macro_use]
+extern crate serde_derive;
+extern crate serde_gvariant;
+
+use serde_gvariant::{ArrayIter, ArrayWriter};
+
+#[derive(Debug, PartialEq, Deserialize, Serialize)]
+struct Entry {
+    name: String,
+    size: u64,
+}
+
+#[test]
+fn test_array_writer() {
+    let entries: Vec<Entry> = (0..3)
+        .map(|i| Entry {
+            name: format!("f{}", i),
+            size: i,
+        })
+        .collect();
+    let mut writer = ArrayWriter::new(vec![]);
+    for entry in &entries {
+        writer.push(entry).expect("push");
+    }
+    assert_eq!(writer.len(), 3);
+    assert_eq!(writer.element_type().unwrap().to_string(), "(st)");
+    let encoded = writer.finish().expect("finish");
+    let cfg = serde_gvariant::config().canonical(true);
+    assert_eq!(encoded, cfg.serialize(&entries).unwrap());
+
+    // Fixed-size elements need no framing offsets.
+    let mut writer = ArrayWriter::new(vec![]);
+    writer.push(&1u32).unwrap();
+    writer.push(&2u32).unwrap();
+    assert!(writer.push("three").is_err());
+    let encoded = writer.finish().unwrap();
+    assert_eq!(
+        encoded,
+        vec![0x01, 0x00, 0x00, 0x00, 0x02, 0x00, 0x00, 0x00]
+    );
+}
+
+#[test]
+fn test_array_writer_large() {
+    let file = std::io::Cursor::new(vec![]);
+    let mut writer = serde_gvariant::config().array_writer(file);
+    for i in 0..100_000 {
+        writer.push(&format!("{:05}", i)).unwrap();
+    }
+    let encoded = writer.finish().unwrap().into_inner();
+    assert_eq!(encoded.len(), 100_000 * 6 + 100_000 * 4);
+    let decoded: Vec<String> = serde_gvariant::from_slice(&encoded).unwrap();
+    assert_eq!(decoded.len(), 100_000);
+    assert_eq!(decoded[54_321], "54321");
+}
+
+#[test]
+fn test_array_iter() {
+    // Files of an ostree dirtree, `a(say)`.
+    #[derive(Debug, Deserialize, Serialize)]
+    struct File {
+        name: String,
+        checksum: Vec<u8>,
+    }
+
+    let mut writer = ArrayWriter::new(vec![]);
+    for i in 0..1000u32 {
+        let name = format!("f{}", i);
+        let checksum = i.to_le_bytes().to_vec();
+        writer.push(&File { name, checksum }).unwrap();
+    }
+    let encoded = writer.finish().unwrap();
+    let reader = std::io::Cursor::new(&encoded);
+    let found = ArrayIter::<File>::from_reader(reader)
+        .unwrap()
+        .find(|f| f.as_ref().map(|f| f.name == "f777").unwrap_or(true))
+        .unwrap()
+        .unwrap();
+    assert_eq!(found.checksum, 777u32.to_le_bytes());
+
+    let mut writer = ArrayWriter::new(vec![]);
+    for size in 0..1000u64 {
+        writer.push(&size).unwrap();
+    }
+    let encoded = writer.finish().unwrap();
+    let total: u64 = ArrayIter::<u64>::from_slice(&encoded)
+        .unwrap()
+        .map(|size| size.unwrap())
+        .sum();
+    assert_eq!(total, 999 * 1000 / 2);
+
+    // Elements may borrow from the input.
+    let encoded = serde_gvariant::to_vec(&vec!["a", "bc"]).unwrap();
+    let cfg = serde_gvariant::config();
+    let names: Vec<&str> = cfg
+        .array_iter_slice(&encoded)
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap();
+    assert_eq!(names, vec!["a", "bc"]);
+    assert_eq!(ArrayIter::<u32>::from_slice(&[]).unwrap().count(), 0);
+
+    // Iteration stops at the first error.
+    let encoded: Vec<u8> = vec![b'a', b'b', 0x00, 0x02, 0x03];
+    let mut iter = ArrayIter::<String>::from_slice(&encoded).unwrap();
+    let err = iter.next().unwrap().unwrap_err();
+    assert_eq!(err.path(), Some("[0]"));
+    assert!(iter.next().is_none());
+}
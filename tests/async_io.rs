@@ -0,0 +1,29 @@
+#![cfg(feature = "tokio")]
+
+extern crate serde_gvariant;
+extern crate tokio;
+
+#[tokio::test]
+async fn test_async_roundtrip() {
+    let decoded: Vec<String> = vec!["a".into(), "b".into()];
+    let encoded: Vec<u8> = vec![b'a', 0x00, b'b', 0x00, 0x02, 0x04];
+
+    let mut out = vec![];
+    serde_gvariant::to_async_write(&mut out, &decoded)
+        .await
+        .unwrap();
+    assert_eq!(out, encoded);
+
+    let (mut client, server) = tokio::io::duplex(64);
+    tokio::spawn(async move {
+        serde_gvariant::to_async_write(&mut client, &decoded)
+            .await
+            .unwrap();
+    });
+    let de: Vec<String> = serde_gvariant::from_async_read(server).await.unwrap();
+    assert_eq!(de, vec!["a".to_string(), "b".to_string()]);
+
+    let cfg = serde_gvariant::config().max_input_len(encoded.len() - 1);
+    let res: Result<Vec<String>, _> = cfg.deserialize_async_read(&encoded[..]).await;
+    assert!(res.is_err());
+}
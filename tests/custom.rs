@@ -1,16 +1,20 @@
+extern crate serde;
 extern crate serde_bytes;
 #[macro_use]
 extern crate serde_derive;
 extern crate serde_gvariant;
+extern crate serde_json;
 
-use serde_gvariant::{Array, Variant};
+use serde::de::IgnoredAny;
+use serde_gvariant::errors::ErrorKind;
+use serde_gvariant::{Array, CharType, Handle, I8Type, Variant, VariantRef, VariantType};
 
 #[test]
 fn test_bytes_buf() {
     let data = &[0x01, 0x02, 0x00, 0x03, 0x04];
     let buf = serde_bytes::ByteBuf::from(data.clone().to_vec());
     let slice = serde_bytes::Bytes::new(data);
-    let de: serde_bytes::ByteBuf = serde_gvariant::from_slice(&slice).expect("bytes de");
+    let de: serde_bytes::ByteBuf = serde_gvariant::from_slice(slice).expect("bytes de");
     let ser: Vec<u8> = serde_gvariant::to_vec(&buf).expect("bytes ser");
     assert_eq!(ser, data.to_vec());
     assert_eq!(de, buf);
@@ -69,7 +73,7 @@ fn test_fixed_struct() {
     #[derive(Debug, Default, Deserialize, Serialize, PartialEq)]
     struct TestType {
         len: u8,
-    };
+    }
     let encoded: Vec<u8> = vec![0x00];
     let decoded = TestType::default();
     let ser: Vec<u8> = serde_gvariant::to_vec(&decoded).expect("fixed struct ser");
@@ -85,7 +89,7 @@ fn test_variable_struct_01() {
         struct TestType {
             len: u8,
             value: String,
-        };
+        }
         let encoded: Vec<u8> = vec![0x00, 0x00];
         let decoded = TestType::default();
         let ser: Vec<u8> = serde_gvariant::to_vec(&decoded).expect("fixed struct ser");
@@ -103,7 +107,7 @@ fn test_variable_struct_02() {
             len: u8,
             value: String,
             meta: String,
-        };
+        }
         let encoded: Vec<u8> = vec![0x00, 0x00, 0x00, 0x02];
         let decoded = TestType::default();
         let ser: Vec<u8> = serde_gvariant::to_vec(&decoded).expect("fixed struct ser");
@@ -121,7 +125,7 @@ fn test_variable_struct_03() {
             value: String,
             metalen: u32,
             meta: String,
-        };
+        }
         let encoded: Vec<u8> = vec![
             0x03, 0x00, b'f', b'o', b'o', 0x00, 0x00, 0x00, 0x06, 0x00, 0x00, 0x00, b'f', b'o',
             b'o', b'b', b'a', b'r', 0x00, 0x06,
@@ -139,6 +143,904 @@ fn test_variable_struct_03() {
     }
 }
 
+#[test]
+fn test_variable_struct_04() {
+    #[derive(Debug, Default, Deserialize, Serialize, PartialEq)]
+    struct TestType {
+        a: String,
+        b: String,
+        c: String,
+    }
+    // Framing offsets are stored in reverse order.
+    let encoded: Vec<u8> = vec![
+        b'a', 0x00, b'b', b'b', 0x00, b'c', b'c', b'c', 0x00, 0x05, 0x02,
+    ];
+    let decoded = TestType {
+        a: "a".to_string(),
+        b: "bb".to_string(),
+        c: "ccc".to_string(),
+    };
+    let ser: Vec<u8> = serde_gvariant::to_vec(&decoded).expect("variable struct ser");
+    let de: TestType = serde_gvariant::from_slice(&encoded[..]).expect("variable struct de");
+    assert_eq!(ser, encoded);
+    assert_eq!(de, decoded);
+
+    let decoded = vec![decoded, TestType::default()];
+    let ser: Vec<u8> = serde_gvariant::to_vec(&decoded).expect("struct array ser");
+    let de: Vec<TestType> = serde_gvariant::from_slice(&ser[..]).expect("struct array de");
+    assert_eq!(de, decoded);
+}
+
+#[test]
+fn test_fixed_struct_padding() {
+    #[derive(Debug, Default, Deserialize, Serialize, PartialEq)]
+    struct Pair {
+        a: u32,
+        b: u8,
+    }
+    #[derive(Debug, Default, Deserialize, Serialize, PartialEq)]
+    struct Outer {
+        p: Pair,
+        s: String,
+    }
+
+    // (uy) is padded to its alignment, and never framed.
+    let pair = Pair { a: 1, b: 2 };
+    let encoded: Vec<u8> = vec![0x01, 0x00, 0x00, 0x00, 0x02, 0x00, 0x00, 0x00];
+    let ser = serde_gvariant::to_vec(&pair).expect("(uy) ser");
+    let de: Pair = serde_gvariant::from_slice(&encoded).expect("(uy) de");
+    assert_eq!(ser, encoded);
+    assert_eq!(de, pair);
+
+    // a(uy) is a fixed-width array, without framing offsets.
+    let pairs = vec![Pair { a: 1, b: 2 }, Pair { a: 3, b: 4 }];
+    let encoded: Vec<u8> = vec![
+        0x01, 0x00, 0x00, 0x00, 0x02, 0x00, 0x00, 0x00, 0x03, 0x00, 0x00, 0x00, 0x04, 0x00, 0x00,
+        0x00,
+    ];
+    let ser = serde_gvariant::to_vec(&pairs).expect("a(uy) ser");
+    let de: Vec<Pair> = serde_gvariant::from_slice(&encoded).expect("a(uy) de");
+    assert_eq!(ser, encoded);
+    assert_eq!(de, pairs);
+
+    // ((uy)s) only frames variable-sized fields.
+    let outer = Outer {
+        p: pair,
+        s: "x".to_string(),
+    };
+    let encoded: Vec<u8> = vec![0x01, 0x00, 0x00, 0x00, 0x02, 0x00, 0x00, 0x00, b'x', 0x00];
+    let ser = serde_gvariant::to_vec(&outer).expect("((uy)s) ser");
+    let de: Outer = serde_gvariant::from_slice(&encoded).expect("((uy)s) de");
+    assert_eq!(ser, encoded);
+    assert_eq!(de, outer);
+
+    let outers = vec![outer, Outer::default()];
+    let ser = serde_gvariant::to_vec(&outers).expect("a((uy)s) ser");
+    let de: Vec<Outer> = serde_gvariant::from_slice(&ser).expect("a((uy)s) de");
+    assert_eq!(de, outers);
+}
+
+#[test]
+fn test_struct_skip() {
+    #[derive(Debug, Default, Deserialize, Serialize, PartialEq)]
+    struct TestType {
+        name: String,
+        #[serde(skip)]
+        cache: Vec<u8>,
+        size: u32,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        comment: Option<String>,
+    }
+    let encoded: Vec<u8> = vec![b'a', 0x00, 0x00, 0x00, 0x07, 0x00, 0x00, 0x00, 0x02];
+    let decoded = TestType {
+        name: "a".to_string(),
+        cache: vec![0xff],
+        size: 7,
+        comment: None,
+    };
+    let ser: Vec<u8> = serde_gvariant::to_vec(&decoded).expect("skip struct ser");
+    assert_eq!(ser, encoded);
+
+    let cfg = serde_gvariant::config().trailing_defaults(true);
+    let de: TestType = cfg.deserialize_slice(&encoded[..]).expect("skip struct de");
+    let expected = TestType {
+        cache: vec![],
+        ..decoded
+    };
+    assert_eq!(de, expected);
+
+    let variant = serde_gvariant::to_variant(&expected).expect("skip struct to variant");
+    assert_eq!(variant.variant_type().to_string(), "(su)");
+    let de: TestType = cfg
+        .deserialize_variant(&variant)
+        .expect("skip struct from variant");
+    assert_eq!(de, expected);
+
+    // Optional fields are maybe types, with a terminator for variable-sized values.
+    let encoded: Vec<u8> = vec![
+        b'a', 0x00, 0x00, 0x00, 0x07, 0x00, 0x00, 0x00, b'c', 0x00, 0x00, 0x02,
+    ];
+    let decoded = TestType {
+        comment: Some("c".to_string()),
+        ..expected
+    };
+    let ser: Vec<u8> = serde_gvariant::to_vec(&decoded).expect("some struct ser");
+    assert_eq!(ser, encoded);
+    let de: TestType = serde_gvariant::from_slice(&encoded[..]).expect("some struct de");
+    assert_eq!(de, decoded);
+
+    // Maybe types are framed, even when empty or with a fixed-size value.
+    #[derive(Debug, Deserialize, Serialize, PartialEq)]
+    struct Maybes {
+        a: Option<u32>,
+        b: Option<String>,
+        c: String,
+    }
+    let decoded = Maybes {
+        a: Some(1),
+        b: None,
+        c: "c".to_string(),
+    };
+    let encoded: Vec<u8> = vec![0x01, 0x00, 0x00, 0x00, b'c', 0x00, 0x04, 0x04];
+    let ser: Vec<u8> = serde_gvariant::to_vec(&decoded).expect("maybe struct ser");
+    assert_eq!(ser, encoded);
+    let de: Maybes = serde_gvariant::from_slice(&encoded[..]).expect("maybe struct de");
+    assert_eq!(de, decoded);
+
+    // Only trailing fields can be left out: others would shift the next ones.
+    #[derive(Debug, Deserialize, Serialize, PartialEq)]
+    struct Mid {
+        x: u8,
+        #[serde(skip_serializing_if = "Vec::is_empty", default)]
+        v: Vec<u32>,
+        y: String,
+    }
+    let skipped = Mid {
+        x: 1,
+        v: vec![],
+        y: "hi".to_string(),
+    };
+    let err = serde_gvariant::to_vec(&skipped).unwrap_err();
+    assert!(
+        matches!(err.kind(), ErrorKind::UnsupportedType(_)),
+        "{}",
+        err
+    );
+    assert!(serde_gvariant::to_variant(&skipped).is_err());
+    let decoded = Mid {
+        v: vec![2],
+        ..skipped
+    };
+    let ser: Vec<u8> = serde_gvariant::to_vec(&decoded).expect("mid struct ser");
+    let de: Mid = serde_gvariant::from_slice(&ser).expect("mid struct de");
+    assert_eq!(de, decoded);
+}
+
+#[test]
+fn test_struct_trailing_defaults() {
+    #[derive(Debug, Default, Deserialize, Serialize, PartialEq)]
+    struct V1 {
+        name: String,
+        version: u32,
+    }
+    #[derive(Debug, Default, Deserialize, Serialize, PartialEq)]
+    struct V2 {
+        name: String,
+        version: u32,
+        #[serde(default)]
+        tags: Vec<String>,
+        #[serde(default)]
+        flags: u64,
+    }
+    let old = V1 {
+        name: "foo".to_string(),
+        version: 1,
+    };
+    let encoded = serde_gvariant::to_vec(&old).expect("v1 ser");
+
+    // Strict by default.
+    assert!(serde_gvariant::from_slice::<V2>(&encoded).is_err());
+
+    let cfg = serde_gvariant::config().trailing_defaults(true);
+    let de: V2 = cfg.deserialize_slice(&encoded).expect("v2 de");
+    let expected = V2 {
+        name: "foo".to_string(),
+        version: 1,
+        ..V2::default()
+    };
+    assert_eq!(de, expected);
+
+    // Trees follow the same setting.
+    let variant = serde_gvariant::to_variant(&old).expect("v1 to variant");
+    assert!(serde_gvariant::from_variant::<V2>(variant.clone()).is_err());
+    let de: V2 = cfg.deserialize_variant(&variant).expect("v2 from variant");
+    assert_eq!(de, expected);
+
+    // Full structures are unaffected.
+    let new = V2 {
+        tags: vec!["a".to_string()],
+        flags: 3,
+        ..expected
+    };
+    let encoded = serde_gvariant::to_vec(&new).expect("v2 ser");
+    let de: V2 = cfg.deserialize_slice(&encoded).expect("v2 de");
+    assert_eq!(de, new);
+}
+
+#[test]
+fn test_struct_flatten() {
+    #[derive(Debug, Default, Deserialize, Serialize, PartialEq)]
+    struct Base {
+        name: String,
+        size: u32,
+    }
+    #[derive(Debug, Default, Deserialize, Serialize, PartialEq)]
+    struct TestType {
+        id: u64,
+        #[serde(flatten)]
+        base: Base,
+    }
+    let decoded = TestType {
+        id: 3,
+        base: Base {
+            name: "foo".to_string(),
+            size: 7,
+        },
+    };
+    let variant = serde_gvariant::to_variant(&decoded).expect("flatten to variant");
+    assert_eq!(variant.variant_type().to_string(), "a{sv}");
+    let de: TestType = serde_gvariant::from_variant(variant).expect("flatten from variant");
+    assert_eq!(de, decoded);
+
+    // Binary encoding does not box heterogeneous fields.
+    let err = serde_gvariant::to_vec(&decoded).unwrap_err();
+    assert!(
+        matches!(err.kind(), ErrorKind::UnsupportedType(_)),
+        "{}",
+        err
+    );
+    assert_eq!(err.path(), Some("name"));
+
+    #[derive(Debug, Default, Deserialize, Serialize, PartialEq)]
+    struct Names {
+        first: String,
+        last: String,
+    }
+    #[derive(Debug, Default, Deserialize, Serialize, PartialEq)]
+    struct Person {
+        nick: String,
+        #[serde(flatten)]
+        names: Names,
+    }
+    let person = Person {
+        nick: "jd".to_string(),
+        names: Names {
+            first: "John".to_string(),
+            last: "Doe".to_string(),
+        },
+    };
+    let encoded = serde_gvariant::to_vec(&person).expect("flatten ser");
+    let (_, signature) = serde_gvariant::to_vec_with_signature(&person).unwrap();
+    assert_eq!(signature, "a{ss}");
+    // Flattened fields are decoded as self-described values.
+    let cfg = serde_gvariant::config().signature(VariantType::parse("a{ss}").unwrap());
+    let de: Person = cfg.deserialize_slice(&encoded).expect("flatten de");
+    assert_eq!(de, person);
+}
+
+#[test]
+fn test_map_layout() {
+    use std::collections::BTreeMap;
+
+    // Entries are aligned and framed like `(su)` structures.
+    let mut map = BTreeMap::new();
+    map.insert("a".to_string(), 1u32);
+    let encoded = serde_gvariant::to_vec(&map).expect("a{su} ser");
+    assert_eq!(
+        encoded,
+        vec![b'a', 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x02, 0x09]
+    );
+
+    // Values are laid out as their own type.
+    let mut map = BTreeMap::new();
+    map.insert(1u8, vec![5u64]);
+    let encoded = serde_gvariant::to_vec(&map).expect("a{yat} ser");
+    let mut expected = vec![0x01, 0, 0, 0, 0, 0, 0, 0, 0x05, 0, 0, 0, 0, 0, 0, 0];
+    expected.push(0x10);
+    assert_eq!(encoded, expected);
+    let (_, signature) = serde_gvariant::to_vec_with_signature(&map).unwrap();
+    assert_eq!(signature, "a{yat}");
+}
+
+#[test]
+fn test_map_roundtrip() {
+    use std::collections::{BTreeMap, HashMap};
+
+    let map: BTreeMap<String, u32> = (0..4).map(|i| (format!("k{}", i), i)).collect();
+    let encoded = serde_gvariant::to_vec(&map).expect("BTreeMap ser");
+    let de: BTreeMap<String, u32> = serde_gvariant::from_slice(&encoded).expect("BTreeMap de");
+    assert_eq!(de, map);
+    let de: HashMap<String, u32> = serde_gvariant::from_slice(&encoded).expect("HashMap de");
+    assert_eq!(de, map.clone().into_iter().collect());
+
+    // Fixed-size entries are unframed, and padded to their alignment.
+    let map: HashMap<u32, u8> = vec![(1, 10), (2, 20), (3, 30)].into_iter().collect();
+    let encoded = serde_gvariant::to_vec(&map).expect("HashMap ser");
+    assert_eq!(encoded.len(), 3 * 8);
+    let de: HashMap<u32, u8> = serde_gvariant::from_slice(&encoded).expect("HashMap de");
+    assert_eq!(de, map);
+
+    // Nested in structures and arrays.
+    #[derive(Debug, Deserialize, PartialEq, Serialize)]
+    struct Record {
+        id: u64,
+        sizes: HashMap<u16, u64>,
+        tags: BTreeMap<String, String>,
+        last: u8,
+    }
+    let record = Record {
+        id: 1,
+        sizes: vec![(1, 100), (7, 700)].into_iter().collect(),
+        tags: vec![("a".into(), "x".into()), ("bc".into(), String::new())]
+            .into_iter()
+            .collect(),
+        last: 2,
+    };
+    let records = vec![record];
+    let encoded = serde_gvariant::to_vec(&records).expect("records ser");
+    let de: Vec<Record> = serde_gvariant::from_slice(&encoded).expect("records de");
+    assert_eq!(de, records);
+    let empty: Vec<BTreeMap<String, String>> = vec![BTreeMap::new(), BTreeMap::new()];
+    let encoded = serde_gvariant::to_vec(&empty).expect("empty maps ser");
+    let de: Vec<BTreeMap<String, String>> =
+        serde_gvariant::from_slice(&encoded).expect("empty maps de");
+    assert_eq!(de, empty);
+}
+
+#[test]
+fn test_newtype_struct() {
+    #[derive(Debug, Default, Deserialize, Serialize, PartialEq)]
+    struct Checksum(Vec<u8>);
+    #[derive(Debug, Default, Deserialize, Serialize, PartialEq)]
+    struct Name(String);
+    #[derive(Debug, Default, Deserialize, Serialize, PartialEq)]
+    struct Mode(u32);
+    #[derive(Debug, Default, Deserialize, Serialize, PartialEq)]
+    struct TestType {
+        name: Name,
+        mode: Mode,
+        checksum: Checksum,
+        aliases: Vec<Name>,
+    }
+    let decoded = TestType {
+        name: Name("foo".to_string()),
+        mode: Mode(0o755),
+        checksum: Checksum(vec![0xaa, 0xbb]),
+        aliases: vec![Name("a".to_string()), Name("b".to_string())],
+    };
+    #[derive(Serialize)]
+    struct Plain {
+        name: String,
+        mode: u32,
+        checksum: Vec<u8>,
+        aliases: Vec<String>,
+    }
+    let plain = Plain {
+        name: "foo".to_string(),
+        mode: 0o755,
+        checksum: vec![0xaa, 0xbb],
+        aliases: vec!["a".to_string(), "b".to_string()],
+    };
+    let ser: Vec<u8> = serde_gvariant::to_vec(&decoded).expect("newtype ser");
+    assert_eq!(ser, serde_gvariant::to_vec(&plain).expect("plain ser"));
+    let de: TestType = serde_gvariant::from_slice(&ser[..]).expect("newtype de");
+    assert_eq!(de, decoded);
+
+    let variant = serde_gvariant::to_variant(&decoded).expect("newtype to variant");
+    assert_eq!(variant.variant_type().to_string(), "(suayas)");
+    let de: TestType = serde_gvariant::from_variant(variant).expect("newtype from variant");
+    assert_eq!(de, decoded);
+}
+
+#[test]
+fn test_unit() {
+    #[derive(Debug, Default, Deserialize, Serialize, PartialEq)]
+    struct Marker;
+    #[derive(Debug, Default, Deserialize, Serialize, PartialEq)]
+    struct TestType {
+        marker: Marker,
+        unit: (),
+        len: u8,
+    }
+    let encoded: Vec<u8> = vec![0x00];
+    let ser: Vec<u8> = serde_gvariant::to_vec(&()).expect("unit ser");
+    assert_eq!(ser, encoded);
+    let ser: Vec<u8> = serde_gvariant::to_vec(&Marker).expect("unit struct ser");
+    assert_eq!(ser, encoded);
+    let de: () = serde_gvariant::from_slice(&encoded[..]).expect("unit de");
+    assert_eq!(de, ());
+    let de: Marker = serde_gvariant::from_slice(&encoded[..]).expect("unit struct de");
+    assert_eq!(de, Marker);
+
+    let encoded: Vec<u8> = vec![0x00, 0x00, 0x07];
+    let decoded = TestType {
+        len: 7,
+        ..TestType::default()
+    };
+    let ser: Vec<u8> = serde_gvariant::to_vec(&decoded).expect("unit field ser");
+    let de: TestType = serde_gvariant::from_slice(&encoded[..]).expect("unit field de");
+    assert_eq!(ser, encoded);
+    assert_eq!(de, decoded);
+
+    let variant = serde_gvariant::to_variant(&decoded).expect("unit to variant");
+    assert_eq!(variant.variant_type().to_string(), "(()()y)");
+    let de: TestType = serde_gvariant::from_variant(variant).expect("unit from variant");
+    assert_eq!(de, decoded);
+}
+
+#[test]
+fn test_type_mapping() {
+    #[derive(Debug, Default, Deserialize, Serialize, PartialEq)]
+    struct TestType {
+        ratio: f32,
+        sign: i8,
+        initial: char,
+    }
+    let decoded = TestType {
+        ratio: 0.5,
+        sign: -1,
+        initial: 'é',
+    };
+
+    let cfg = serde_gvariant::config();
+    let encoded: Vec<u8> = vec![
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xe0, 0x3f, 0xff, 0xff, 0x00, 0x00, 0xe9, 0x00, 0x00,
+        0x00,
+    ];
+    let ser: Vec<u8> = cfg.serialize(&decoded).expect("mapping ser");
+    let de: TestType = cfg.deserialize_slice(&encoded[..]).expect("mapping de");
+    assert_eq!(ser, encoded);
+    assert_eq!(de, decoded);
+    let variant = cfg.serialize_variant(&decoded).expect("mapping to variant");
+    assert_eq!(variant.variant_type().to_string(), "(dnu)");
+    let de: TestType = serde_gvariant::from_variant(variant).expect("mapping from variant");
+    assert_eq!(de, decoded);
+
+    let cfg = cfg.char_type(CharType::String);
+    let ser: Vec<u8> = cfg.serialize(&decoded).expect("char string ser");
+    assert_eq!(&ser[10..], &[0xc3, 0xa9, 0x00]);
+    let de: TestType = cfg.deserialize_slice(&ser[..]).expect("char string de");
+    assert_eq!(de, decoded);
+    let variant = cfg
+        .serialize_variant(&decoded)
+        .expect("char string to variant");
+    assert_eq!(variant.variant_type().to_string(), "(dns)");
+    let de: TestType = serde_gvariant::from_variant(variant).expect("char string from variant");
+    assert_eq!(de, decoded);
+
+    let cfg = cfg.i8_type(I8Type::Reject);
+    assert!(cfg.serialize(&decoded).is_err());
+    assert!(cfg.deserialize_slice::<TestType>(&ser[..]).is_err());
+
+    // Out of range values are rejected on the way back.
+    let encoded = serde_gvariant::to_vec(&1e300f64).expect("f64 ser");
+    assert!(serde_gvariant::from_slice::<f32>(&encoded[..]).is_err());
+    let encoded = serde_gvariant::to_vec(&f64::INFINITY).expect("f64 ser");
+    let de: f32 = serde_gvariant::from_slice(&encoded[..]).expect("f32 de");
+    assert_eq!(de, f32::INFINITY);
+    let encoded = serde_gvariant::to_vec(&300i16).expect("i16 ser");
+    assert!(serde_gvariant::from_slice::<i8>(&encoded[..]).is_err());
+    let encoded = serde_gvariant::to_vec(&0xd800u32).expect("u32 ser");
+    assert!(serde_gvariant::from_slice::<char>(&encoded[..]).is_err());
+}
+
+#[test]
+fn test_any() {
+    #[derive(Debug, PartialEq, Deserialize)]
+    #[serde(untagged)]
+    enum Value {
+        Number(u32),
+        Text(String),
+    }
+
+    #[derive(Debug, PartialEq, Deserialize)]
+    struct Labelled {
+        value: serde_json::Value,
+        label: String,
+    }
+
+    // A `v` describes itself.
+    let encoded: Vec<u8> = vec![b'f', b'o', b'o', 0x00, 0x00, b's'];
+    let de: Value = serde_gvariant::from_slice(&encoded[..]).expect("untagged de");
+    assert_eq!(de, Value::Text("foo".into()));
+    let encoded: Vec<u8> = vec![0x2a, 0x00, 0x00, 0x00, 0x00, b'u'];
+    let de: Value = serde_gvariant::from_slice(&encoded[..]).expect("untagged de");
+    assert_eq!(de, Value::Number(42));
+
+    let encoded: Vec<u8> = vec![0x2a, 0x00, 0x00, 0x00, 0x00, b'u', b'a', b'b', 0x00, 0x06];
+    let de: Labelled = serde_gvariant::from_slice(&encoded[..]).expect("struct de");
+    assert_eq!(de.value, serde_json::json!(42));
+    assert_eq!(de.label, "ab");
+
+    // Otherwise, the signature is supplied.
+    let encoded: Vec<u8> = vec![b'a', 0x00, 0x01, 0x00, 0x02, 0x00, 0x02];
+    let cfg = serde_gvariant::config().signature(VariantType::parse("(saq)").unwrap());
+    let de: serde_json::Value = cfg.deserialize_slice(&encoded[..]).expect("json de");
+    assert_eq!(de, serde_json::json!(["a", [1, 2]]));
+}
+
+#[test]
+fn test_ignored_any() {
+    #[derive(Debug, Serialize)]
+    struct Full {
+        name: String,
+        extra: Vec<String>,
+        count: u32,
+    }
+
+    #[derive(Debug, Deserialize)]
+    struct Partial {
+        name: String,
+        _extra: IgnoredAny,
+        count: u32,
+    }
+
+    let full = Full {
+        name: "foo".into(),
+        extra: vec!["a".into(), "bc".into()],
+        count: 7,
+    };
+    let encoded = serde_gvariant::to_vec(&full).expect("struct ser");
+    let cfg = serde_gvariant::config().signature(VariantType::parse("(sasu)").unwrap());
+    let de: Partial = cfg.deserialize_slice(&encoded[..]).expect("struct de");
+    assert_eq!(de.name, "foo");
+    assert_eq!(de.count, 7);
+
+    // Without a signature, the size of ignored values is unknown.
+    let err = serde_gvariant::from_slice::<Partial>(&encoded[..]).unwrap_err();
+    assert!(matches!(err.kind(), ErrorKind::UnsupportedType(_)));
+
+    #[derive(Debug, Deserialize)]
+    struct Skipped {
+        _size: IgnoredAny,
+        name: String,
+    }
+    let encoded: Vec<u8> = vec![0x07, 0x00, 0x00, 0x00, b'a', 0x00];
+    let err = serde_gvariant::from_slice::<Skipped>(&encoded[..]).unwrap_err();
+    assert!(matches!(err.kind(), ErrorKind::UnsupportedType(_)));
+    let cfg = serde_gvariant::config().signature(VariantType::parse("(us)").unwrap());
+    let de: Skipped = cfg.deserialize_slice(&encoded[..]).expect("struct de");
+    assert_eq!(de.name, "a");
+
+    let encoded: Vec<u8> = vec![0x01, 0x00, 0x00, 0x00, 0x02, 0x00, 0x00, 0x00];
+    let err = serde_gvariant::from_slice::<Vec<IgnoredAny>>(&encoded[..]).unwrap_err();
+    assert!(matches!(err.kind(), ErrorKind::UnsupportedType(_)));
+    let cfg = serde_gvariant::config().signature(VariantType::parse("au").unwrap());
+    let de: Vec<IgnoredAny> = cfg.deserialize_slice(&encoded[..]).expect("array de");
+    assert_eq!(de.len(), 2);
+}
+
+#[test]
+fn test_canonical() {
+    use std::collections::{BTreeMap, HashMap};
+
+    #[derive(Serialize)]
+    struct Entry {
+        id: u8,
+        size: u32,
+        name: String,
+        tags: HashMap<String, u32>,
+    }
+
+    let cfg = serde_gvariant::config().canonical(true);
+    let tags: HashMap<String, u32> = (0..16).map(|i| (format!("t{}", i), i)).collect();
+    let sorted: BTreeMap<String, u32> = tags.clone().into_iter().collect();
+    let entry = Entry {
+        id: 1,
+        size: 2,
+        name: "ab".into(),
+        tags,
+    };
+    let encoded = cfg.serialize(&entry).expect("canonical ser");
+    assert_eq!(
+        encoded[..11],
+        [0x01, 0x00, 0x00, 0x00, 0x02, 0x00, 0x00, 0x00, b'a', b'b', 0x00]
+    );
+    assert_eq!(*encoded.last().unwrap(), 0x0b);
+
+    // Maps are sorted, whatever their iteration order.
+    let rehashed: HashMap<String, u32> = sorted.clone().into_iter().rev().collect();
+    assert_eq!(
+        cfg.serialize(&rehashed).unwrap(),
+        cfg.serialize(&sorted).unwrap()
+    );
+    assert_eq!(
+        serde_gvariant::to_vec(&rehashed).unwrap(),
+        cfg.serialize(&sorted).unwrap()
+    );
+    let ty = VariantType::parse("a{su}").unwrap();
+    let de: BTreeMap<String, u32> = cfg
+        .clone()
+        .signature(ty)
+        .deserialize_slice(&cfg.serialize(&rehashed).unwrap())
+        .expect("map de");
+    assert_eq!(de, sorted);
+
+    // Framing offsets widen as needed.
+    let long: Vec<String> = (0..100).map(|i| format!("{:03}", i)).collect();
+    let encoded = cfg.serialize(&long).expect("long array ser");
+    assert_eq!(encoded.len(), 100 * 4 + 100 * 2);
+    assert_eq!(serde_gvariant::to_vec(&long).unwrap(), encoded);
+    let de: Vec<String> = serde_gvariant::from_slice(&encoded[..]).expect("long array de");
+    assert_eq!(de, long);
+
+    // Their width depends on the whole container, offsets included.
+    let long = vec!["a".repeat(126); 2];
+    let encoded = serde_gvariant::to_vec(&long).expect("long array ser");
+    assert_eq!(encoded.len(), 2 * 127 + 2 * 2);
+    assert_eq!(cfg.serialize(&long).unwrap(), encoded);
+    let de: Vec<String> = serde_gvariant::from_slice(&encoded[..]).expect("long array de");
+    assert_eq!(de, long);
+
+    #[derive(Debug, Deserialize, Serialize, PartialEq)]
+    struct Long {
+        a: String,
+        b: String,
+        c: u8,
+    }
+    let long = Long {
+        a: long[0].clone(),
+        b: long[1].clone(),
+        c: 1,
+    };
+    let encoded = serde_gvariant::to_vec(&long).expect("long struct ser");
+    assert_eq!(encoded.len(), 2 * 127 + 1 + 2 * 2);
+    assert_eq!(cfg.serialize(&long).unwrap(), encoded);
+    let de: Long = serde_gvariant::from_slice(&encoded[..]).expect("long struct de");
+    assert_eq!(de, long);
+
+    let encoded = cfg.serialize(&Variant::from(42u32)).expect("variant ser");
+    assert_eq!(encoded, vec![0x2a, 0x00, 0x00, 0x00, 0x00, b'u']);
+
+    // Empty containers and `None` are laid out as without normalization.
+    #[derive(Debug, Deserialize, Serialize, PartialEq)]
+    struct Sparse {
+        a: u8,
+        b: Vec<String>,
+        c: u8,
+        d: BTreeMap<String, u32>,
+        e: Option<String>,
+        f: f64,
+    }
+    let sparse = Sparse {
+        a: 1,
+        b: vec![],
+        c: 2,
+        d: BTreeMap::new(),
+        e: None,
+        f: -0.0,
+    };
+    let ty = VariantType::parse("(yasya{su}msd)").unwrap();
+    let typed = cfg.clone().signature(ty.clone());
+    let encoded = typed.serialize(&sparse).expect("sparse ser");
+    assert_eq!(
+        encoded,
+        vec![1, 2, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 4, 4, 1]
+    );
+    let plain = serde_gvariant::config().signature(ty);
+    assert_eq!(plain.serialize(&sparse).unwrap()[..8], encoded[..8]);
+    let de: Sparse = plain.deserialize_slice(&encoded).expect("sparse de");
+    assert_eq!(de, sparse);
+    assert!(de.f.is_sign_positive());
+    let encoded = cfg.serialize(&sparse).expect("untyped sparse ser");
+    assert_eq!(
+        encoded,
+        serde_gvariant::to_vec(&Sparse { f: 0.0, ..sparse }).unwrap()
+    );
+
+    #[derive(Serialize)]
+    struct Names {
+        a: u8,
+        b: Vec<String>,
+        c: u8,
+    }
+    let names = Names {
+        a: 1,
+        b: vec![],
+        c: 2,
+    };
+    assert_eq!(cfg.serialize(&names).unwrap(), vec![1, 2, 1]);
+    assert_eq!(serde_gvariant::to_vec(&names).unwrap(), vec![1, 2, 1]);
+}
+
+#[test]
+fn test_empty_alignment() {
+    #[derive(Debug, Deserialize, Serialize, PartialEq)]
+    struct Record {
+        a: u8,
+        b: Option<u64>,
+        c: String,
+    }
+
+    // Empty containers and `None` are aligned to their type, when known.
+    let record = Record {
+        a: 1,
+        b: None,
+        c: "x".into(),
+    };
+    let cfg = serde_gvariant::config().signature(VariantType::parse("(ymts)").unwrap());
+    let encoded = cfg.serialize(&record).expect("none ser");
+    assert_eq!(encoded, vec![1, 0, 0, 0, 0, 0, 0, 0, b'x', 0, 8]);
+    let de: Record = cfg.deserialize_slice(&encoded).expect("none de");
+    assert_eq!(de, record);
+
+    let cfg = serde_gvariant::config().signature(VariantType::parse("(yats)").unwrap());
+    let encoded = cfg
+        .serialize(&(1u8, Vec::<u64>::new(), "x"))
+        .expect("empty array ser");
+    assert_eq!(encoded, vec![1, 0, 0, 0, 0, 0, 0, 0, b'x', 0, 8]);
+
+    // Otherwise, they are not aligned at all.
+    let encoded = serde_gvariant::to_vec(&record).expect("untyped none ser");
+    assert_eq!(encoded, vec![1, b'x', 0, 1]);
+}
+
+#[test]
+fn test_nested_alignment() {
+    use std::collections::{BTreeMap, HashMap};
+
+    #[derive(Debug, Deserialize, Serialize, PartialEq)]
+    struct Record {
+        a: u8,
+        b: Vec<u64>,
+    }
+
+    // Arrays start after padding, which their first element tells.
+    let record = Record { a: 1, b: vec![5] };
+    let encoded = serde_gvariant::to_vec(&record).expect("(yat) ser");
+    assert_eq!(
+        encoded,
+        vec![1, 0, 0, 0, 0, 0, 0, 0, 5, 0, 0, 0, 0, 0, 0, 0]
+    );
+    let de: Record = serde_gvariant::from_slice(&encoded).expect("(yat) de");
+    assert_eq!(de, record);
+    let record = Record {
+        a: 1,
+        b: vec![5, 6, 7],
+    };
+    let encoded = serde_gvariant::to_vec(&record).expect("(yat) ser");
+    let de: Record = serde_gvariant::from_slice(&encoded).expect("(yat) de");
+    assert_eq!(de, record);
+
+    // So do dictionaries, with fixed-size entries.
+    let mut map = BTreeMap::new();
+    map.insert(1u8, vec![5u64]);
+    let encoded = serde_gvariant::to_vec(&map).expect("a{yat} ser");
+    let de: BTreeMap<u8, Vec<u64>> = serde_gvariant::from_slice(&encoded).expect("a{yat} de");
+    assert_eq!(de, map);
+    let sizes: HashMap<u64, u16> = vec![(100, 1), (700, 7)].into_iter().collect();
+    let encoded = serde_gvariant::to_vec(&(2u8, sizes.clone())).expect("(ya{tq}) ser");
+    let de: (u8, HashMap<u64, u16>) = serde_gvariant::from_slice(&encoded).expect("(ya{tq}) de");
+    assert_eq!(de, (2, sizes));
+
+    // Framing offsets of structures are relative to their aligned start.
+    let nested = (1u8, (2u64, "ab".to_string(), "c".to_string()));
+    let encoded = serde_gvariant::to_vec(&nested).expect("(y(tss)) ser");
+    assert_eq!(encoded[..10], [1, 0, 0, 0, 0, 0, 0, 0, 2, 0]);
+    let de: (u8, (u64, String, String)) =
+        serde_gvariant::from_slice(&encoded).expect("(y(tss)) de");
+    assert_eq!(de, nested);
+
+    // Values laid out before siblings tell their type are laid out again.
+    let records = vec![(1u8, Vec::<u64>::new()), (2u8, vec![7u64])];
+    let encoded = serde_gvariant::to_vec(&records).expect("a(yat) ser");
+    let signature = VariantType::parse("a(yat)").unwrap();
+    let cfg = serde_gvariant::config().signature(signature);
+    assert_eq!(encoded, cfg.serialize(&records).expect("a(yat) typed ser"));
+    let de: Vec<(u8, Vec<u64>)> = cfg.deserialize_slice(&encoded).expect("a(yat) de");
+    assert_eq!(de, records);
+    let (_, signature) = serde_gvariant::to_vec_with_signature(&records).expect("a(yat) sig");
+    assert_eq!(signature, "a(yat)");
+
+    // Without a signature, elements are aligned as the first one tells: when
+    // a later one needs more, the data is rejected rather than misread.
+    let err = serde_gvariant::from_slice::<Vec<(u8, Vec<u64>)>>(&encoded).unwrap_err();
+    assert!(
+        matches!(err.kind(), ErrorKind::InvalidFraming(_)),
+        "{}",
+        err
+    );
+    let misplaced = vec![
+        1, 0, 0, 0, 0, 0, 0, 0, 2, 0, 0, 0, 0, 0, 0, 0, 7, 0, 0, 0, 0, 0, 0, 0, 1, 24,
+    ];
+    let err = serde_gvariant::from_slice::<Vec<(u8, Vec<u64>)>>(&misplaced).unwrap_err();
+    assert!(
+        matches!(err.kind(), ErrorKind::UnsupportedType(_)),
+        "{}",
+        err
+    );
+
+    let mut map = BTreeMap::new();
+    map.insert(1u8, Vec::<u64>::new());
+    map.insert(2u8, vec![7u64]);
+    let encoded = serde_gvariant::to_vec(&map).expect("a{yat} ser");
+    let signature = VariantType::parse("a{yat}").unwrap();
+    let cfg = serde_gvariant::config().signature(signature);
+    assert_eq!(encoded, cfg.serialize(&map).expect("a{yat} typed ser"));
+    let de: BTreeMap<u8, Vec<u64>> = cfg.deserialize_slice(&encoded).expect("a{yat} de");
+    assert_eq!(de, map);
+}
+
+#[test]
+fn test_signature() {
+    #[derive(Serialize)]
+    struct Record {
+        id: u32,
+        names: Vec<String>,
+        flags: u8,
+    }
+
+    let record = Record {
+        id: 1,
+        names: vec!["a".into(), "bc".into()],
+        flags: 2,
+    };
+    let (encoded, signature) = serde_gvariant::to_vec_with_signature(&record).expect("ser");
+    assert_eq!(signature, "(uasy)");
+    let ty = VariantType::parse(&signature).unwrap();
+    let decoded = VariantRef::new(&encoded, ty).to_variant().expect("decode");
+    assert_eq!(decoded, serde_gvariant::to_variant(&record).unwrap());
+
+    fn signature_of<T: serde::Serialize>(value: &T) -> String {
+        serde_gvariant::to_vec_with_signature(value).unwrap().1
+    }
+    assert_eq!(signature_of(&Some("a")), "ms");
+    assert_eq!(signature_of(&vec![None, Some("a")]), "ams");
+    assert_eq!(signature_of(&serde_bytes::ByteBuf::from(vec![1])), "ay");
+    assert_eq!(signature_of(&vec!['x']), "au");
+    assert_eq!(signature_of(&-1i8), "n");
+    assert_eq!(signature_of(&()), "()");
+    assert_eq!(signature_of(&Handle(0)), "h");
+
+    // Types of empty containers and `None` come from the configured signature.
+    let err = serde_gvariant::to_vec_with_signature(&Vec::<u32>::new()).unwrap_err();
+    assert!(matches!(err.kind(), ErrorKind::UnsupportedType(_)));
+    assert!(serde_gvariant::to_vec_with_signature(&None::<u32>).is_err());
+    assert!(serde_gvariant::to_vec_with_signature(&vec![None::<u32>, None]).is_err());
+    let cfg = serde_gvariant::config().signature(VariantType::parse("au").unwrap());
+    let (encoded, signature) = cfg.serialize_with_signature(&Vec::<u32>::new()).unwrap();
+    assert!(encoded.is_empty());
+    assert_eq!(signature, "au");
+    let empty = Record {
+        id: 1,
+        names: vec![],
+        flags: 2,
+    };
+    let cfg = serde_gvariant::config().signature(VariantType::parse("(uasy)").unwrap());
+    assert_eq!(cfg.serialize_with_signature(&empty).unwrap().1, "(uasy)");
+    let cfg = serde_gvariant::config().signature(VariantType::parse("(uauy)").unwrap());
+    let err = cfg.serialize(&record).unwrap_err();
+    assert!(matches!(err.kind(), ErrorKind::SignatureMismatch(_, _)));
+    assert_eq!(err.path(), Some("names[0]"));
+
+    // Elements of arrays share a type.
+    #[derive(Serialize)]
+    #[serde(untagged)]
+    enum Mixed {
+        A(u32),
+        B(&'static str),
+    }
+    let err = serde_gvariant::to_vec(&vec![Mixed::A(1), Mixed::B("x")]).unwrap_err();
+    match err.kind() {
+        ErrorKind::SignatureMismatch(expected, actual) => {
+            assert_eq!(expected, "u");
+            assert_eq!(actual, "s");
+        }
+        k => panic!("unexpected error kind: {:?}", k),
+    }
+    assert_eq!(err.path(), Some("[1]"));
+}
+
 #[test]
 fn test_array() {
     let encoded: Vec<u8> = vec![b'a', 0x00, b'b', 0x00, 0x02, 0x04];
@@ -149,6 +1051,32 @@ fn test_array() {
     assert_eq!(de, decoded);
 }
 
+#[test]
+fn test_read_non_seekable() {
+    let encoded: Vec<u8> = vec![b'a', 0x00, b'b', 0x00, 0x02, 0x04];
+    let decoded: Vec<String> = vec!["a".into(), "b".into()];
+    // `Read` for `&[u8]` consumes the slice, there is no `Seek`.
+    let de: Vec<String> = serde_gvariant::from_read(&encoded[..]).expect("Vector de");
+    assert_eq!(de, decoded);
+
+    let cfg = serde_gvariant::config().max_input_len(encoded.len());
+    let de: Vec<String> = cfg.deserialize_read(&encoded[..]).expect("Vector de");
+    assert_eq!(de, decoded);
+    let cfg = serde_gvariant::config().max_input_len(encoded.len() - 1);
+    let res: Result<Vec<String>, _> = cfg.deserialize_read(&encoded[..]);
+    assert!(res.is_err());
+
+    // Input decoded through a signature is bounded too.
+    let cfg = cfg.signature(VariantType::parse("as").unwrap());
+    let res: Result<Vec<String>, _> = cfg.deserialize_reader(std::io::Cursor::new(&encoded));
+    assert!(res.is_err());
+    let cfg = cfg.max_input_len(encoded.len());
+    let de: Vec<String> = cfg
+        .deserialize_reader(std::io::Cursor::new(&encoded))
+        .expect("Vector de");
+    assert_eq!(de, decoded);
+}
+
 #[test]
 fn test_variant_01() {
     {
@@ -0,0 +1,117 @@
+extern crate serde;
+#[macro_use]
+extern crate serde_derive;
+extern crate serde_gvariant;
+
+use serde_gvariant::errors::ErrorKind;
+use serde_gvariant::{I8Type, VarDict, Variant, VariantRef, VariantType};
+
+#[derive(Debug, Deserialize, Serialize)]
+struct Outer {
+    name: String,
+    items: Vec<Inner>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+struct Inner {
+    tag: i8,
+}
+
+#[test]
+fn test_unsupported_path() {
+    let value = Outer {
+        name: "foo".to_string(),
+        items: vec![Inner { tag: -1 }],
+    };
+    let cfg = serde_gvariant::config().i8_type(I8Type::Reject);
+    for err in &[
+        cfg.serialize(&value).unwrap_err(),
+        cfg.serialize_variant(&value).unwrap_err(),
+    ] {
+        assert!(matches!(err.kind(), ErrorKind::UnsupportedType(ty) if ty == "i8"));
+        assert_eq!(err.path(), Some("items[0].tag"));
+    }
+}
+
+#[test]
+fn test_framing_offset() {
+    // The first string lacks its nul terminator.
+    let encoded: Vec<u8> = vec![b'a', b'b', 0x00, 0x02, 0x03];
+    let err = serde_gvariant::from_slice::<Vec<String>>(&encoded).unwrap_err();
+    assert!(matches!(err.kind(), ErrorKind::InvalidFraming(_)));
+    assert_eq!(err.path(), Some("[0]"));
+    assert_eq!(err.offset(), Some(0));
+    assert_eq!(
+        err.to_string(),
+        "invalid framing: non-terminated string at '[0]' (byte offset 0x0)"
+    );
+}
+
+#[test]
+fn test_decode_utf8() {
+    let encoded: Vec<u8> = vec![b'a', 0x00, b'b', 0xff, 0x00, 0x02, 0x05];
+    let ty = VariantType::parse("as").unwrap();
+    let err = VariantRef::new(&encoded, ty).to_variant().unwrap_err();
+    assert!(matches!(err.kind(), ErrorKind::InvalidUtf8));
+    assert_eq!(err.path(), Some("[1]"));
+    assert_eq!(err.offset(), Some(3));
+}
+
+#[test]
+fn test_deserialize_utf8() {
+    let encoded: Vec<u8> = vec![b'a', 0x00, b'b', 0xff, 0x00, 0x02, 0x05];
+    let borrowed = serde_gvariant::from_slice::<Vec<&str>>(&encoded).unwrap_err();
+    let owned =
+        serde_gvariant::from_reader::<_, Vec<String>>(std::io::Cursor::new(&encoded)).unwrap_err();
+    for err in &[borrowed, owned] {
+        assert!(matches!(err.kind(), ErrorKind::InvalidUtf8));
+        assert_eq!(err.path(), Some("[1]"));
+        assert_eq!(err.offset(), Some(3));
+    }
+}
+
+#[test]
+fn test_variant_path() {
+    #[derive(Debug, Deserialize)]
+    #[allow(dead_code)]
+    struct Metadata {
+        version: u32,
+    }
+
+    let mut dict = VarDict::new();
+    dict.insert("version", "1.0");
+    let err = serde_gvariant::from_variant::<Metadata>(Variant::from(dict)).unwrap_err();
+    assert!(matches!(err.kind(), ErrorKind::Message(_)));
+    assert_eq!(err.path(), Some("version"));
+    assert_eq!(err.offset(), None);
+
+    let value = Variant::from(vec![vec![1u8], vec![]]);
+    let err = serde_gvariant::from_variant::<Vec<Vec<String>>>(value).unwrap_err();
+    assert_eq!(err.path(), Some("[0][0]"));
+}
+
+#[test]
+fn test_limit_exceeded() {
+    let encoded: Vec<u8> = vec![b'a', 0x00, b'b', 0x00, 0x02, 0x04];
+    let cfg = serde_gvariant::config().max_input_len(encoded.len() - 1);
+    let err = cfg
+        .deserialize_read::<_, Vec<String>>(&encoded[..])
+        .unwrap_err();
+    assert!(matches!(err.kind(), ErrorKind::LimitExceeded(_)));
+
+    let err = VariantType::parse("a(s").unwrap_err();
+    assert!(matches!(err.kind(), ErrorKind::InvalidSignature(_)));
+}
+
+#[test]
+fn test_std_error() {
+    let err = serde_gvariant::from_slice::<u32>(&[0x01]).unwrap_err();
+    assert!(matches!(err.kind(), ErrorKind::UnexpectedEof));
+    let err: Box<dyn std::error::Error> = Box::new(err);
+    assert!(err.source().is_none());
+
+    let io = std::io::Error::other("boom");
+    let err = serde_gvariant::errors::Error::from(io);
+    assert!(matches!(err.kind(), ErrorKind::Io(_)));
+    assert!(std::error::Error::source(&err).is_some());
+}
@@ -42,7 +42,7 @@ fn test_struct() {
     struct TestType {
         first: String,
         second: i32,
-    };
+    }
     let encoded: Vec<u8> = vec![b'f', b'o', b'o', 0x00, 0xff, 0xff, 0xff, 0xff, 0x04];
     let decoded = TestType {
         first: "foo".to_string(),
@@ -60,7 +60,7 @@ fn test_simple_struct() {
     struct TestType {
         first: u8,
         second: u8,
-    };
+    }
     let encoded: Vec<u8> = vec![0x70, 0x80];
     let decoded = TestType {
         first: 0x70,
@@ -0,0 +1,116 @@
+extern crate serde_gvariant;
+
+use serde_gvariant::errors::ErrorKind;
+use serde_gvariant::{Role, Variant};
+
+#[test]
+fn test_explain() {
+    let value = (7u32, vec!["ab", "c"], Variant::from(true));
+    let (encoded, signature) = serde_gvariant::config()
+        .canonical(true)
+        .serialize_with_signature(&value)
+        .unwrap();
+    let report = serde_gvariant::explain(&encoded, &signature);
+    assert!(report.is_normal());
+    let roles: Vec<_> = report
+        .regions()
+        .iter()
+        .map(|r| (r.range.clone(), r.role, r.path.as_str()))
+        .collect();
+    assert_eq!(
+        roles,
+        vec![
+            (0..4, Role::Value, "[0]"),
+            (4..6, Role::Value, "[1][0]"),
+            (6..7, Role::Nul, "[1][0]"),
+            (7..8, Role::Value, "[1][1]"),
+            (8..9, Role::Nul, "[1][1]"),
+            (9..10, Role::FramingOffset { width: 1 }, "[1]"),
+            (10..11, Role::FramingOffset { width: 1 }, "[1]"),
+            (11..16, Role::Padding, ""),
+            (16..17, Role::Value, "[2][0]"),
+            (17..19, Role::SignatureTrailer, "[2]"),
+            (19..20, Role::FramingOffset { width: 1 }, ""),
+        ]
+    );
+    let rendered = report.to_string();
+    assert!(
+        rendered.contains("00000004  61 62                    value     s      [1][0] = \"ab\"\n")
+    );
+    assert!(rendered.contains("00000011  00 62                    signature v      [2] = \"b\"\n"));
+
+    // Non-normal bytes are flagged.
+    let mut bad = encoded.clone();
+    bad[12] = 0xff;
+    bad[16] = 0x02;
+    let report = serde_gvariant::explain(&bad, &signature);
+    assert!(!report.is_normal());
+    assert!(report.error().is_none());
+    let issues: Vec<_> = report
+        .regions()
+        .iter()
+        .filter_map(|r| r.issue.as_deref().map(|issue| (r.range.start, issue)))
+        .collect();
+    assert_eq!(
+        issues,
+        vec![(11, "non-zero padding"), (16, "boolean neither 0 nor 1")]
+    );
+
+    // Inconsistent framing stops decoding the container, without failing.
+    let mut bad = encoded.clone();
+    bad[19] = 0x30;
+    let report = serde_gvariant::explain(&bad, &signature);
+    let err = report.error().unwrap();
+    assert!(matches!(err.kind(), ErrorKind::InvalidFraming(_)));
+    let roles: Vec<_> = report
+        .regions()
+        .iter()
+        .map(|r| (r.range.clone(), r.role, r.issue.as_deref()))
+        .collect();
+    assert_eq!(
+        roles,
+        vec![
+            (0..19, Role::Unknown, Some("decoding stopped here")),
+            (
+                19..20,
+                Role::FramingOffset { width: 1 },
+                Some("inconsistent framing offsets")
+            ),
+        ]
+    );
+    assert!(report.to_string().ends_with("(byte offset 0x4)\n"));
+
+    // Other containers are still decoded, as well as earlier children.
+    let value = (7u32, vec!["ab", "c", "d"], Variant::from(true));
+    let (encoded, signature) = serde_gvariant::config()
+        .canonical(true)
+        .serialize_with_signature(&value)
+        .unwrap();
+    let mut bad = encoded.clone();
+    bad[12] = 0x04;
+    let report = serde_gvariant::explain(&bad, &signature);
+    let err = report.error().unwrap();
+    assert!(matches!(err.kind(), ErrorKind::InvalidFraming(_)));
+    assert_eq!(err.path(), Some("[1][1]"));
+    let roles: Vec<_> = report
+        .regions()
+        .iter()
+        .map(|r| (r.range.clone(), r.role, r.path.as_str()))
+        .collect();
+    assert_eq!(
+        roles,
+        vec![
+            (0..4, Role::Value, "[0]"),
+            (4..6, Role::Value, "[1][0]"),
+            (6..7, Role::Nul, "[1][0]"),
+            (7..11, Role::Unknown, "[1]"),
+            (11..12, Role::FramingOffset { width: 1 }, "[1]"),
+            (12..13, Role::FramingOffset { width: 1 }, "[1]"),
+            (13..14, Role::FramingOffset { width: 1 }, "[1]"),
+            (14..16, Role::Padding, ""),
+            (16..17, Role::Value, "[2][0]"),
+            (17..19, Role::SignatureTrailer, "[2]"),
+            (19..20, Role::FramingOffset { width: 1 }, ""),
+        ]
+    );
+}
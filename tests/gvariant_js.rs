@@ -165,7 +165,7 @@ fn test_js_large_integer() {
             let de_i64: i64 = serde_gvariant::from_slice(&t.0[..]).expect("i64 de");
             let ser_i64: Vec<u8> = serde_gvariant::to_vec(&t.1).expect("i64 ser");
             assert_eq!(ser_i64, t.0);
-            assert_eq!(de_i64, t.1 as i64);
+            assert_eq!(de_i64, t.1);
         }
     }
 }
@@ -0,0 +1,66 @@
+#[macro_use]
+extern crate serde_derive;
+extern crate serde_gvariant;
+
+use serde_gvariant::{Handle, Variant, VariantType};
+
+#[test]
+fn test_handle() {
+    let encoded = serde_gvariant::to_vec(&Handle(3)).expect("handle ser");
+    assert_eq!(encoded, vec![0x03, 0x00, 0x00, 0x00]);
+    let de: Handle = serde_gvariant::from_slice(&encoded[..]).expect("handle de");
+    assert_eq!(de, Handle(3));
+
+    let value = serde_gvariant::to_variant(&Handle(3)).expect("handle to_variant");
+    assert_eq!(value, Variant::Handle(Handle(3)));
+    assert_eq!(value.signature(), "h");
+    let de: Handle = serde_gvariant::from_variant(value).expect("handle from_variant");
+    assert_eq!(de, Handle(3));
+
+    let encoded: Vec<u8> = vec![0x03, 0x00, 0x00, 0x00, 0x00, b'h'];
+    let de: Variant = serde_gvariant::from_slice(&encoded[..]).expect("variant de");
+    assert_eq!(de, Variant::Handle(Handle(3)));
+    assert_eq!(VariantType::parse("ah").unwrap().to_string(), "ah");
+}
+
+#[cfg(unix)]
+#[test]
+fn test_fds() {
+    use serde_gvariant::Fd;
+    use std::fs::File;
+    use std::io::Read;
+    use std::os::fd::OwnedFd;
+
+    #[derive(Debug, Deserialize, Serialize)]
+    struct Message {
+        name: String,
+        fds: Vec<Fd>,
+    }
+
+    let open = || Fd::from(OwnedFd::from(File::open("Cargo.toml").unwrap()));
+    let msg = Message {
+        name: "foo".into(),
+        fds: vec![open(), open()],
+    };
+    assert!(serde_gvariant::to_vec(&msg).is_err());
+
+    let cfg = serde_gvariant::config();
+    let (encoded, fds) = cfg.serialize_with_fds(&msg).expect("fds ser");
+    assert_eq!(fds.len(), 2);
+    assert!(serde_gvariant::from_slice::<Message>(&encoded).is_err());
+    let de: Message = cfg
+        .deserialize_slice_with_fds(&encoded, fds)
+        .expect("fds de");
+    assert_eq!(de.name, "foo");
+
+    let mut content = String::new();
+    let mut file = File::from(OwnedFd::from(de.fds.into_iter().nth(1).unwrap()));
+    file.read_to_string(&mut content).unwrap();
+    assert!(content.starts_with("[package]"));
+
+    // Each descriptor is taken once.
+    let encoded = serde_gvariant::to_vec(&vec![Handle(0), Handle(0)]).unwrap();
+    let fds = vec![OwnedFd::from(File::open("Cargo.toml").unwrap())];
+    let res = cfg.deserialize_slice_with_fds::<Vec<Fd>>(&encoded, fds);
+    assert!(res.is_err());
+}
@@ -3,7 +3,7 @@ extern crate serde_bytes;
 extern crate serde_derive;
 extern crate serde_gvariant;
 
-use serde_gvariant::{Array, Variant};
+use serde_gvariant::{VarDict, Variant};
 use std::io::{Read, Seek};
 use std::{fs, io};
 
@@ -49,12 +49,30 @@ struct OstreeDirtreeDir {
     meta_checksum: Vec<u8>,
 }
 
+/// An ostree dirtree object, borrowing from its serialized form.
+#[cfg(feature = "mmap")]
+#[derive(Debug, Deserialize, PartialEq)]
+struct OstreeDirtreeRef<'a> {
+    #[serde(borrow)]
+    files: Vec<OstreeDirtreeFileRef<'a>>,
+    dirs: Vec<OstreeDirtreeDir>,
+}
+
+/// An ostree file object, borrowing from its serialized form.
+#[cfg(feature = "mmap")]
+#[derive(Debug, Deserialize, PartialEq)]
+struct OstreeDirtreeFileRef<'a> {
+    filename: &'a str,
+    #[serde(with = "serde_bytes")]
+    checksum: &'a [u8],
+}
+
 /* Commit */
 
 /// An ostree commit object.
 #[derive(Debug, Deserialize, PartialEq, Serialize)]
 struct OstreeCommit {
-    metadata: Vec<(String, Variant)>,
+    metadata: VarDict,
     parent_checksum: Vec<u8>,
     related_objs: Vec<OstreeCommitRelated>,
     subject: String,
@@ -84,8 +102,8 @@ fn test_basic_01_dirmeta() {
 
     let cfg = serde_gvariant::Config::new().network_endian(true);
     let _de: OstreeDirmeta = cfg.deserialize_reader(bufrd).unwrap();
-    //let ser = cfg.serialize(&_de).unwrap();
-    //assert_eq!(content, ser);
+    let ser = cfg.serialize(&_de).unwrap();
+    assert_eq!(content, ser);
 
     let exp = OstreeDirmeta {
         uid: 1000,
@@ -107,8 +125,8 @@ fn test_basic_01_dirtree() {
 
     let cfg = serde_gvariant::Config::new().network_endian(true);
     let _de: OstreeDirtree = cfg.deserialize_reader(bufrd).unwrap();
-    //let ser = cfg.serialize(&_de).unwrap();
-    //assert_eq!(content, ser);
+    let ser = cfg.serialize(&_de).unwrap();
+    assert_eq!(content, ser);
 
     let exp = OstreeDirtree {
         files: vec![OstreeDirtreeFile {
@@ -134,16 +152,13 @@ fn test_basic_01_commit() {
 
     let cfg = serde_gvariant::Config::new().network_endian(true);
     let _de: OstreeCommit = cfg.deserialize_reader(bufrd).unwrap();
-    //let ser = cfg.serialize(&_de).unwrap();
-    //assert_eq!(content, ser);
+    let ser = cfg.serialize(&_de).unwrap();
+    assert_eq!(content, ser);
 
+    let mut metadata = VarDict::new();
+    metadata.insert("ostree.ref-binding", vec!["master"]);
     let exp = OstreeCommit {
-        metadata: vec![(
-            "ostree.ref-binding".to_string(),
-            Array::from_elements(vec![Variant::String("master".to_string())])
-                .unwrap()
-                .into_variant(),
-        )],
+        metadata,
         parent_checksum: vec![],
         related_objs: vec![],
         subject: "".to_string(),
@@ -159,4 +174,53 @@ fn test_basic_01_commit() {
         ],
     };
     assert_eq!(exp, _de);
+    let refs: Option<Vec<String>> = _de.metadata.get("ostree.ref-binding").unwrap();
+    assert_eq!(refs, Some(vec!["master".to_string()]));
+}
+
+#[test]
+fn test_commit_roundtrip() {
+    let mut metadata = VarDict::new();
+    metadata.insert("ostree.ref-binding", Vec::<String>::new());
+    metadata.insert("version", "1");
+    // Encoded from a typed tree, as a `v` whose signature is then cut off.
+    let tree = Variant::from((
+        metadata,
+        Vec::<u8>::new(),
+        Vec::<(String, Vec<u8>)>::new(),
+        "subject",
+        "",
+        1527716235u64,
+        vec![1u8; 32],
+        vec![2u8; 32],
+    ));
+    let boxed = serde_gvariant::to_vec(&tree).unwrap();
+    let content = &boxed[..boxed.len() - tree.signature().len() - 1];
+
+    let de: OstreeCommit = serde_gvariant::from_slice(content).unwrap();
+    assert_eq!(de.subject, "subject");
+    assert_eq!(de.root_metadata, vec![2; 32]);
+    let ser = serde_gvariant::to_vec(&de).unwrap();
+    assert_eq!(content, &ser[..]);
+}
+
+#[cfg(feature = "mmap")]
+#[test]
+fn test_basic_01_dirtree_mmap() {
+    let sample = "tests/fixtures/ostree/basic-01.dirtree";
+    let owned: OstreeDirtree = unsafe { serde_gvariant::from_file(sample) }.unwrap();
+    assert_eq!(owned.files[0].filename, "foo.txt");
+
+    let map = unsafe { serde_gvariant::open_mmap(sample) }.unwrap();
+    let borrowed: OstreeDirtreeRef = map.deserialize().unwrap();
+    assert!(borrowed.dirs.is_empty());
+    assert_eq!(borrowed.files.len(), 1);
+    let file = &borrowed.files[0];
+    assert_eq!(file.filename, "foo.txt");
+    assert_eq!(file.checksum, &owned.files[0].checksum[..]);
+
+    // Both point into the mapping.
+    let range = map.as_ptr_range();
+    assert!(range.contains(&file.filename.as_ptr()));
+    assert!(range.contains(&file.checksum.as_ptr()));
 }
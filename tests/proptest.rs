@@ -82,6 +82,18 @@ proptest! {
         prop_assert_eq!(input, &decoded);
     }
 
+    #[test]
+    fn testprop_canonical_variant(ref bytes in any::<Vec<u8>>()){
+        use serde_gvariant::Variant;
+        if let Ok(decoded) = serde_gvariant::from_slice::<Variant>(&bytes[..]) {
+            let cfg = serde_gvariant::config().canonical(true);
+            let encoded = cfg.serialize(&decoded)?;
+            let redecoded: Variant = serde_gvariant::from_slice(&encoded[..])?;
+            prop_assert_eq!(&decoded, &redecoded);
+            prop_assert_eq!(encoded, cfg.serialize(&redecoded)?);
+        }
+    }
+
     #[test]
     fn testprop_nonpanic_variant(ref bytes in any::<Vec<u8>>()){
         use serde_gvariant::errors::Result;
@@ -0,0 +1,743 @@
+extern crate serde;
+extern crate serde_bytes;
+#[macro_use]
+extern crate serde_derive;
+extern crate serde_gvariant;
+extern crate serde_json;
+
+use serde_gvariant::errors::ErrorKind;
+use serde_gvariant::{
+    Array, Change, Dictionary, Handle, Maybe, Structure, VarDict, Variant, VariantType,
+};
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+
+#[test]
+fn test_from_basic() {
+    assert_eq!(Variant::from(true), Variant::Bool(true));
+    assert_eq!(Variant::from(42u8), Variant::U8(42));
+    assert_eq!(Variant::from(42u32), Variant::U32(42));
+    assert_eq!(Variant::from(-42i64), Variant::I64(-42));
+    assert_eq!(Variant::from(0.5f64), Variant::F64(0.5));
+    assert_eq!(Variant::from(0.5f32), Variant::F64(0.5));
+    assert_eq!(Variant::from('x'), Variant::U32(0x78));
+    assert_eq!(Variant::from("foo"), Variant::String("foo".to_string()));
+    assert_eq!(
+        Variant::from(Box::new(Variant::U16(7))),
+        Variant::Variant(Box::new(Variant::U16(7)))
+    );
+}
+
+#[test]
+fn test_from_containers() {
+    let array = Variant::from(vec!["a", "b"]);
+    let exp = Array::from_elements(vec![
+        Variant::String("a".to_string()),
+        Variant::String("b".to_string()),
+    ])
+    .unwrap()
+    .into_variant();
+    assert_eq!(array, exp);
+    assert_eq!(array.signature(), "as");
+
+    assert_eq!(
+        Variant::from(Some(3u32)).as_option(),
+        Some(Some(&Variant::U32(3)))
+    );
+    assert_eq!(Variant::from(None::<u32>).as_option(), Some(None));
+
+    let mut map = BTreeMap::new();
+    map.insert("key".to_string(), 1u64);
+    let dict = Variant::from(map.clone());
+    assert_eq!(dict.get::<BTreeMap<String, u64>>().unwrap(), map);
+
+    let tuple = Variant::from(("foo", 1u32, true));
+    assert_eq!(tuple.signature(), "(sub)");
+    let back: (String, u32, bool) = tuple.get().unwrap();
+    assert_eq!(back, ("foo".to_string(), 1, true));
+}
+
+#[test]
+fn test_container_types() {
+    assert_eq!(Variant::from(Vec::<String>::new()).signature(), "as");
+    assert_eq!(Variant::from(None::<u32>).signature(), "mu");
+    assert_eq!(
+        Variant::from(BTreeMap::<String, u8>::new()).signature(),
+        "a{sy}"
+    );
+    assert_eq!(Variant::from(vec![Variant::U8(1)]).signature(), "av");
+    assert_eq!(Variant::from((1u8, Variant::U8(1))).signature(), "(yv)");
+
+    let empty = Array::with_type(VariantType::String, vec![]).unwrap();
+    assert_eq!(empty.element_type(), &VariantType::String);
+    assert_eq!(empty.into_variant(), Variant::from(Vec::<&str>::new()));
+    assert_eq!(Array::from_elements(vec![]).unwrap().signature(), "av");
+    assert_eq!(
+        Dictionary::from_map(BTreeMap::new()).unwrap().signature(),
+        "a{sv}"
+    );
+
+    let err = Array::from_elements(vec![Variant::U8(1), Variant::U32(2)]).unwrap_err();
+    match err.kind() {
+        ErrorKind::SignatureMismatch(expected, actual) => {
+            assert_eq!(expected, "y");
+            assert_eq!(actual, "u");
+        }
+        k => panic!("unexpected error kind: {:?}", k),
+    }
+
+    let mut map = BTreeMap::new();
+    map.insert(Variant::from("a"), Variant::U8(1));
+    map.insert(Variant::from("b"), Variant::from("x"));
+    assert!(Dictionary::from_map(map).is_err());
+    assert!(
+        Dictionary::with_types(VariantType::Variant, VariantType::U8, BTreeMap::new()).is_err()
+    );
+
+    let maybe = Maybe::nothing(VariantType::parse("as").unwrap());
+    assert_eq!(maybe.signature(), "mas");
+    assert!(Maybe::with_type(VariantType::U8, Some(Variant::U16(1))).is_err());
+}
+
+#[test]
+fn test_variant_type_parse() {
+    for sig in &["b", "as", "a{sv}", "(uu)", "mmay", "()", "(sa{sv}a(say))"] {
+        let ty = VariantType::parse(sig).unwrap();
+        assert_eq!(&ty.to_string(), sig);
+    }
+    for sig in &["", "a", "(u", "{vs}", "a{s}", "uu", "z"] {
+        assert!(VariantType::parse(sig).is_err(), "{}", sig);
+    }
+    let ty = VariantType::parse("(yu)").unwrap();
+    assert_eq!(ty.alignment(), 4);
+    assert_eq!(ty.fixed_size(), Some(8));
+    assert_eq!(VariantType::parse("()").unwrap().fixed_size(), Some(1));
+    assert_eq!(VariantType::parse("(ys)").unwrap().fixed_size(), None);
+}
+
+#[test]
+fn test_try_from() {
+    assert_eq!(u32::try_from(Variant::U32(5)).unwrap(), 5);
+    assert_eq!(String::try_from(Variant::from("bar")).unwrap(), "bar");
+    assert_eq!(
+        Variant::from(vec![1u8, 2, 3]).get::<Vec<u8>>().unwrap(),
+        vec![1, 2, 3]
+    );
+    assert_eq!(
+        Variant::from(Some(1i16)).get::<Option<i16>>().unwrap(),
+        Some(1)
+    );
+
+    let values = vec![Variant::U32(1), Variant::from("a")];
+    let array = Variant::from(values.clone());
+    assert_eq!(array.signature(), "av");
+    assert_eq!(array.get::<Vec<Variant>>().unwrap(), values);
+    assert_eq!(
+        Variant::from(vec![1u8, 2]).get::<Vec<Variant>>().unwrap(),
+        vec![Variant::U8(1), Variant::U8(2)]
+    );
+
+    let mut map = BTreeMap::new();
+    map.insert("a".to_string(), Variant::Bool(true));
+    let dict = Variant::from(map.clone());
+    let entries = vec![("a".to_string(), Variant::Bool(true))];
+    assert_eq!(dict.get::<BTreeMap<String, Variant>>().unwrap(), map);
+    let tuples = Variant::from(vec![("a", Variant::Bool(true))]);
+    assert_eq!(tuples.signature(), "a(sv)");
+    assert_eq!(tuples.get::<Vec<(String, Variant)>>().unwrap(), entries);
+}
+
+#[test]
+fn test_signature_mismatch() {
+    let err = u32::try_from(Variant::String("foo".to_string())).unwrap_err();
+    match err.kind() {
+        ErrorKind::SignatureMismatch(expected, actual) => {
+            assert_eq!(expected, "u");
+            assert_eq!(actual, "s");
+        }
+        k => panic!("unexpected error kind: {:?}", k),
+    }
+
+    let err = Variant::from(vec![1u8]).get::<Vec<String>>().unwrap_err();
+    match err.kind() {
+        ErrorKind::SignatureMismatch(expected, actual) => {
+            assert_eq!(expected, "s");
+            assert_eq!(actual, "y");
+        }
+        k => panic!("unexpected error kind: {:?}", k),
+    }
+}
+
+#[test]
+fn test_accessors() {
+    let value = Variant::ObjectPath("/org/gtk".to_string());
+    assert_eq!(value.as_str(), Some("/org/gtk"));
+    assert_eq!(value.as_u32(), None);
+    assert_eq!(Variant::I32(-1).as_i32(), Some(-1));
+    assert_eq!(Variant::Bool(false).as_bool(), Some(false));
+    assert!(Variant::from(vec![1u8]).as_array().is_some());
+    assert!(Variant::from(("a",)).as_structure().is_some());
+    assert_eq!(
+        Variant::from(Box::new(Variant::U8(1))).as_variant(),
+        Some(&Variant::U8(1))
+    );
+}
+
+#[test]
+fn test_to_variant_struct() {
+    #[derive(Serialize)]
+    struct TestType {
+        name: String,
+        size: u64,
+        tags: Vec<String>,
+        parent: Option<u32>,
+        extra: Variant,
+    }
+    let value = TestType {
+        name: "foo".to_string(),
+        size: 42,
+        tags: vec!["a".to_string()],
+        parent: None,
+        extra: Variant::Bool(true),
+    };
+    let variant = serde_gvariant::to_variant(&value).unwrap();
+    // Serde does not tell the inner type of `None`, it defaults to `mv`.
+    let exp = Variant::from((
+        "foo",
+        42u64,
+        vec!["a"],
+        None::<Variant>,
+        Box::new(Variant::Bool(true)),
+    ));
+    assert_eq!(variant, exp);
+}
+
+#[test]
+fn test_to_variant_map() {
+    let mut map = BTreeMap::new();
+    map.insert("one".to_string(), 1u32);
+    map.insert("two".to_string(), 2u32);
+    let variant = serde_gvariant::to_variant(&map).unwrap();
+    assert_eq!(variant, Variant::from(map));
+}
+
+#[test]
+fn test_to_variant_empty() {
+    let variant = serde_gvariant::to_variant(&vec![None, Some(1u32)]).unwrap();
+    assert_eq!(variant.signature(), "amu");
+    let variant = serde_gvariant::to_variant(&vec![vec![], vec![1i16]]).unwrap();
+    assert_eq!(variant.signature(), "aan");
+    let variant = serde_gvariant::to_variant(&Vec::<String>::new()).unwrap();
+    assert_eq!(variant.signature(), "av");
+
+    // Typed empty containers keep their type through a tree.
+    let value = Variant::from((
+        Vec::<String>::new(),
+        None::<u64>,
+        BTreeMap::<u32, bool>::new(),
+    ));
+    let variant = serde_gvariant::to_variant(&value).unwrap();
+    assert_eq!(variant, Variant::Variant(Box::new(value)));
+}
+
+#[test]
+fn test_to_vec_variant() {
+    #[derive(Debug, Deserialize, PartialEq, Serialize)]
+    struct Holder {
+        id: u8,
+        value: Variant,
+    }
+    let holder = Holder {
+        id: 1,
+        value: Variant::from((Vec::<String>::new(), 7u32)),
+    };
+    let bytes = serde_gvariant::to_vec(&holder).unwrap();
+    let mut exp = vec![1, 0, 0, 0, 0, 0, 0, 0, 7, 0, 0, 0, 0, 0];
+    exp.extend_from_slice(b"(asu)");
+    assert_eq!(bytes, exp);
+    let canonical = serde_gvariant::config().canonical(true);
+    assert_eq!(canonical.serialize(&holder).unwrap(), bytes);
+    let back: Holder = serde_gvariant::from_slice(&bytes).unwrap();
+    assert_eq!(back, holder);
+
+    // Unboxed containers are laid out as their own type, even when empty.
+    #[derive(Serialize)]
+    struct Unboxed {
+        id: u8,
+        list: Array,
+    }
+    let list = Array::try_from(Variant::from(Vec::<u64>::new())).unwrap();
+    let bytes = serde_gvariant::to_vec(&Unboxed { id: 1, list }).unwrap();
+    assert_eq!(bytes, vec![1, 0, 0, 0, 0, 0, 0, 0]);
+}
+
+#[test]
+fn test_to_variant_roundtrip_variant() {
+    let value = Variant::from(vec![Variant::ObjectPath("/a".to_string())]);
+    let variant = serde_gvariant::to_variant(&value).unwrap();
+    assert_eq!(variant, Variant::Variant(Box::new(value)));
+}
+
+#[test]
+fn test_from_variant_struct() {
+    #[derive(Debug, Deserialize, PartialEq, Serialize)]
+    struct TestType {
+        name: String,
+        size: u64,
+        tags: Vec<String>,
+        parent: Option<u32>,
+    }
+    let value = TestType {
+        name: "foo".to_string(),
+        size: 42,
+        tags: vec!["a".to_string(), "b".to_string()],
+        parent: Some(3),
+    };
+    let variant = serde_gvariant::to_variant(&value).unwrap();
+    let back: TestType = serde_gvariant::from_variant(variant).unwrap();
+    assert_eq!(back, value);
+}
+
+#[test]
+fn test_from_variant_vardict() {
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Metadata {
+        version: String,
+        timestamp: u64,
+    }
+    let mut map = BTreeMap::new();
+    map.insert("version", Variant::from("1.0"));
+    map.insert("timestamp", Variant::U64(1527716235));
+    let vardict = Variant::from(map);
+    let cfg = serde_gvariant::config();
+    let meta: Metadata = cfg.deserialize_variant(&vardict).unwrap();
+    let exp = Metadata {
+        version: "1.0".to_string(),
+        timestamp: 1527716235,
+    };
+    assert_eq!(meta, exp);
+}
+
+#[test]
+fn test_from_variant_config() {
+    use serde_gvariant::{CharType, I8Type};
+    let cfg = serde_gvariant::config();
+    assert_eq!(cfg.deserialize_variant::<i8>(&Variant::I16(3)).unwrap(), 3);
+    let cfg = cfg.i8_type(I8Type::Reject);
+    let err = cfg.deserialize_variant::<i8>(&Variant::I16(3)).unwrap_err();
+    assert!(matches!(err.kind(), ErrorKind::UnsupportedType(_)));
+    let err = cfg.deserialize_variant::<Vec<i8>>(&Variant::from(vec![3i16]));
+    assert!(err.is_err());
+
+    let cfg = serde_gvariant::config().char_type(CharType::String);
+    assert_eq!(
+        cfg.deserialize_variant::<char>(&Variant::from("x"))
+            .unwrap(),
+        'x'
+    );
+}
+
+#[test]
+fn test_from_variant_borrowed() {
+    use serde::Deserialize;
+    let variant = Variant::from(("foo", vec![1u8, 2]));
+    let (name, bytes): (&str, serde_bytes::ByteBuf) = Deserialize::deserialize(&variant).unwrap();
+    assert_eq!(name, "foo");
+    assert_eq!(bytes.into_vec(), vec![1, 2]);
+}
+
+#[test]
+fn test_from_variant_roundtrip_variant() {
+    let value = Variant::from(vec![
+        Variant::ObjectPath("/a".to_string()),
+        Variant::ObjectPath("/b".to_string()),
+    ]);
+    let boxed = serde_gvariant::to_variant(&value).unwrap();
+    let back: Variant = serde_gvariant::from_variant(boxed).unwrap();
+    assert_eq!(back, value);
+
+    let plain: Variant = serde_gvariant::from_variant(Variant::U32(5)).unwrap();
+    assert_eq!(plain, Variant::U32(5));
+
+    let value = Variant::from((
+        Vec::<String>::new(),
+        None::<u64>,
+        vec![Variant::from(BTreeMap::<u32, bool>::new())],
+    ));
+    let back: Variant =
+        serde_gvariant::from_variant(Variant::Variant(Box::new(value.clone()))).unwrap();
+    assert_eq!(back, value);
+    assert_eq!(back.signature(), "(asmtav)");
+}
+
+#[test]
+fn test_variant_json() {
+    let value = Variant::from(Structure::new(vec![
+        Variant::U32(7),
+        Variant::from(vec!["a", "b"]),
+        Variant::Option(Maybe::just(Variant::ObjectPath("/a".to_string()))),
+        Variant::Variant(Box::new(Variant::Bool(true))),
+    ]));
+    let json = serde_json::to_string(&value).unwrap();
+    assert_eq!(
+        json,
+        r#"{"Structure":[{"U32":7},{"Vec":["as",[{"String":"a"},{"String":"b"}]]},"#.to_string()
+            + r#"{"Option":["mo",{"ObjectPath":"/a"}]},{"Variant":{"Bool":true}}]}"#
+    );
+    let back: Variant = serde_json::from_str(&json).unwrap();
+    assert_eq!(back, value);
+
+    // Empty containers keep their types.
+    let value = Variant::from(Structure::new(vec![
+        Variant::Vec(Array::with_type(VariantType::String, vec![]).unwrap()),
+        Variant::Option(Maybe::nothing(VariantType::U32)),
+        Variant::from(BTreeMap::<u32, bool>::new()),
+    ]));
+    let json = serde_json::to_string(&value).unwrap();
+    assert_eq!(
+        json,
+        r#"{"Structure":[{"Vec":["as",[]]},{"Option":["mu",null]},{"Dictionary":["a{ub}",{}]}]}"#
+    );
+    let back: Variant = serde_json::from_str(&json).unwrap();
+    assert_eq!(back, value);
+    assert_eq!(back.signature(), "(asmua{ub})");
+
+    // Signatures must match their arm.
+    let err = serde_json::from_str::<Variant>(r#"{"Vec":["mu",null]}"#).unwrap_err();
+    assert!(
+        err.to_string().contains("'mu' is not the type of a Vec"),
+        "{}",
+        err
+    );
+}
+
+#[test]
+fn test_from_slice_empty_containers() {
+    // An empty `as`, boxed as `v`.
+    let value: Variant = serde_gvariant::from_slice(b"\x00as").unwrap();
+    assert_eq!(value, Variant::from(Vec::<String>::new()));
+    assert_eq!(value.signature(), "as");
+
+    // A `nothing` of type `mu`.
+    let value: Variant = serde_gvariant::from_slice(b"\x00mu").unwrap();
+    assert_eq!(value.signature(), "mu");
+
+    // A `(uas)` with two strings.
+    let data = b"\x2a\x00\x00\x00a\x00bc\x00\x02\x05\x00(uas)";
+    let value: Variant = serde_gvariant::from_slice(data).unwrap();
+    assert_eq!(value, Variant::from((42u32, vec!["a", "bc"])));
+}
+
+#[test]
+fn test_normalize() {
+    let nan = f64::from_bits(0x7ff8_0000_0000_0001);
+    let mut value = Variant::from((-0.0f64, vec![nan]));
+    value.normalize();
+    let fields = value.as_structure().unwrap();
+    assert_eq!(fields[0].as_f64().unwrap().to_bits(), 0);
+    let elems = fields[1].as_array().unwrap();
+    assert_eq!(elems[0].as_f64().unwrap().to_bits(), f64::NAN.to_bits());
+
+    // Equality agrees with ordering and hashing, for `Eq`.
+    assert_eq!(value, value.clone());
+    assert_eq!(Variant::F64(nan), Variant::F64(f64::NAN));
+    assert_eq!(Variant::F64(-0.0), Variant::F64(0.0));
+}
+
+#[test]
+fn test_array_api() {
+    let mut array: Array = vec![1u32, 2].into_iter().collect();
+    assert_eq!(array.len(), 2);
+    assert_eq!(array[1], Variant::U32(2));
+    assert_eq!(array.get(2), None);
+    array.push(Variant::U32(3)).unwrap();
+    assert!(array.push(Variant::U8(4)).is_err());
+    array.try_extend(vec![4u32]).unwrap();
+    let values: Vec<u32> = array.iter().filter_map(Variant::as_u32).collect();
+    assert_eq!(values, vec![1, 2, 3, 4]);
+    assert_eq!(array.pop(), Some(Variant::U32(4)));
+
+    let empty: Array = Vec::<String>::new().into_iter().collect();
+    assert!(empty.is_empty());
+    assert_eq!(empty.signature(), "as");
+
+    let elements: Vec<Variant> = array.clone().into();
+    assert_eq!(Array::try_from(elements).unwrap(), array);
+
+    // `v` items of the element type are unboxed, others are rejected.
+    array.try_extend(vec![Variant::U32(4)]).unwrap();
+    array
+        .try_extend(vec![Variant::Variant(Box::new(Variant::U32(5)))])
+        .unwrap();
+    assert!(array
+        .try_extend(vec![Variant::U32(6), Variant::U8(7)])
+        .is_err());
+    assert_eq!(array.len(), 5);
+    assert_eq!(array[4], Variant::U32(5));
+    assert_eq!(array.into_iter().count(), 5);
+
+    // Arrays of `v` box their items, unless already boxed.
+    let boxed: Array = vec![Variant::U8(1)].into_iter().collect();
+    assert_eq!(boxed.signature(), "av");
+    let mut boxed = Array::try_from_iter(vec![Variant::Variant(Box::new(Variant::U8(1)))]).unwrap();
+    assert_eq!(boxed.signature(), "av");
+    boxed.try_extend(vec![Variant::from("a")]).unwrap();
+    assert_eq!(boxed[1], Variant::Variant(Box::new(Variant::from("a"))));
+    let unboxed = Array::try_from_iter(vec![Variant::U8(1), Variant::U8(2)]).unwrap();
+    assert_eq!(unboxed.signature(), "ay");
+    assert!(Array::try_from_iter(vec![Variant::U8(1), Variant::U16(2)]).is_err());
+}
+
+#[test]
+fn test_dictionary_api() {
+    let mut dict: Dictionary = vec![("a", 1u8), ("b", 2)].into_iter().collect();
+    assert_eq!(dict.signature(), "a{sy}");
+    assert_eq!(dict.len(), 2);
+    assert_eq!(dict.get(&Variant::from("a")), Some(&Variant::U8(1)));
+    assert_eq!(dict[&Variant::from("b")], Variant::U8(2));
+    assert!(!dict.contains_key(&Variant::from("c")));
+
+    let old = dict.insert(Variant::from("a"), Variant::U8(10)).unwrap();
+    assert_eq!(old, Some(Variant::U8(1)));
+    assert!(dict.insert(Variant::U8(1), Variant::U8(1)).is_err());
+    assert!(dict.insert(Variant::from("c"), Variant::U32(1)).is_err());
+    dict.try_extend(vec![("c", 3u8)]).unwrap();
+    assert!(dict.try_extend(vec![("d", 4u32)]).is_err());
+    dict.try_extend(vec![("d", Variant::Variant(Box::new(Variant::U8(4))))])
+        .unwrap();
+    assert_eq!(dict.remove(&Variant::from("d")), Some(Variant::U8(4)));
+    let other = Dictionary::try_from_iter(vec![(Variant::from("a"), Variant::U8(1))]).unwrap();
+    assert_eq!(other.signature(), "a{sy}");
+    assert_eq!(dict.remove(&Variant::from("b")), Some(Variant::U8(2)));
+
+    let keys: Vec<&str> = dict.keys().filter_map(Variant::as_str).collect();
+    assert_eq!(keys, vec!["a", "c"]);
+    let map: BTreeMap<Variant, Variant> = dict.clone().into();
+    assert_eq!(Dictionary::try_from(map).unwrap(), dict);
+    assert_eq!((&dict).into_iter().count(), 2);
+
+    // Maps with `Variant` keys are checked at runtime instead of panicking.
+    let mut map = BTreeMap::new();
+    map.insert(Variant::from((1u8,)), Variant::U8(1));
+    assert!(Dictionary::try_from(map).is_err());
+    let map: BTreeMap<String, u8> = BTreeMap::new();
+    assert_eq!(Variant::from(map).signature(), "a{sy}");
+}
+
+#[test]
+fn test_structure_api() {
+    let mut s = Structure::new(vec![Variant::from("a"), Variant::U32(1)]);
+    assert_eq!(s.signature(), "(su)");
+    assert_eq!(s.len(), 2);
+    assert_eq!(s[0], Variant::from("a"));
+    s.push(Variant::Bool(true));
+    s.extend(vec![Variant::U8(0)]);
+    assert_eq!(s.get(3), Some(&Variant::U8(0)));
+    assert_eq!(s.signature(), "(suby)");
+
+    let fields: Vec<Variant> = s.clone().into();
+    assert_eq!(fields.into_iter().collect::<Structure>(), s);
+    assert!(Structure::new(vec![]).is_empty());
+    let sigs: Vec<String> = s.into_iter().map(|f| f.signature()).collect();
+    assert_eq!(sigs, vec!["s", "u", "b", "y"]);
+}
+
+#[test]
+fn test_vardict() {
+    let mut dict = VarDict::new();
+    assert_eq!(dict.insert("version", "1.0"), None);
+    dict.insert("timestamp", 1527716235u64);
+    dict.insert("refs", vec!["a", "b"]);
+    assert_eq!(dict.len(), 3);
+    assert_eq!(dict.get::<u64>("timestamp").unwrap(), Some(1527716235));
+    assert_eq!(dict.get::<String>("missing").unwrap(), None);
+    assert!(dict.get::<u32>("version").is_err());
+    assert_eq!(
+        dict.get_variant("refs").map(Variant::signature),
+        Some("as".to_string())
+    );
+    let keys: Vec<&str> = dict.keys().map(String::as_str).collect();
+    assert_eq!(keys, vec!["refs", "timestamp", "version"]);
+
+    let variant = serde_gvariant::to_variant(&dict).unwrap();
+    assert_eq!(variant.signature(), "a{sv}");
+    assert_eq!(variant, dict.clone().into_variant());
+    assert_eq!(VarDict::try_from(variant.clone()).unwrap(), dict);
+    assert!(VarDict::try_from(Variant::from(vec![1u8])).is_err());
+    let bytes = serde_gvariant::to_vec(&dict).unwrap();
+    let boxed = serde_gvariant::to_vec(&variant).unwrap();
+    assert_eq!(boxed, [&bytes[..], b"\0a{sv}"].concat());
+    let back: VarDict = serde_gvariant::from_variant(variant).unwrap();
+    assert_eq!(back, dict);
+
+    let back: VarDict = serde_gvariant::from_slice(&bytes).unwrap();
+    assert_eq!(back, dict);
+    let json = serde_json::to_string(&dict).unwrap();
+    assert!(json.starts_with(r#"{"refs":"#), "{}", json);
+    let back: VarDict = serde_json::from_str(&json).unwrap();
+    assert_eq!(back, dict);
+
+    assert_eq!(dict.remove("refs"), Some(Variant::from(vec!["a", "b"])));
+    assert!(!dict.contains_key("refs"));
+    // Boxed values are boxed once more, whichever way they go.
+    let boxed = Variant::Variant(Box::new(Variant::U32(1)));
+    dict.insert("boxed", boxed.clone());
+    let variant = dict.clone().into_variant();
+    assert_eq!(variant, serde_gvariant::to_variant(&dict).unwrap());
+    let entry = variant
+        .as_dictionary()
+        .unwrap()
+        .get(&Variant::from("boxed"));
+    assert_eq!(entry.map(Variant::signature), Some("v".to_string()));
+    assert_eq!(entry.and_then(Variant::as_variant), Some(&boxed));
+    assert_eq!(VarDict::try_from(variant.clone()).unwrap(), dict);
+    let bytes = serde_gvariant::to_vec(&dict).unwrap();
+    let encoded = serde_gvariant::to_vec(variant.as_dictionary().unwrap()).unwrap();
+    assert_eq!(encoded, bytes);
+    let back: VarDict = serde_gvariant::from_slice(&bytes).unwrap();
+    assert_eq!(back, dict);
+    assert_eq!(back.get_variant("boxed"), Some(&boxed));
+}
+
+#[test]
+fn test_diff() {
+    let mut old = VarDict::new();
+    old.insert("version", "1.0");
+    old.insert("ostree.ref-binding", vec!["stable"]);
+    old.insert("removed", 1u32);
+    let mut new = VarDict::new();
+    new.insert("version", "1.1");
+    new.insert("ostree.ref-binding", vec!["stable", "latest"]);
+    new.insert("added", (true, f64::NAN));
+    let (old, new) = (Variant::from(old), Variant::from(new));
+
+    let changes = old.diff(&new);
+    let rendered: Vec<_> = changes.iter().map(ToString::to_string).collect();
+    assert_eq!(
+        rendered,
+        vec![
+            "+ ['added']: <(true, NaN)>",
+            "+ ['ostree.ref-binding'][1]: 'latest'",
+            "- ['removed']: <1>",
+            "~ ['version']: '1.0' -> '1.1'",
+        ]
+    );
+    assert_eq!(changes[1].path(), "['ostree.ref-binding'][1]");
+    // Paths are queries for the changed values.
+    let latest = new.query(changes[1].path()).unwrap();
+    assert_eq!(latest, vec![&Variant::from("latest")]);
+    let keys = vec![
+        vec![Variant::from("it's \"quoted\""), Variant::from("a\\b/[c]")],
+        vec![Variant::F64(-1.5), Variant::F64(f64::NAN)],
+        vec![Variant::Handle(Handle(3))],
+        vec![Variant::Bool(false), Variant::Bool(true)],
+        vec![Variant::I64(-2)],
+    ];
+    for keys in keys {
+        let entries = |value: u32| {
+            let entries = keys.iter().map(|k| (k.clone(), Variant::U32(value)));
+            Variant::from(Dictionary::try_from_iter(entries).unwrap())
+        };
+        let (old, new) = (entries(0), entries(1));
+        let changes = old.diff(&new);
+        assert_eq!(changes.len(), keys.len());
+        for change in changes {
+            let found = new.query(change.path()).unwrap();
+            assert_eq!(found, vec![&Variant::U32(1)], "{}", change);
+        }
+    }
+    assert!(old.diff(&old).is_empty());
+    assert!(new.diff(&new.clone()).is_empty());
+
+    // Values of different types are changed as a whole.
+    let changes = Variant::from((1u32, "a")).diff(&Variant::from((1u64, "b")));
+    assert_eq!(
+        changes,
+        vec![
+            Change::Changed {
+                path: "[0]".to_string(),
+                old: Variant::U32(1),
+                new: Variant::U64(1),
+            },
+            Change::Changed {
+                path: "[1]".to_string(),
+                old: Variant::from("a"),
+                new: Variant::from("b"),
+            },
+        ]
+    );
+    assert_eq!(changes[0].to_string(), "~ [0]: uint32 1 -> uint64 1");
+    let changes = Variant::from(vec![1u8]).diff(&Variant::from(vec![2u16]));
+    assert_eq!(changes[0].to_string(), "~ /: @ay [0x01] -> @aq [2]");
+}
+
+#[test]
+fn test_query() {
+    let mut metadata = VarDict::new();
+    metadata.insert("version", "1.0");
+    metadata.insert("ostree.ref-binding", vec!["stable", "latest"]);
+    metadata.insert("size", Some(42u64));
+    let mut sizes = BTreeMap::new();
+    sizes.insert(Variant::U32(7), Variant::from("seven"));
+    let sizes = Dictionary::try_from(sizes).unwrap();
+    let mut commit = Variant::from(Structure::new(vec![metadata.into(), sizes.into()]));
+
+    let strings =
+        |matches: Vec<&Variant>| -> Vec<String> { matches.iter().map(|v| v.to_string()).collect() };
+    let refs = commit.query("/0/ostree.ref-binding/*").unwrap();
+    assert_eq!(strings(refs), vec!["'stable'", "'latest'"]);
+    let refs = commit.query("[0]['ostree.ref-binding'][1]").unwrap();
+    assert_eq!(strings(refs), vec!["'latest'"]);
+    let version = commit.query("0['version']").unwrap();
+    assert_eq!(version, vec![&Variant::from("1.0")]);
+    assert_eq!(commit.query("/0/size").unwrap(), vec![&Variant::U64(42)]);
+    assert_eq!(strings(commit.query("/1/7").unwrap()), vec!["'seven'"]);
+    assert_eq!(commit.query("/0/*").unwrap().len(), 3);
+    assert_eq!(commit.query("").unwrap(), vec![&commit]);
+    assert!(commit.query("/0/missing/*").unwrap().is_empty());
+    assert!(commit.query("/2").unwrap().is_empty());
+    assert!(commit.query("/0['version'").is_err());
+
+    let edited = commit.query_mut("/0/ostree.ref-binding/*", |value| {
+        *value = Variant::from(format!("{}-1", value.as_str().unwrap()));
+    });
+    assert_eq!(edited.unwrap(), 2);
+    let refs = commit.query("/0/ostree.ref-binding/*").unwrap();
+    assert_eq!(strings(refs), vec!["'stable-1'", "'latest-1'"]);
+
+    // Edits keep the array elements and dictionary values of the same type.
+    let before = commit.clone();
+    let error = commit
+        .query_mut("/0/ostree.ref-binding/0", |value| *value = Variant::U32(1))
+        .unwrap_err();
+    assert!(matches!(error.kind(), ErrorKind::SignatureMismatch(e, a) if e == "s" && a == "u"));
+    let error = commit
+        .query_mut("/1/7", |value| *value = Variant::from(vec!["seven"]))
+        .unwrap_err();
+    assert!(matches!(error.kind(), ErrorKind::SignatureMismatch(e, a) if e == "s" && a == "as"));
+    let error = commit
+        .query_mut("/0/size", |value| *value = Variant::from("big"))
+        .unwrap_err();
+    assert!(matches!(error.kind(), ErrorKind::SignatureMismatch(e, a) if e == "t" && a == "s"));
+    // Either all edits are applied, or none.
+    let error = commit.query_mut("/0/ostree.ref-binding/*", |value| {
+        if value.as_str() == Some("latest-1") {
+            *value = Variant::U32(1);
+        }
+    });
+    assert!(error.is_err());
+    assert_eq!(commit, before);
+    assert!(serde_gvariant::to_vec(&commit).is_ok());
+    assert_eq!(commit.query_mut("/0/missing", |_| {}).unwrap(), 0);
+
+    // Values in a `v` may take any type.
+    let edited = commit.query_mut("/0/version", |value| *value = Variant::U32(2));
+    assert_eq!(edited.unwrap(), 1);
+    let edited = commit.query_mut("/0/ostree.ref-binding", |value| *value = "stable".into());
+    assert_eq!(edited.unwrap(), 1);
+    assert_eq!(commit.query("/0/version").unwrap(), vec![&Variant::U32(2)]);
+    let metadata = commit.query("/0").unwrap()[0].clone();
+    let metadata = VarDict::try_from(metadata).unwrap();
+    let binding: Option<String> = metadata.get("ostree.ref-binding").unwrap();
+    assert_eq!(binding.as_deref(), Some("stable"));
+}
@@ -0,0 +1,136 @@
+#[macro_use]
+extern crate serde_derive;
+extern crate serde_gvariant;
+
+use serde_gvariant::{Variant, VariantRef, VariantType};
+use std::fs;
+
+#[derive(Serialize)]
+struct Record {
+    id: u32,
+    names: Vec<String>,
+    flags: u8,
+}
+
+/// Serialize a small (less than 256 bytes) `a{su}` dictionary.
+fn encode_dict_su(entries: &[(&str, u32)]) -> Vec<u8> {
+    let mut buf = vec![];
+    let mut offsets = vec![];
+    for (key, value) in entries {
+        while buf.len() % 4 != 0 {
+            buf.push(0x00);
+        }
+        let start = buf.len();
+        buf.extend_from_slice(key.as_bytes());
+        buf.push(0x00);
+        let key_end = buf.len() - start;
+        while buf.len() % 4 != 0 {
+            buf.push(0x00);
+        }
+        buf.extend_from_slice(&value.to_le_bytes());
+        buf.push(key_end as u8);
+        offsets.push(buf.len() as u8);
+    }
+    buf.extend(offsets);
+    assert!(buf.len() < 256);
+    buf
+}
+
+#[test]
+fn test_variant_ref_children() {
+    let value = Record {
+        id: 42,
+        names: vec!["foo".to_string(), "barbaz".to_string()],
+        flags: 7,
+    };
+    let encoded = serde_gvariant::to_vec(&value).unwrap();
+    let ty = VariantType::parse("(uasy)").unwrap();
+    let view = VariantRef::new(&encoded, ty);
+
+    assert_eq!(view.n_children().unwrap(), 3);
+    assert_eq!(view.child(0).unwrap().get::<u32>().unwrap(), 42);
+    assert_eq!(view.child(2).unwrap().get::<u8>().unwrap(), 7);
+    assert!(view.child(3).is_err());
+
+    let names = view.child(1).unwrap();
+    assert_eq!(names.variant_type().to_string(), "as");
+    assert_eq!(names.n_children().unwrap(), 2);
+    assert_eq!(names.child(1).unwrap().as_str().unwrap(), "barbaz");
+    assert_eq!(names.child(0).unwrap().as_str().unwrap(), "foo");
+    assert!(names.child(2).is_err());
+    assert!(names.child(0).unwrap().n_children().is_err());
+
+    assert_eq!(
+        view.to_variant().unwrap(),
+        Variant::from((42u32, vec!["foo", "barbaz"], 7u8))
+    );
+}
+
+#[test]
+fn test_variant_ref_boxed_and_maybe() {
+    // A `v` holding a `u`, then a just-`s`, then nothing.
+    let boxed = b"\x2a\x00\x00\x00\x00u";
+    let view = VariantRef::new(boxed, VariantType::Variant);
+    assert_eq!(view.n_children().unwrap(), 1);
+    let inner = view.child(0).unwrap();
+    assert_eq!(*inner.variant_type(), VariantType::U32);
+    assert_eq!(inner.get::<u32>().unwrap(), 42);
+
+    let ty = VariantType::parse("ms").unwrap();
+    let just = VariantRef::new(b"foo\x00\x00", ty.clone());
+    assert_eq!(just.n_children().unwrap(), 1);
+    assert_eq!(just.child(0).unwrap().as_str().unwrap(), "foo");
+    let nothing = VariantRef::new(b"", ty);
+    assert_eq!(nothing.n_children().unwrap(), 0);
+    assert!(nothing.child(0).is_err());
+}
+
+#[test]
+fn test_variant_ref_lookup() {
+    let keys: Vec<String> = (0..16).map(|i| format!("k{:02}", i)).collect();
+    let entries: Vec<(&str, u32)> = keys
+        .iter()
+        .enumerate()
+        .map(|(i, k)| (k.as_str(), i as u32 * 10))
+        .collect();
+    let encoded = encode_dict_su(&entries);
+    let ty = VariantType::parse("a{su}").unwrap();
+    let view = VariantRef::new(&encoded, ty);
+
+    assert_eq!(view.n_children().unwrap(), 16);
+    for i in [0u32, 1, 7, 14, 15] {
+        let key = format!("k{:02}", i);
+        let value = view.lookup(&key).unwrap().unwrap();
+        assert_eq!(value.get::<u32>().unwrap(), i * 10);
+    }
+    assert!(view.lookup("k16").unwrap().is_none());
+    assert!(view.lookup("").unwrap().is_none());
+
+    let entry = view.child(7).unwrap();
+    assert_eq!(entry.variant_type().to_string(), "{su}");
+    assert_eq!(entry.child(0).unwrap().as_str().unwrap(), "k07");
+    assert_eq!(entry.child(1).unwrap().get::<u32>().unwrap(), 70);
+
+    let not_dict = VariantRef::new(&encoded, VariantType::parse("a(su)").unwrap());
+    assert!(not_dict.lookup("k00").is_err());
+}
+
+#[test]
+fn test_variant_ref_ostree_commit() {
+    let content = fs::read("tests/fixtures/ostree/basic-01.commit").unwrap();
+    let ty = VariantType::parse("(a{sv}aya(say)sstayay)").unwrap();
+    let view = VariantRef::new(&content, ty).network_endian(true);
+
+    assert_eq!(view.n_children().unwrap(), 8);
+    assert_eq!(view.child(5).unwrap().get::<u64>().unwrap(), 1527716235);
+    assert_eq!(view.child(6).unwrap().n_children().unwrap(), 32);
+
+    let metadata = view.child(0).unwrap();
+    let refs = metadata.lookup("ostree.ref-binding").unwrap().unwrap();
+    assert_eq!(*refs.variant_type(), VariantType::Variant);
+    assert_eq!(
+        refs.get::<Vec<String>>().unwrap(),
+        vec!["master".to_string()]
+    );
+    assert!(metadata.lookup("ostree.missing").unwrap().is_none());
+}
@@ -0,0 +1,116 @@
+extern crate serde_gvariant;
+
+use serde_gvariant::errors::{self, ErrorKind};
+use serde_gvariant::{BasicValue, GVariantVisitor, Variant, VariantType};
+use std::ops::Range;
+
+/// Record callbacks as strings.
+#[derive(Default)]
+struct Recorder {
+    events: Vec<String>,
+    skip: Option<VariantType>,
+}
+
+impl<'a> GVariantVisitor<'a> for Recorder {
+    fn visit_basic(&mut self, range: Range<usize>, value: BasicValue<'a>) -> errors::Result<()> {
+        self.events.push(format!("{:?} {:?}", range, value));
+        Ok(())
+    }
+
+    fn begin_container(
+        &mut self,
+        range: Range<usize>,
+        ty: &VariantType,
+        len: usize,
+    ) -> errors::Result<bool> {
+        self.events
+            .push(format!("begin {} {:?} {}", ty, range, len));
+        Ok(self.skip.as_ref() != Some(ty))
+    }
+
+    fn visit_offsets(&mut self, range: Range<usize>, width: usize) -> errors::Result<()> {
+        self.events.push(format!("offsets {:?} {}", range, width));
+        Ok(())
+    }
+
+    fn end_container(&mut self, _range: Range<usize>, ty: &VariantType) -> errors::Result<()> {
+        self.events.push(format!("end {}", ty));
+        Ok(())
+    }
+}
+
+#[test]
+fn test_walk() {
+    let value = (7u32, vec!["ab", "c"], Variant::from(true));
+    let (encoded, signature) = serde_gvariant::config()
+        .canonical(true)
+        .serialize_with_signature(&value)
+        .unwrap();
+    let ty = VariantType::parse(&signature).unwrap();
+
+    let mut recorder = Recorder::default();
+    serde_gvariant::walk(&encoded, &ty, &mut recorder).unwrap();
+    assert_eq!(
+        recorder.events,
+        vec![
+            "begin (uasv) 0..20 3",
+            "0..4 U32(7)",
+            "begin as 4..11 2",
+            "4..7 String(\"ab\")",
+            "7..9 String(\"c\")",
+            "offsets 9..11 1",
+            "end as",
+            "begin v 16..19 1",
+            "16..17 Bool(true)",
+            "end v",
+            "offsets 19..20 1",
+            "end (uasv)",
+        ]
+    );
+
+    let mut recorder = Recorder {
+        skip: Some(VariantType::parse("as").unwrap()),
+        ..Recorder::default()
+    };
+    serde_gvariant::walk(&encoded, &ty, &mut recorder).unwrap();
+    assert_eq!(&recorder.events[2..4], &["begin as 4..11 2", "end as"]);
+
+    // Network endianness.
+    let cfg = serde_gvariant::config().network_endian(true);
+    let mut recorder = Recorder::default();
+    cfg.walk(&[0x00, 0x2a], &VariantType::U16, &mut recorder)
+        .unwrap();
+    assert_eq!(recorder.events, vec!["0..2 U16(42)"]);
+}
+
+#[test]
+fn test_walk_errors() {
+    // Visitor errors abort the walk, at the current value.
+    struct Find<'a>(&'a str);
+
+    impl<'a> GVariantVisitor<'a> for Find<'_> {
+        fn visit_basic(&mut self, _: Range<usize>, value: BasicValue<'a>) -> errors::Result<()> {
+            match value {
+                BasicValue::String(s) if s == self.0 => {
+                    Err(errors::Error::from(std::io::Error::other("found")))
+                }
+                _ => Ok(()),
+            }
+        }
+    }
+
+    let encoded = serde_gvariant::to_vec(&vec!["a", "b", "c"]).unwrap();
+    let ty = VariantType::parse("as").unwrap();
+    let err = serde_gvariant::walk(&encoded, &ty, &mut Find("b")).unwrap_err();
+    assert!(matches!(err.kind(), ErrorKind::Io(_)));
+    assert_eq!(err.path(), Some("[1]"));
+    assert_eq!(err.offset(), Some(2));
+    assert!(serde_gvariant::walk(&encoded, &ty, &mut Find("d")).is_ok());
+
+    // The first string lacks its nul terminator.
+    let encoded: Vec<u8> = vec![b'a', b'b', 0x00, 0x02, 0x03];
+    let err = serde_gvariant::walk(&encoded, &ty, &mut Find("d")).unwrap_err();
+    assert!(matches!(err.kind(), ErrorKind::InvalidFraming(_)));
+    assert_eq!(err.path(), Some("[0]"));
+    assert_eq!(err.offset(), Some(0));
+}